@@ -1,10 +1,12 @@
 #![allow(unused)]
 use crate::fs::handler;
 use crate::config::Config;
+use crate::module::handler::quota::QuotaState;
 use crate::msg::group_msg::send_group_msg;
+use crate::msg::image::ImageCache;
+use crate::msg::push::PushTracker;
 use crate::response::ApiResponse;
-use crate::socket::BotMessage;
-use channels::serdes::bincode;
+use crate::socket::{BotMessage, Handshake, WireCodec};
 use tokio::{
     sync::{
         mpsc,
@@ -12,38 +14,76 @@ use tokio::{
     },
     net::tcp::OwnedWriteHalf,
 };
+use std::collections::HashMap;
 use std::sync::Arc;
 
-pub type BotMessageSender = Arc<tokio::sync::Mutex<channels::Sender<BotMessage, channels::io::Tokio<OwnedWriteHalf>, bincode::Bincode>>>;
+pub type BotMessageSender = Arc<tokio::sync::Mutex<channels::Sender<BotMessage, channels::io::Tokio<OwnedWriteHalf>, WireCodec>>>;
 
 #[derive(Clone, Debug)]
 pub struct AppStatus {
     pub config: Arc<RwLock<Config>>,
     pub file_tx: Arc<RwLock<mpsc::Sender<handler::FileRequest>>>,
-    pub botmsg_tx: Arc<RwLock<Option<BotMessageSender>>>,
+    /// Connected cores by peer address
+    pub botmsg_tx: Arc<RwLock<HashMap<String, BotMessageSender>>>,
+    /// Handshake of each connected core, missing for cores speaking protocol v1
+    pub peer_handshakes: Arc<RwLock<HashMap<String, Handshake>>>,
+    pub push: Arc<PushTracker>,
+    pub images: Arc<ImageCache>,
+    /// API calls for a OneBot implementation connected over reverse WebSocket
+    pub onebot_ws: Arc<RwLock<Option<mpsc::UnboundedSender<serde_json::Value>>>>,
+    pub quota: Arc<QuotaState>,
 }
 
 impl AppStatus {
+    /// Sends to every connected core
     pub async fn send_bot_message(&self, message: BotMessage) -> Result<(), Box<dyn std::error::Error>> {
-        let sender_guard = self.botmsg_tx.read().await;
-        if let Some(sender) = sender_guard.as_ref() {
-            sender.lock().await.send(message).await
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
-        } else {
+        let senders: Vec<(String, BotMessageSender)> = self.botmsg_tx
+            .read()
+            .await
+            .iter()
+            .map(|(peer, sender)| (peer.clone(), sender.clone()))
+            .collect();
+        if senders.is_empty() {
             tracing::warn!("No bot connection available to send message");
-            Err("Bot external server offline".into())
+            return Err("Bot external server offline".into());
+        }
+
+        let mut delivered = false;
+        for (peer, sender) in senders {
+            match sender.lock().await.send(message.clone()).await {
+                Ok(_) => delivered = true,
+                Err(e) => tracing::error!("Failed to send message to {}: {}", peer, e),
+            }
+        }
+        if delivered {
+            Ok(())
+        } else {
+            Err("Failed to send message to any bot connection".into())
         }
     }
 
-    pub async fn update_bot_connection(&self, sender: BotMessageSender) {
-        let mut sender_guard = self.botmsg_tx.write().await;
-        *sender_guard = Some(sender);
-        tracing::info!("Bot connection updated in AppStatus");
+    pub async fn send_bot_message_to(&self, peer: &str, message: BotMessage) -> Result<(), Box<dyn std::error::Error>> {
+        let sender = self.botmsg_tx.read().await.get(peer).cloned();
+        match sender {
+            Some(sender) => sender.lock().await.send(message).await
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>),
+            None => Err(format!("Bot connection {} offline", peer).into()),
+        }
+    }
+
+    pub async fn update_bot_connection(&self, peer: &str, sender: BotMessageSender) {
+        self.botmsg_tx.write().await.insert(peer.to_string(), sender);
+        tracing::info!("Bot connection {} updated in AppStatus", peer);
+    }
+
+    pub async fn update_peer_handshake(&self, peer: &str, handshake: Handshake) {
+        self.peer_handshakes.write().await.insert(peer.to_string(), handshake);
     }
 
-    pub async fn clear_bot_connection(&self) {
-        let mut sender_guard = self.botmsg_tx.write().await;
-        *sender_guard = None;
-        tracing::info!("Bot connection cleared from AppStatus");
+    pub async fn clear_bot_connection(&self, peer: &str) {
+        self.botmsg_tx.write().await.remove(peer);
+        self.peer_handshakes.write().await.remove(peer);
+        self.images.forget_peer(peer).await;
+        tracing::info!("Bot connection {} cleared from AppStatus", peer);
     }
 }
@@ -1,9 +1,11 @@
 #![allow(unused)]
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{
     Arc
 };
 use crate::i18n;
+use crate::msg::prelude::MessageEvent;
 use crate::response::ApiResponse;
 
 pub const CONFIG_PATH: &str = "config.json";
@@ -13,23 +15,138 @@ pub struct Config {
     pub bot_config: BotConfig,
     pub backend_config: BackendConfig,
     pub pass_api_config: PassApiConfig,
+    /// 不配置则不发送漫游公告
+    #[serde(default)]
+    pub roaming_announce_config: Option<RoamingAnnounceConfig>,
+    #[serde(default)]
+    pub quota_config: QuotaConfig,
+    /// 不配置则不启动HTTP服务
+    #[serde(default)]
+    pub http_config: Option<HttpConfig>,
+    /// 不配置则不发送空间天气警报
+    #[serde(default)]
+    pub space_weather_alert_config: Option<SpaceWeatherAlertConfig>,
+    /// 不配置则不直接接收 OneBot 事件，需要同时配置 http_config
+    #[serde(default)]
+    pub onebot_config: Option<OneBotConfig>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BotConfig {
     pub sse_url: String,
+    /// client 模式下为 core 的地址，server 模式下为监听地址
     pub listen_addr: String,
     pub qq_id: String,
     pub group_id: Vec<u64>,
     pub admin_id: Vec<u64>,
+    /// 允许私聊的 QQ，不配置则所有人都可以私聊，管理员总是可以
+    #[serde(default)]
+    pub private_user_id: Option<Vec<u64>>,
+    #[serde(default)]
+    pub link_mode: LinkMode,
+    /// 同时连接的其他 core (client) 或额外的监听地址 (server)，如测试环境
+    #[serde(default)]
+    pub extra_addr: Vec<String>,
+    /// 两端需要一致，json 可以容忍字段增减，便于 core 和 server 分别升级
+    #[serde(default)]
+    pub codec: LinkCodec,
+    /// 连接后先发送握手，core 都已支持协议 v2 时才可以打开；默认等 core 先发送握手再回复，旧版 core 无法解析握手帧
+    #[serde(default)]
+    pub handshake_first: bool,
+    /// core 不在同一台机器上时设为 inline，图片以字节发送而不是 runtime_data 下的路径
+    #[serde(default)]
+    pub image_transfer: ImageTransfer,
+    /// inline 时单张图片的上限，字节，超过时仍然发送路径
+    #[serde(default = "default_inline_image_limit")]
+    pub inline_image_limit: usize,
+    /// 投递主动消息的 core，握手中的名称或 listen_addr/extra_addr 中的地址
+    /// 不配置时只有一个支持 push 的 core 连接着才经由它投递，有多个 (如测试环境) 时直接调用 OneBot API
+    #[serde(default)]
+    pub push_core: Option<String>,
+}
+
+fn default_inline_image_limit() -> usize {
+    4 * 1024 * 1024
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageTransfer {
+    /// `file:///server_` 路径，core 和 server 共用 runtime_data
+    #[default]
+    Path,
+    /// PNG 字节，旧版 `Chat` 回复中为 `base64://`
+    Inline,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkCodec {
+    #[default]
+    Bincode,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    /// 主动连接 core
+    #[default]
+    Client,
+    /// 监听并等待 core 连接
+    Server,
+    /// 不连接 core，只通过 onebot_config 直接接收消息
+    None,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BackendConfig {
     pub timeout: u64,
     pub concurrent_limit: u64,
-    /// 该参数中的群聊开放过境查询相关模块
+    /// core 的 Request 没有给出 timeout_ms 时的处理超时，秒，0 为不限制
+    #[serde(default)]
+    pub request_timeout_secs: u64,
+    /// 该参数中的群聊开放过境查询 (/pass) 并接收过境提醒，与 pass_predict_user_id 都不配置时不启用过境预测
     pub pass_predict_group_id: Option<Vec<u64>>,
+    /// 该参数中的私聊用户开放过境查询 (/pass)，并私聊接收过境提醒，只配置了群时私聊不限制查询、不发送提醒
+    #[serde(default)]
+    pub pass_predict_user_id: Option<Vec<u64>>,
+}
+
+/// 群聊看群号，私聊看 QQ，列表不配置时不限制
+pub fn chat_in(payload: &MessageEvent, groups: &Option<Vec<u64>>, users: &Option<Vec<u64>>) -> bool {
+    let (list, id) = if payload.is_private() {
+        (users, payload.user_id)
+    } else {
+        (groups, payload.group_id)
+    };
+    list.as_ref().is_none_or(|list| list.contains(&id))
+}
+
+impl BotConfig {
+    /// Whether the bot answers in this chat at all
+    pub fn chat_allowed(&self, payload: &MessageEvent) -> bool {
+        if payload.is_private() {
+            self.admin_id.contains(&payload.user_id)
+                || chat_in(payload, &None, &self.private_user_id)
+        } else {
+            self.group_id.contains(&payload.group_id)
+        }
+    }
+}
+
+impl Config {
+    /// Pass prediction needs the API host and at least one chat list, nothing is polled or sent otherwise
+    pub fn pass_predict_configured(&self) -> bool {
+        !self.pass_api_config.host.is_empty()
+            && (self.backend_config.pass_predict_group_id.is_some() || self.backend_config.pass_predict_user_id.is_some())
+    }
+}
+
+impl BackendConfig {
+    pub fn pass_predict_enabled(&self, payload: &MessageEvent) -> bool {
+        chat_in(payload, &self.pass_predict_group_id, &self.pass_predict_user_id)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -41,6 +158,104 @@ pub struct PassApiConfig {
     pub alt: f64,
     pub day: u32,
     pub min_elevation: u32,
+    /// 卫星当前位置的接口，返回 n2yo `positions` 格式，`{id}` `{lat}` `{lon}` `{alt}` `{apikey}` 会被替换
+    /// 如 `https://api.n2yo.com/rest/v1/satellite/positions/{id}/{lat}/{lon}/{alt}/1&apiKey={apikey}`，不配置则不能按卫星覆盖范围查询漫游
+    #[serde(default)]
+    pub positions_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoamingAnnounceConfig {
+    /// 漫游信息新增或变更时公告到这些群
+    pub group_id: Vec<u64>,
+    /// 开启后不再即时公告，每天汇总一次新出现的稀有网格
+    #[serde(default)]
+    pub digest: bool,
+    /// 汇总发送时间，北京时间整点，0-23
+    #[serde(default = "default_digest_hour", deserialize_with = "deserialize_hour")]
+    pub digest_hour: u32,
+}
+
+fn default_digest_hour() -> u32 {
+    20
+}
+
+/// An hour of the day, rejected at load instead of never matching the clock
+fn deserialize_hour<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let hour = u32::deserialize(deserializer)?;
+    if hour >= 24 {
+        return Err(serde::de::Error::custom(format!("digest_hour must be 0-23, got {}", hour)));
+    }
+    Ok(hour)
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    /// key 为命令名 (`query`) 或命令名.子命令 (`roaming.add`)，覆盖内置的默认限制
+    #[serde(default)]
+    pub limits: HashMap<String, QuotaLimit>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuotaLimit {
+    /// 窗口内最多使用次数
+    pub max: usize,
+    /// 滑动窗口长度，秒
+    pub window_secs: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// 监听地址，如 0.0.0.0:8080
+    pub listen_addr: String,
+    /// 对外访问的地址，用于在回复中给出链接，如 https://rinko.example.com
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// `/api` 的访问令牌，请求带 `Authorization: Bearer <令牌>` 或 `?token=`，为空时不开放
+    #[serde(default)]
+    pub api_tokens: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OneBotConfig {
+    /// OneBot 实现连接反向 WebSocket 或上报事件时带的 access_token，必须配置，为空时不接收事件
+    #[serde(default)]
+    pub access_token: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpaceWeatherAlertConfig {
+    /// 超过阈值时警报到这些群
+    pub group_id: Vec<u64>,
+    /// K 指数达到该值时警报
+    #[serde(default = "default_k_index_alert")]
+    pub k_index: f64,
+    /// X 射线达到该级别时警报，如 X 或 M5
+    #[serde(default = "default_xray_class_alert", deserialize_with = "deserialize_xray_class")]
+    pub xray_class: String,
+}
+
+fn default_k_index_alert() -> f64 {
+    5.0
+}
+
+fn default_xray_class_alert() -> String {
+    "X".to_string()
+}
+
+/// A GOES class, rejected at load instead of never triggering an alert
+fn deserialize_xray_class<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let class = String::deserialize(deserializer)?;
+    match crate::module::solar_image::space_weather::xray_flux(&class) {
+        Some(flux) if flux.is_finite() && flux > 0.0 => Ok(class),
+        _ => Err(serde::de::Error::custom(format!("xray_class must be a GOES class such as X or M5, got {:?}", class))),
+    }
 }
 
 pub trait ConfigProvider: Send + Sync + 'static {
@@ -58,6 +58,12 @@ pub enum FileRequest {
         data: FileData,
         responder: oneshot::Sender<Result<(), anyhow::Error>>,
     },
+    /// Append text to the end of the file, creates the file if missing
+    Append {
+        path: PathBuf,
+        data: String,
+        responder: oneshot::Sender<Result<(), anyhow::Error>>,
+    },
     Exists {
         path: PathBuf,
         responder: oneshot::Sender<Result<bool, anyhow::Error>>,
@@ -139,6 +145,25 @@ pub async fn file_manager(mut rx: mpsc::Receiver<FileRequest>) {
                 }
                 let _ = responder.send(result);
             }
+            FileRequest::Append { path, data, responder } => {
+                let result = async {
+                    if let Some(parent) = path.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    let mut file = tokio::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&path)
+                        .await?;
+                    file.write_all(data.as_bytes()).await?;
+                    Ok(())
+                }.await;
+
+                if let Err(ref e) = result {
+                    tracing::error!("{} {}: {}", i18n::text("file_write_error"), path.display(), e);
+                }
+                let _ = responder.send(result);
+            }
             FileRequest::Exists { path, responder } => {
                 let result = async {
                     let metadata = tokio::fs::metadata(&path).await?;
@@ -293,6 +318,57 @@ pub async fn write_file(
         tracing::error!("Failed to send file write request: {}", e);
         return Err(anyhow::anyhow!("Failed to send file write request: {}", e));
     }
+    drop(tx_filerequest);
+
+    // the write has happened, or failed, once the file manager answers
+    match rx.await {
+        Ok(result) => result,
+        Err(e) => Err(anyhow::anyhow!("Failed to receive file write response: {}", e)),
+    }
+}
+
+pub async fn append_file(
+    tx_filerequest: Arc<RwLock<tokio::sync::mpsc::Sender<FileRequest>>>,
+    path: String,
+    data: String,
+) -> anyhow::Result<()> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let request = FileRequest::Append {
+        path: path.into(),
+        data,
+        responder: tx,
+    };
+
+    let tx_filerequest = tx_filerequest.write().await;
+    if let Err(e) = tx_filerequest.send(request).await {
+        return Err(anyhow::anyhow!("Failed to send file append request: {}", e));
+    }
+    drop(tx_filerequest);
 
-    Ok(())
-}
\ No newline at end of file
+    match rx.await {
+        Ok(result) => result,
+        Err(e) => Err(anyhow::anyhow!("Failed to receive file append response: {}", e)),
+    }
+}
+
+pub async fn delete_file(
+    tx_filerequest: Arc<RwLock<tokio::sync::mpsc::Sender<FileRequest>>>,
+    path: String,
+) -> anyhow::Result<()> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let request = FileRequest::Delete {
+        path: path.into(),
+        responder: tx,
+    };
+
+    let tx_filerequest = tx_filerequest.write().await;
+    if let Err(e) = tx_filerequest.send(request).await {
+        return Err(anyhow::anyhow!("Failed to send file delete request: {}", e));
+    }
+    drop(tx_filerequest);
+
+    match rx.await {
+        Ok(result) => result,
+        Err(e) => Err(anyhow::anyhow!("Failed to receive file delete response: {}", e)),
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    app_status::AppStatus,
+    fs::handler::{check_file_exists, load_file, FileData, FileFormat},
+    http::token_matches,
+    module::{
+        amsat::{
+            prelude::{SatelliteFileFormat, OFFICIAL_REPORT_DATA},
+            user_report::read_user_report_file,
+        },
+        pass_query::sat_pass_predict::CACHE_FILE as PASS_CACHE_PATH,
+        tools::{roaming::load_roaming_list, roaming_io::export_entries},
+    },
+    response::json_response,
+};
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use chrono::Utc;
+
+#[derive(Debug, Deserialize)]
+pub struct TokenQuery {
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SatelliteQuery {
+    /// case-insensitive substring of the satellite name
+    sat: Option<String>,
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, json_response::<()>(false, Some(message.into()), None)).into_response()
+}
+
+fn name_matches(name: &str, query: &Option<String>) -> bool {
+    match query {
+        Some(query) => name.to_lowercase().contains(&query.to_lowercase()),
+        None => true,
+    }
+}
+
+/// `Authorization: Bearer <token>` or `?token=`, checked against `http_config.api_tokens`
+pub async fn require_token(
+    State(app_status): State<Arc<AppStatus>>,
+    Query(query): Query<TokenQuery>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let tokens = {
+        let config_guard = app_status.config.read().await;
+        config_guard.http_config.as_ref().map(|c| c.api_tokens.clone()).unwrap_or_default()
+    };
+    if tokens.is_empty() {
+        return error_response(StatusCode::NOT_FOUND, "API is not enabled");
+    }
+
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    match bearer.or(query.token) {
+        // every token is compared, a match does not end the loop early
+        Some(token) if tokens.iter().fold(false, |found, t| token_matches(&token, t) | found) => next.run(request).await,
+        Some(_) => error_response(StatusCode::FORBIDDEN, "Invalid token"),
+        None => error_response(StatusCode::UNAUTHORIZED, "Missing token"),
+    }
+}
+
+/// `GET /api/satellites[?sat=]`, the latest official status of each satellite
+pub async fn satellites(
+    State(app_status): State<Arc<AppStatus>>,
+    Query(query): Query<SatelliteQuery>,
+) -> Response {
+    let data = match load_file(app_status.file_tx.clone(), OFFICIAL_REPORT_DATA.to_string(), FileFormat::Json).await {
+        Ok(FileData::Json(data)) => data,
+        Ok(_) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Unexpected file format"),
+        Err(e) => {
+            tracing::error!("Failed to read official report data: {}", e);
+            return error_response(StatusCode::SERVICE_UNAVAILABLE, "Satellite status is not available yet");
+        }
+    };
+    let satellites: Vec<SatelliteFileFormat> = match serde_json::from_value(data) {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to parse official report data: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to parse satellite status");
+        }
+    };
+
+    let satellites: Vec<SatelliteFileFormat> = satellites
+        .into_iter()
+        .filter(|s| name_matches(&s.name, &query.sat))
+        .collect();
+    json_response(true, None, Some(satellites)).into_response()
+}
+
+/// `GET /api/passes[?sat=]`, upcoming passes from the prediction cache
+pub async fn passes(
+    State(app_status): State<Arc<AppStatus>>,
+    Query(query): Query<SatelliteQuery>,
+) -> Response {
+    if !check_file_exists(app_status.file_tx.clone(), PASS_CACHE_PATH.to_string()).await {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "Pass predictions are not available");
+    }
+    let cache = match load_file(app_status.file_tx.clone(), PASS_CACHE_PATH.to_string(), FileFormat::Json).await {
+        Ok(FileData::Json(serde_json::Value::Object(cache))) => cache,
+        Ok(_) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Unexpected pass cache format"),
+        Err(e) => {
+            tracing::error!("Failed to read pass cache: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read pass predictions");
+        }
+    };
+
+    let now = Utc::now().timestamp();
+    let mut satellites = serde_json::Map::new();
+    for (name, mut data) in cache {
+        let satname = data["satname"].as_str().unwrap_or_default().to_string();
+        if !name_matches(&name, &query.sat) && !name_matches(&satname, &query.sat) {
+            continue;
+        }
+        if let Some(passes) = data["passes"].as_array_mut() {
+            passes.retain(|p| p["endUTC"].as_i64().unwrap_or(0) > now);
+        }
+        satellites.insert(name, data);
+    }
+    json_response(true, None, Some(satellites)).into_response()
+}
+
+/// `GET /api/roaming`, the same entries as `/roaming.json`
+pub async fn roaming(State(app_status): State<Arc<AppStatus>>) -> Response {
+    match load_roaming_list(&app_status).await {
+        Ok(data) => json_response(true, None, Some(export_entries(&data, Utc::now()))).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to read roaming data: {}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read roaming data")
+        }
+    }
+}
+
+/// `GET /api/reports/pending[?sat=]`, user reports waiting for submission or held by a conflict
+/// - who reported or released a hold is left out, callsigns and statuses stay
+pub async fn pending_reports(
+    State(app_status): State<Arc<AppStatus>>,
+    Query(query): Query<SatelliteQuery>,
+) -> Response {
+    let reports = match read_user_report_file(&app_status).await {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to read user reports: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read user reports");
+        }
+    };
+
+    let reports: Vec<SatelliteFileFormat> = reports
+        .into_iter()
+        .filter(|s| !s.data.is_empty() && name_matches(&s.name, &query.sat))
+        .map(|mut s| {
+            for element in &mut s.data {
+                for report in &mut element.report {
+                    report.source = None;
+                }
+                // a released hold no longer holds anything
+                element.hold = element.hold.take().filter(|hold| hold.released_by.is_none());
+                if let Some(hold) = &mut element.hold {
+                    for conflict in &mut hold.conflicts {
+                        conflict.user_id = None;
+                    }
+                }
+            }
+            s
+        })
+        .collect();
+    json_response(true, None, Some(reports)).into_response()
+}
@@ -0,0 +1,47 @@
+use crate::app_status::AppStatus;
+use axum::{middleware, routing::{get, post}, Router};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+pub mod api;
+pub mod onebot;
+pub mod roaming;
+
+pub fn build_router(app_status: Arc<AppStatus>) -> Router {
+    let api = Router::new()
+        .route("/satellites", get(api::satellites))
+        .route("/passes", get(api::passes))
+        .route("/roaming", get(api::roaming))
+        .route("/reports/pending", get(api::pending_reports))
+        .route_layer(middleware::from_fn_with_state(app_status.clone(), api::require_token));
+
+    Router::new()
+        .route("/roaming.csv", get(roaming::roaming_csv))
+        .route("/roaming.ics", get(roaming::roaming_ics))
+        .route("/roaming.json", get(roaming::roaming_json))
+        .nest("/api", api)
+        .route("/onebot/ws", get(onebot::reverse_ws))
+        .route("/onebot/event", post(onebot::http_event))
+        .with_state(app_status)
+}
+
+/// Constant time, so the response time does not tell how much of a guessed token was right
+pub fn token_matches(given: &str, expected: &str) -> bool {
+    given.as_bytes().ct_eq(expected.as_bytes()).into()
+}
+
+/// Runs until the listener fails, the bot keeps working without it
+pub async fn serve(app_status: Arc<AppStatus>, listen_addr: String) {
+    let listener = match tokio::net::TcpListener::bind(&listen_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Failed to bind HTTP server on {}: {}", listen_addr, e);
+            return;
+        }
+    };
+    tracing::info!("HTTP server listening on {}", listen_addr);
+
+    if let Err(e) = axum::serve(listener, build_router(app_status)).await {
+        tracing::error!("HTTP server stopped: {}", e);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{
+    app_status::AppStatus,
+    config::OneBotConfig,
+    http::token_matches,
+    module::handler::router::{bot_message_handler, get_command_and_args},
+    msg::{
+        group_msg::post_onebot_message,
+        prelude::{IntoBinMessageEvent, MessageElement, MessageEvent},
+        push::{PushMessage, PushTarget},
+    },
+    socket::MsgContent,
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Deserialize)]
+pub struct AccessTokenQuery {
+    access_token: Option<String>,
+}
+
+/// `Authorization: Bearer <token>` (or `Token <token>`) or `?access_token=`, as OneBot v11 sends it
+/// - events carry the user id the commands trust, so there is no unauthenticated mode
+fn authorized(headers: &HeaderMap, query: &AccessTokenQuery, config: &OneBotConfig) -> bool {
+    let Some(expected) = config.access_token.as_deref().filter(|t| !t.is_empty()) else {
+        return false;
+    };
+    let header_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer ").or_else(|| value.strip_prefix("Token ")))
+        .map(str::trim);
+    header_token
+        .or(query.access_token.as_deref())
+        .is_some_and(|token| token_matches(token, expected))
+}
+
+/// The status to answer with if the adapter is off, has no token or the token is wrong
+async fn check_access(
+    app_status: &AppStatus,
+    headers: &HeaderMap,
+    query: &AccessTokenQuery,
+) -> Result<(), StatusCode> {
+    let config = {
+        let config_guard = app_status.config.read().await;
+        config_guard.onebot_config.clone()
+    };
+    match config {
+        None => Err(StatusCode::NOT_FOUND),
+        Some(config) if config.access_token.as_deref().is_none_or(str::is_empty) => {
+            tracing::warn!("OneBot adapter is disabled until onebot_config.access_token is set");
+            Err(StatusCode::NOT_FOUND)
+        }
+        Some(config) if !authorized(headers, query, &config) => Err(StatusCode::UNAUTHORIZED),
+        Some(_) => Ok(()),
+    }
+}
+
+/// `POST /onebot/event`, HTTP event reporting, replies go through the OneBot API
+pub async fn http_event(
+    State(app_status): State<Arc<AppStatus>>,
+    Query(query): Query<AccessTokenQuery>,
+    headers: HeaderMap,
+    Json(event): Json<serde_json::Value>,
+) -> Response {
+    if let Err(status) = check_access(&app_status, &headers, &query).await {
+        return status.into_response();
+    }
+    tokio::spawn(handle_event(app_status, event));
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// `GET /onebot/ws`, reverse WebSocket in the universal role
+/// - events arrive here, API calls from `post_onebot_message` are sent back while it is open
+pub async fn reverse_ws(
+    State(app_status): State<Arc<AppStatus>>,
+    Query(query): Query<AccessTokenQuery>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if let Err(status) = check_access(&app_status, &headers, &query).await {
+        return status.into_response();
+    }
+    let self_id = headers
+        .get("X-Self-ID")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+    ws.on_upgrade(move |socket| run_reverse_ws(socket, self_id, app_status))
+}
+
+async fn run_reverse_ws(socket: WebSocket, self_id: String, app_status: Arc<AppStatus>) {
+    tracing::info!("OneBot {} connected over reverse WebSocket", self_id);
+    let (mut sink, mut stream) = socket.split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<serde_json::Value>();
+    *app_status.onebot_ws.write().await = Some(tx.clone());
+
+    let writer = tokio::spawn(async move {
+        while let Some(action) = rx.recv().await {
+            if let Err(e) = sink.send(Message::Text(action.to_string().into())).await {
+                tracing::error!("Failed to send OneBot action: {}", e);
+                break;
+            }
+        }
+    });
+
+    while let Some(frame) = stream.next().await {
+        let text = match frame {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!("OneBot {} WebSocket error: {}", self_id, e);
+                break;
+            }
+        };
+        let value: serde_json::Value = match serde_json::from_str(text.as_str()) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!("Invalid frame from OneBot {}: {}", self_id, e);
+                continue;
+            }
+        };
+
+        // API call results carry a retcode, everything else is an event
+        if let Some(retcode) = value.get("retcode").and_then(|r| r.as_i64()) {
+            if retcode != 0 {
+                tracing::error!("OneBot action failed: {}", value);
+            }
+            continue;
+        }
+        tokio::spawn(handle_event(app_status.clone(), value));
+    }
+
+    writer.abort();
+    // a newer connection may have replaced this one already
+    let mut current = app_status.onebot_ws.write().await;
+    if current.as_ref().is_some_and(|current| current.same_channel(&tx)) {
+        *current = None;
+    }
+    tracing::info!("OneBot {} disconnected", self_id);
+}
+
+/// Messages from allowed chats that start with a command go through the router, the reply goes back to the same chat
+async fn handle_event(app_status: Arc<AppStatus>, event: serde_json::Value) {
+    if event.get("post_type").and_then(|t| t.as_str()) != Some("message") {
+        return;
+    }
+    let event: MessageEvent = match serde_json::from_value(event) {
+        Ok(event) => event,
+        Err(e) => {
+            tracing::warn!("Unsupported OneBot message event: {}", e);
+            return;
+        }
+    };
+    if !matches!(event.message_type.as_str(), "group" | "private") || event.user_id == event.self_id {
+        return;
+    }
+    if !app_status.config.read().await.bot_config.chat_allowed(&event) {
+        return;
+    }
+
+    let text: String = event
+        .message
+        .iter()
+        .filter_map(|elem| match elem {
+            MessageElement::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    let (command, _) = get_command_and_args(text.trim());
+    if command.is_empty() {
+        return;
+    }
+
+    let target = PushTarget::reply_to(&event);
+    let content = MsgContent {
+        command: Some(command),
+        payload: Some(event.into_bin_message_event()),
+        message: None,
+        api_response: None,
+    };
+    let reply = bot_message_handler(content, app_status.clone()).await;
+    if let Some(segments) = reply.into_segments() {
+        post_onebot_message(&PushMessage { target, segments }, &app_status).await;
+    }
+}
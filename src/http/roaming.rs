@@ -0,0 +1,47 @@
+use crate::{
+    app_status::AppStatus,
+    module::tools::{
+        roaming::load_roaming_list,
+        roaming_io::{export_roaming, ExportFormat},
+    },
+};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use chrono::Utc;
+
+async fn roaming_export(app_status: &AppStatus, format: ExportFormat) -> Response {
+    let content = match load_roaming_list(app_status).await {
+        Ok(data) => export_roaming(&data, format, Utc::now()),
+        Err(e) => Err(e),
+    };
+
+    match content {
+        Ok(content) => (
+            [
+                (header::CONTENT_TYPE, format.content_type().to_string()),
+                (header::CONTENT_DISPOSITION, format!("inline; filename=\"roaming.{}\"", format.extension())),
+            ],
+            content,
+        ).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to export roaming data: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to export roaming data").into_response()
+        }
+    }
+}
+
+pub async fn roaming_csv(State(app_status): State<Arc<AppStatus>>) -> Response {
+    roaming_export(&app_status, ExportFormat::Csv).await
+}
+
+pub async fn roaming_ics(State(app_status): State<Arc<AppStatus>>) -> Response {
+    roaming_export(&app_status, ExportFormat::Ics).await
+}
+
+pub async fn roaming_json(State(app_status): State<Arc<AppStatus>>) -> Response {
+    roaming_export(&app_status, ExportFormat::Json).await
+}
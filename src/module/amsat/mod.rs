@@ -1,4 +1,6 @@
 pub mod user_report;
 pub mod prelude;
 pub mod official_report;
+pub mod report_audit;
+pub mod report_stats;
 pub mod amsat_scraper;
\ No newline at end of file
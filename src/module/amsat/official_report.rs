@@ -1,9 +1,9 @@
 use crate::{
     app_status::AppStatus, 
     fs::{self, handler::*},
-    module::{amsat::{amsat_scraper, prelude::*}, tools::render::render_satstatus_data},
-    msg::{group_msg::send_group_message_to_multiple_groups, prelude::MessageEvent},
-    response::ApiResponse,
+    module::{amsat::{amsat_scraper, prelude::*, report_stats}, tools::render::render_satstatus_data},
+    msg::{group_msg::send_group_message_to_multiple_groups, prelude::MessageEvent, segment::Segment},
+    response::{ApiResponse, Reply},
 };
 use serde::{Deserialize, Serialize};
 use tokio::{
@@ -50,7 +50,7 @@ async fn get_amsat_data(
                         attempt,
                         MAX_RETRIES
                     );
-                    let response: ApiResponse<Vec<String>> = ApiResponse::error(response_msg);
+                    let response: Reply = ApiResponse::error(response_msg);
                     send_group_message_to_multiple_groups(response, &app_status).await;
                 }
             }
@@ -253,7 +253,7 @@ pub fn pack_satellite_data(reports: Vec<SatStatus>) -> Option<SatelliteFileForma
     sorted.sort_by(|a, b| b.0.cmp(&a.0));
 
     let data: Vec<SatelliteFileElement> = sorted.into_iter()
-        .map(|(time, report)| SatelliteFileElement { time, report })
+        .map(|(time, report)| SatelliteFileElement { time, report, hold: None })
         .collect();
 
     let last_update_time = Utc::now().to_rfc3339();
@@ -340,7 +340,7 @@ pub fn update_satellite_data(
 
     // Convert back to Vec<SatelliteFileElement>
     let updated_data: Vec<SatelliteFileElement> = existing_data.into_iter()
-        .map(|(time, report)| SatelliteFileElement { time, report })
+        .map(|(time, report)| SatelliteFileElement { time, report, hold: None })
         .collect();
 
     // sort the updated data by time, descending
@@ -357,7 +357,7 @@ pub fn update_satellite_data(
 /// Scheduled task
 pub async fn amsat_data_handler(
     app_status: &Arc<AppStatus>,
-) -> ApiResponse<Vec<String>> {
+) -> Reply {
     let mut response = ApiResponse::empty();
     let tx_filerequest = app_status.file_tx.clone();
 
@@ -486,11 +486,16 @@ pub async fn amsat_data_handler(
         }
     }
 
+    // stats only, a failure here should not fail the update
+    if let Err(e) = report_stats::record_official_reports(app_status, &official_report_data).await {
+        tracing::error!("Failed to record official reports for stats: {}", e);
+    }
+
     response.success = true;
     if !response_data.is_empty() {
         response_data.insert(0, "卫星状态更新了喵~".to_string());
     }
-    response.data = Some(response_data);
+    response.data = Some(response_data.into_iter().map(Segment::from).collect());
     response
 }
 
@@ -566,7 +571,7 @@ pub async fn query_satellite_status(
     input: &str,
     app_status: &Arc<AppStatus>,
     payload: &MessageEvent
-) -> ApiResponse<Vec<String>> {
+) -> Reply {
     tracing::debug!("Querying satellite status for input: {}", input);
     let mut response = ApiResponse::empty();
     let tx_filerequest = app_status.file_tx.clone();
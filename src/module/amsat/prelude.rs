@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use strsim::jaro_winkler;
 use chrono::{DateTime, FixedOffset, TimeZone, Utc, NaiveDateTime};
+use crate::msg::prelude::MessageEvent;
 
 pub const USER_REPORT_DATA: &str = "runtime_data/user_report_data.json";
 /// stores the official report data
@@ -18,6 +19,9 @@ pub struct SatStatus {
     /// Mutable format!!! format depends on usage
     pub report: String,
     pub grid_square: String,
+    /// Who typed the report, only present for local user reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ReportSource>,
 }
 
 impl Default for SatStatus {
@@ -28,6 +32,30 @@ impl Default for SatStatus {
             callsign: String::new(),
             report: ReportStatus::Grey.to_string(),
             grid_square: String::new(),
+            source: None,
+        }
+    }
+}
+
+/// Chat message a user report came from
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReportSource {
+    pub user_id: u64,
+    pub group_id: u64,
+    pub message_id: u64,
+    /// group card at the time of reporting
+    pub card: String,
+    pub is_admin: bool,
+}
+
+impl ReportSource {
+    pub fn from_payload(payload: &MessageEvent, admin_list: &[u64]) -> Self {
+        ReportSource {
+            user_id: payload.user_id,
+            group_id: payload.group_id,
+            message_id: payload.message_id,
+            card: payload.sender.card.clone(),
+            is_admin: admin_list.contains(&payload.user_id),
         }
     }
 }
@@ -37,6 +65,85 @@ pub struct SatelliteFileElement {
     /// Mutable format!!! format depends on usage, RFC3339 in most cases
     pub time: String,               // time block, e.g., "2025-08-03T13:30:00Z"
     pub report: Vec<SatStatus>,     // list of reports for this time block
+    /// Only used by local user reports, holds submission while reports conflict
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold: Option<ReportHold>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictingReport {
+    pub callsign: String,
+    pub status: String,
+    pub user_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportHold {
+    /// RFC3339
+    pub created_at: String,
+    pub conflicts: Vec<ConflictingReport>,
+    /// callsigns whose reporters confirmed their status
+    pub confirmed: Vec<String>,
+    /// admin who released the hold
+    pub released_by: Option<u64>,
+}
+
+impl ReportHold {
+    pub fn new(reports: &[SatStatus]) -> Self {
+        ReportHold {
+            created_at: Utc::now().to_rfc3339(),
+            conflicts: reports.iter().map(|r| ConflictingReport {
+                callsign: r.callsign.clone(),
+                status: r.report.clone(),
+                user_id: r.source.as_ref().map(|s| s.user_id),
+            }).collect(),
+            confirmed: Vec::new(),
+            released_by: None,
+        }
+    }
+
+    /// Rebuild the conflict list, confirmations stay valid only if the status did not change
+    pub fn refresh(&mut self, reports: &[SatStatus]) {
+        let previous = std::mem::replace(self, ReportHold::new(reports));
+        self.created_at = previous.created_at;
+        self.released_by = previous.released_by;
+        self.confirmed = previous.confirmed.into_iter()
+            .filter(|callsign| {
+                let old = previous.conflicts.iter().find(|c| &c.callsign == callsign);
+                let new = self.conflicts.iter().find(|c| &c.callsign == callsign);
+                matches!((old, new), (Some(o), Some(n)) if o.status == n.status)
+            })
+            .collect();
+    }
+
+    pub fn pending_callsigns(&self) -> Vec<&str> {
+        self.conflicts.iter()
+            .map(|c| c.callsign.as_str())
+            .filter(|c| !self.confirmed.iter().any(|confirmed| confirmed == c))
+            .collect()
+    }
+
+    /// Reporters still asked to confirm, once each, none after an admin released the hold
+    pub fn pending_reporters(&self) -> Vec<u64> {
+        if self.released_by.is_some() {
+            return Vec::new();
+        }
+        let pending = self.pending_callsigns();
+        let mut reporters: Vec<u64> = Vec::new();
+        for conflict in self.conflicts.iter().filter(|c| pending.contains(&c.callsign.as_str())) {
+            if let Some(user_id) = conflict.user_id
+                && !reporters.contains(&user_id)
+            {
+                reporters.push(user_id);
+            }
+        }
+        reporters
+    }
+
+    /// Submission waits while this returns true
+    pub fn is_active(&self) -> bool {
+        self.released_by.is_none() && !self.pending_callsigns().is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
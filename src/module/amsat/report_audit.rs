@@ -0,0 +1,213 @@
+use crate::{
+    app_status::AppStatus,
+    fs::handler::*,
+    module::amsat::{official_report::load_satellites_list, prelude::*},
+    msg::prelude::MessageEvent,
+    response::{ApiResponse, Reply},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+
+/// Append-only, one JSON object per line
+pub const USER_REPORT_AUDIT_LOG: &str = "runtime_data/user_report_audit.jsonl";
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+const MAX_HISTORY_LIMIT: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AuditAction {
+    /// new report added to a pass template
+    Add,
+    /// report replaced by the same callsign
+    Update,
+    Remove,
+    /// submitted to AMSAT by the scheduler or `/spot`
+    Submit,
+    SubmitFailed,
+    /// submission held because local reports conflict
+    Hold,
+    /// reporter confirmed a held report
+    Confirm,
+    /// admin released a held pass
+    Override,
+    /// held reports dropped after the hold expired
+    Discard,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReportAuditEntry {
+    /// RFC3339
+    pub time: String,
+    pub action: AuditAction,
+    pub satellite: String,
+    pub callsign: String,
+    pub grid: Option<String>,
+    pub status: Option<String>,
+    /// time block of the pass template
+    pub pass_time: Option<String>,
+    /// `None` for scheduled tasks
+    pub actor: Option<ReportSource>,
+    pub note: Option<String>,
+}
+
+impl ReportAuditEntry {
+    pub fn new(action: AuditAction, report: &SatStatus, actor: Option<ReportSource>) -> Self {
+        ReportAuditEntry {
+            time: Utc::now().to_rfc3339(),
+            action,
+            satellite: report.name.clone(),
+            callsign: report.callsign.clone(),
+            grid: Some(report.grid_square.clone()),
+            status: Some(report.report.clone()),
+            pass_time: Some(report.reported_time.clone()),
+            actor,
+            note: None,
+        }
+    }
+
+    /// Entry about a whole pass template rather than a single report
+    pub fn for_pass(action: AuditAction, satellite: &str, pass_time: &str, actor: Option<ReportSource>) -> Self {
+        ReportAuditEntry {
+            time: Utc::now().to_rfc3339(),
+            action,
+            satellite: satellite.to_string(),
+            callsign: "-".to_string(),
+            grid: None,
+            status: None,
+            pass_time: Some(pass_time.to_string()),
+            actor,
+            note: None,
+        }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    fn to_display_string(&self) -> String {
+        let time = DateTime::parse_from_rfc3339(&self.time)
+            .map(|dt| dt.with_timezone(&Utc).format("%m-%d %H:%MZ").to_string())
+            .unwrap_or_else(|_| self.time.clone());
+        let chat = |actor: &ReportSource| match actor.group_id {
+            0 => "私聊".to_string(),
+            group_id => format!("群 {}", group_id),
+        };
+        let actor = match &self.actor {
+            Some(actor) if actor.is_admin => format!("管理员 {} ({}, 消息 {})", actor.user_id, chat(actor), actor.message_id),
+            Some(actor) => format!("{} [{}] ({}, 消息 {})", actor.user_id, actor.card, chat(actor), actor.message_id),
+            None => "定时任务".to_string(),
+        };
+
+        let mut line = format!(
+            "{} {:?} {} {} {} {}\n  by {}",
+            time,
+            self.action,
+            self.satellite,
+            self.callsign,
+            self.grid.as_deref().unwrap_or("-"),
+            self.status.as_deref().unwrap_or("-"),
+            actor,
+        );
+        if let Some(note) = &self.note {
+            line.push_str(&format!("\n  {}", note));
+        }
+        line
+    }
+}
+
+/// Failures are only logged, auditing never blocks the report itself
+pub async fn append_audit_entry(
+    app_status: &AppStatus,
+    entry: ReportAuditEntry,
+) {
+    let line = match serde_json::to_string(&entry) {
+        Ok(line) => line + "\n",
+        Err(e) => {
+            tracing::error!("Failed to serialize audit entry: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = append_file(app_status.file_tx.clone(), USER_REPORT_AUDIT_LOG.to_string(), line).await {
+        tracing::error!("Failed to append report audit log: {}", e);
+    }
+}
+
+pub async fn read_audit_log(
+    app_status: &AppStatus,
+) -> anyhow::Result<Vec<ReportAuditEntry>> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), USER_REPORT_AUDIT_LOG.to_string()).await {
+        return Ok(Vec::new());
+    }
+
+    let content = match load_file(tx_filerequest, USER_REPORT_AUDIT_LOG.to_string(), FileFormat::Text).await? {
+        FileData::Text(content) => content,
+        _ => return Err(anyhow::anyhow!("Unexpected file format received")),
+    };
+
+    let mut entries = Vec::new();
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str::<ReportAuditEntry>(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => tracing::warn!("Skipping malformed audit line: {}", e),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// `/report history <呼号|卫星> [条数]`, admin only
+pub async fn query_report_history(
+    app_status: Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let admin_id = app_status.config.read().await.bot_config.admin_id.clone();
+    if !admin_id.contains(&payload.user_id) {
+        return ApiResponse::error("只有管理员可以查询报告记录喵".to_string());
+    }
+
+    // Args: history <callsign|satellite> [limit]
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let Some(keyword) = args.get(1) else {
+        return ApiResponse::error("参数不足喵，格式是 /report history <呼号|卫星名称> [条数] 喵".to_string());
+    };
+    let limit = args
+        .get(2)
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .min(MAX_HISTORY_LIMIT);
+
+    let entries = match read_audit_log(&app_status).await {
+        Ok(entries) => entries,
+        Err(e) => return ApiResponse::error(format!("读取报告记录失败: {}", e)),
+    };
+
+    // satellite names go through the same fuzzy matching as /report
+    let keyword_upper = keyword.to_uppercase();
+    let match_sat = match load_satellites_list(app_status.file_tx.clone()).await {
+        Ok(list) => search_satellites(keyword, &list, 0.95),
+        Err(_) => Vec::new(),
+    };
+
+    let matched: Vec<&ReportAuditEntry> = entries
+        .iter()
+        .filter(|e| e.callsign == keyword_upper || match_sat.contains(&e.satellite))
+        .collect();
+
+    if matched.is_empty() {
+        return Reply::text(format!("没有找到 {} 的报告记录喵", keyword));
+    }
+
+    let mut data = vec![format!("{} 的报告记录 (最近 {} 条，共 {} 条):", keyword, limit.min(matched.len()), matched.len())];
+    data.extend(
+        matched
+            .iter()
+            .rev()
+            .take(limit)
+            .map(|e| e.to_display_string()),
+    );
+    Reply::lines(data)
+}
@@ -0,0 +1,445 @@
+use crate::{
+    app_status::AppStatus,
+    fs::handler::*,
+    module::{
+        amsat::{prelude::*, report_audit::*},
+        callsign::parser::Callsign,
+        prelude::grid_square,
+        tools::render::{render_leaderboard, render_report_stats},
+    },
+    msg::prelude::MessageEvent,
+    response::{ApiResponse, Reply},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+
+/// Official reports of group members, AMSAT data itself only keeps 48 hours
+pub const REPORT_STATS_LEDGER: &str = "runtime_data/report_stats_ledger.json";
+const LEADERBOARD_SIZE: usize = 10;
+const MONTH_HISTORY: usize = 6;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StatSource {
+    /// submitted through the bot
+    Bot,
+    /// seen in AMSAT official data
+    Amsat,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StatRecord {
+    /// base callsign, portable designators stripped
+    pub callsign: String,
+    pub satellite: String,
+    pub grid: String,
+    /// RFC3339
+    pub time: String,
+    pub source: StatSource,
+}
+
+impl StatRecord {
+    /// Same callsign, satellite and hour are counted once
+    fn dedup_key(&self) -> Option<(String, String, String)> {
+        let time = DateTime::parse_from_rfc3339(&self.time).ok()?.with_timezone(&Utc);
+        Some((self.callsign.clone(), self.satellite.clone(), time.format("%Y-%m-%dT%H").to_string()))
+    }
+
+    fn date(&self) -> Option<NaiveDate> {
+        DateTime::parse_from_rfc3339(&self.time).ok().map(|dt| dt.with_timezone(&Utc).date_naive())
+    }
+
+    fn month(&self) -> Option<String> {
+        self.date().map(|d| format!("{:04}-{:02}", d.year(), d.month()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CallsignStats {
+    pub callsign: String,
+    pub total_reports: usize,
+    /// `YYYY-MM` -> count, oldest first
+    pub monthly_reports: Vec<(String, usize)>,
+    /// satellite -> count, most reported first
+    pub satellites: Vec<(String, usize)>,
+    pub unique_grids: usize,
+    /// consecutive UTC days with at least one report, ending today or yesterday
+    pub current_streak: usize,
+    pub longest_streak: usize,
+    pub last_report: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LeaderboardEntry {
+    pub callsign: String,
+    pub reports: usize,
+    pub satellites: usize,
+    pub grids: usize,
+    pub longest_streak: usize,
+}
+
+fn base_callsign(callsign: &str) -> String {
+    Callsign::parse(callsign)
+        .map(|c| c.base)
+        .unwrap_or_else(|| callsign.to_uppercase())
+}
+
+async fn read_stats_ledger(
+    app_status: &AppStatus,
+) -> anyhow::Result<Vec<StatRecord>> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), REPORT_STATS_LEDGER.to_string()).await {
+        return Ok(Vec::new());
+    }
+
+    match load_file(tx_filerequest, REPORT_STATS_LEDGER.to_string(), FileFormat::Json).await? {
+        FileData::Json(data) => serde_json::from_value(data)
+            .map_err(|e| anyhow::anyhow!("Failed to parse stats ledger: {}", e)),
+        _ => Err(anyhow::anyhow!("Unexpected file format received")),
+    }
+}
+
+/// Called after every AMSAT update, keeps official reports of callsigns that reported via the bot
+pub async fn record_official_reports(
+    app_status: &AppStatus,
+    official_data: &[SatelliteFileFormat],
+) -> anyhow::Result<()> {
+    let audit_log = read_audit_log(app_status).await?;
+    let members: HashSet<String> = audit_log.iter()
+        .filter(|e| e.actor.is_some())
+        .map(|e| base_callsign(&e.callsign))
+        .collect();
+    if members.is_empty() {
+        return Ok(());
+    }
+
+    let mut ledger = read_stats_ledger(app_status).await?;
+    let mut seen: HashSet<_> = ledger.iter().filter_map(|r| r.dedup_key()).collect();
+    let mut added = 0;
+
+    for satellite in official_data {
+        for element in &satellite.data {
+            for report in &element.report {
+                let callsign = base_callsign(&report.callsign);
+                if !members.contains(&callsign) {
+                    continue;
+                }
+                let record = StatRecord {
+                    callsign,
+                    satellite: satellite.name.clone(),
+                    grid: report.grid_square.clone(),
+                    time: report.reported_time.clone(),
+                    source: StatSource::Amsat,
+                };
+                if record.dedup_key().is_some_and(|key| seen.insert(key)) {
+                    ledger.push(record);
+                    added += 1;
+                }
+            }
+        }
+    }
+
+    if added == 0 {
+        return Ok(());
+    }
+    tracing::info!("Recorded {} official reports for stats", added);
+    write_file(
+        app_status.file_tx.clone(),
+        REPORT_STATS_LEDGER.to_string(),
+        &FileData::Json(serde_json::to_value(&ledger)?),
+    ).await
+}
+
+/// Bot submissions from the audit log merged with the official ledger
+async fn load_stat_records(
+    app_status: &AppStatus,
+) -> anyhow::Result<Vec<StatRecord>> {
+    let audit_log = read_audit_log(app_status).await?;
+    let mut records: Vec<StatRecord> = audit_log.into_iter()
+        .filter(|e| e.action == AuditAction::Submit)
+        .map(|e| StatRecord {
+            callsign: base_callsign(&e.callsign),
+            satellite: e.satellite,
+            grid: e.grid.unwrap_or_default(),
+            time: e.pass_time.unwrap_or(e.time),
+            source: StatSource::Bot,
+        })
+        .collect();
+    records.extend(read_stats_ledger(app_status).await?);
+
+    let mut seen = HashSet::new();
+    records.retain(|r| r.dedup_key().is_some_and(|key| seen.insert(key)));
+    Ok(records)
+}
+
+fn compute_streaks(dates: &BTreeSet<NaiveDate>, today: NaiveDate) -> (usize, usize) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for date in dates {
+        run = match previous {
+            Some(prev) if *date - prev == chrono::Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*date);
+    }
+
+    // the current streak is still alive if the last report was today or yesterday
+    let current = match previous {
+        Some(last) if today - last <= chrono::Duration::days(1) => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+pub fn compute_callsign_stats(
+    records: &[StatRecord],
+    callsign: &str,
+    today: NaiveDate,
+) -> CallsignStats {
+    let own: Vec<&StatRecord> = records.iter().filter(|r| r.callsign == callsign).collect();
+
+    let mut monthly: BTreeMap<String, usize> = BTreeMap::new();
+    let mut satellites: HashMap<String, usize> = HashMap::new();
+    let mut grids = HashSet::new();
+    let mut dates = BTreeSet::new();
+    for record in &own {
+        if let Some(month) = record.month() {
+            *monthly.entry(month).or_default() += 1;
+        }
+        if let Some(date) = record.date() {
+            dates.insert(date);
+        }
+        *satellites.entry(record.satellite.clone()).or_default() += 1;
+        if let Some(square) = grid_square(&record.grid) {
+            grids.insert(square);
+        }
+    }
+
+    let mut satellites: Vec<(String, usize)> = satellites.into_iter().collect();
+    satellites.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let monthly_reports: Vec<(String, usize)> = monthly.into_iter().collect();
+    let monthly_reports = monthly_reports[monthly_reports.len().saturating_sub(MONTH_HISTORY)..].to_vec();
+    let (current_streak, longest_streak) = compute_streaks(&dates, today);
+
+    CallsignStats {
+        callsign: callsign.to_string(),
+        total_reports: own.len(),
+        monthly_reports,
+        satellites,
+        unique_grids: grids.len(),
+        current_streak,
+        longest_streak,
+        last_report: own.iter().map(|r| r.time.clone()).max(),
+    }
+}
+
+/// `2025-8`, `2025-08` or `2025/8` as `2025-08`, the key used by `StatRecord::month`
+fn parse_month(input: &str) -> Option<String> {
+    NaiveDate::parse_from_str(&format!("{}-01", input.replace('/', "-")), "%Y-%m-%d")
+        .ok()
+        .map(|date| date.format("%Y-%m").to_string())
+}
+
+/// Only callsigns in `members` are ranked
+pub fn compute_leaderboard(
+    records: &[StatRecord],
+    members: &HashSet<String>,
+    month: &str,
+    today: NaiveDate,
+) -> Vec<LeaderboardEntry> {
+    let month_records: Vec<StatRecord> = records.iter()
+        .filter(|r| r.month().as_deref() == Some(month) && members.contains(&r.callsign))
+        .cloned()
+        .collect();
+    let callsigns: BTreeSet<&str> = month_records.iter().map(|r| r.callsign.as_str()).collect();
+
+    let mut entries: Vec<LeaderboardEntry> = callsigns.into_iter()
+        .map(|callsign| {
+            let stats = compute_callsign_stats(&month_records, callsign, today);
+            LeaderboardEntry {
+                callsign: stats.callsign,
+                reports: stats.total_reports,
+                satellites: stats.satellites.len(),
+                grids: stats.unique_grids,
+                longest_streak: stats.longest_streak,
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| b.reports.cmp(&a.reports)
+        .then(b.satellites.cmp(&a.satellites))
+        .then(a.callsign.cmp(&b.callsign)));
+    entries.truncate(LEADERBOARD_SIZE);
+    entries
+}
+
+/// Callsign used most recently by this QQ account
+pub fn callsign_of_user(
+    audit_log: &[ReportAuditEntry],
+    user_id: u64,
+) -> Option<String> {
+    audit_log.iter()
+        .rev()
+        .find(|e| e.actor.as_ref().is_some_and(|a| a.user_id == user_id) && e.callsign != "-")
+        .map(|e| base_callsign(&e.callsign))
+}
+
+/// Base callsigns reported from this group, by anyone in it
+pub fn group_callsigns(
+    audit_log: &[ReportAuditEntry],
+    group_id: u64,
+) -> HashSet<String> {
+    audit_log.iter()
+        .filter(|e| e.actor.as_ref().is_some_and(|a| a.group_id == group_id) && e.callsign != "-")
+        .map(|e| base_callsign(&e.callsign))
+        .collect()
+}
+
+/// `/stats [呼号]`
+pub async fn query_report_stats(
+    app_status: Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let callsign = match args.split_whitespace().next() {
+        Some(callsign) => base_callsign(callsign),
+        None => {
+            let audit_log = match read_audit_log(&app_status).await {
+                Ok(log) => log,
+                Err(e) => return ApiResponse::error(format!("读取报告记录失败: {}", e)),
+            };
+            match callsign_of_user(&audit_log, payload.user_id) {
+                Some(callsign) => callsign,
+                None => return ApiResponse::error("还没有你的报告记录喵，可以使用 /stats <呼号> 查询".to_string()),
+            }
+        }
+    };
+
+    let records = match load_stat_records(&app_status).await {
+        Ok(records) => records,
+        Err(e) => return ApiResponse::error(format!("读取统计数据失败: {}", e)),
+    };
+
+    let stats = compute_callsign_stats(&records, &callsign, Utc::now().date_naive());
+    if stats.total_reports == 0 {
+        return ApiResponse::error(format!("没有找到 {} 的报告记录喵", callsign));
+    }
+
+    render_report_stats(&stats, payload).await
+}
+
+/// `/rank [YYYY-MM]`, defaults to the current month, ranks the callsigns reported from this group
+pub async fn query_leaderboard(
+    app_status: Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    if payload.is_private() {
+        return ApiResponse::error("排行按群统计，请在群里使用喵".to_string());
+    }
+    let now = Utc::now();
+    let month = match args.split_whitespace().next() {
+        Some(month) => match parse_month(month) {
+            Some(month) => month,
+            None => return ApiResponse::error("月份格式错误喵，例如 /rank 2025-08".to_string()),
+        },
+        None => now.format("%Y-%m").to_string(),
+    };
+
+    let records = match load_stat_records(&app_status).await {
+        Ok(records) => records,
+        Err(e) => return ApiResponse::error(format!("读取统计数据失败: {}", e)),
+    };
+
+    let audit_log = match read_audit_log(&app_status).await {
+        Ok(log) => log,
+        Err(e) => return ApiResponse::error(format!("读取报告记录失败: {}", e)),
+    };
+    let members = group_callsigns(&audit_log, payload.group_id);
+
+    let leaderboard = compute_leaderboard(&records, &members, &month, now.date_naive());
+    if leaderboard.is_empty() {
+        return ApiResponse::error(format!("{} 本群还没有人提交报告喵", month));
+    }
+
+    render_leaderboard(&month, &leaderboard, payload).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn record(callsign: &str, satellite: &str, grid: &str, time: &str) -> StatRecord {
+        StatRecord {
+            callsign: callsign.to_string(),
+            satellite: satellite.to_string(),
+            grid: grid.to_string(),
+            time: time.to_string(),
+            source: StatSource::Bot,
+        }
+    }
+
+    #[test]
+    fn normalises_months() {
+        assert_eq!(parse_month("2025-8").as_deref(), Some("2025-08"));
+        assert_eq!(parse_month("2025-08").as_deref(), Some("2025-08"));
+        assert_eq!(parse_month("2025/12").as_deref(), Some("2025-12"));
+        assert_eq!(parse_month("2025-13"), None);
+        assert_eq!(parse_month("八月"), None);
+    }
+
+    #[test]
+    fn streaks() {
+        let dates: BTreeSet<NaiveDate> = [date(2025, 8, 1), date(2025, 8, 2), date(2025, 8, 3), date(2025, 8, 10), date(2025, 8, 11)]
+            .into_iter()
+            .collect();
+        // last report yesterday, the streak is still alive
+        assert_eq!(compute_streaks(&dates, date(2025, 8, 12)), (2, 3));
+        assert_eq!(compute_streaks(&dates, date(2025, 8, 11)), (2, 3));
+        assert_eq!(compute_streaks(&dates, date(2025, 8, 13)), (0, 3));
+        assert_eq!(compute_streaks(&BTreeSet::new(), date(2025, 8, 13)), (0, 0));
+    }
+
+    #[test]
+    fn streak_across_months() {
+        let dates: BTreeSet<NaiveDate> = [date(2025, 7, 31), date(2025, 8, 1)].into_iter().collect();
+        assert_eq!(compute_streaks(&dates, date(2025, 8, 1)), (2, 2));
+    }
+
+    #[test]
+    fn leaderboard_ranks_members_of_the_month() {
+        let records = vec![
+            record("BG2AAA", "SO-50", "OM89ab", "2025-08-01T12:00:00Z"),
+            record("BG2AAA", "AO-91", "OM89", "2025-08-02T12:00:00Z"),
+            record("BG2BBB", "SO-50", "ON80", "2025-08-01T12:00:00Z"),
+            record("BG2BBB", "SO-50", "ON80", "2025-08-03T12:00:00Z"),
+            record("BG2CCC", "SO-50", "PN11", "2025-08-01T12:00:00Z"),
+            // other month and non-member
+            record("BG2CCC", "SO-50", "PN11", "2025-07-31T12:00:00Z"),
+            record("BG2CCC", "SO-50", "PN11", "2025-07-30T12:00:00Z"),
+            record("BG9ZZZ", "SO-50", "OL72", "2025-08-01T12:00:00Z"),
+        ];
+        let members: HashSet<String> = ["BG2AAA", "BG2BBB", "BG2CCC"].iter().map(|c| c.to_string()).collect();
+
+        let month = parse_month("2025-8").unwrap();
+        let leaderboard = compute_leaderboard(&records, &members, &month, date(2025, 8, 31));
+        let ranked: Vec<(&str, usize, usize, usize, usize)> = leaderboard
+            .iter()
+            .map(|e| (e.callsign.as_str(), e.reports, e.satellites, e.grids, e.longest_streak))
+            .collect();
+        assert_eq!(ranked, vec![
+            // more satellites wins a tie on reports
+            ("BG2AAA", 2, 2, 1, 2),
+            ("BG2BBB", 2, 1, 1, 1),
+            ("BG2CCC", 1, 1, 1, 1),
+        ]);
+
+        assert!(compute_leaderboard(&records, &members, "2025-09", date(2025, 9, 30)).is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    app_status::AppStatus, fs::handler::*, i18n, module::{amsat::{official_report, prelude::*}, prelude::*}, msg::prelude::MessageEvent, response::ApiResponse
+    app_status::AppStatus, fs::handler::*, i18n, module::{amsat::{official_report, prelude::*, report_audit::*}, prelude::*}, msg::{prelude::MessageEvent, segment::{text_with_at, Segment}}, response::{ApiResponse, Reply}
 };
 use tokio::{
     sync::RwLock,
@@ -47,6 +47,7 @@ pub async fn data_parser(
         grid_square: grid,
         reported_time,
         report: report.to_string(),
+        source: None,
     })
 }
 
@@ -181,7 +182,8 @@ pub async fn create_report_template(
 
     let new_element: SatelliteFileElement = SatelliteFileElement {
         time,
-        report: Vec::new()
+        report: Vec::new(),
+        hold: None,
     };
 
     let mut matched = false;
@@ -236,15 +238,15 @@ pub async fn add_user_report(
     app_status: Arc<AppStatus>,
     args: &String,
     payload: &MessageEvent,
-) -> ApiResponse<Vec<String>> {
-    let mut response = ApiResponse::<Vec<String>>::empty();
+) -> Reply {
+    let mut response = Reply::empty();
     let mut response_data = Vec::new();
 
     // Args: Sat-name Callsign Grid Status
     let args: Vec<&str> = args.split_whitespace().collect();
 
     if args.len() < 4 {
-        return ApiResponse::<Vec<String>>::error("参数不足喵，格式是 /report <卫星名称> <呼号> <网格> <状态> 喵".to_string());
+        return Reply::error("参数不足喵，格式是 /report <卫星名称> <呼号> <网格> <状态> 喵".to_string());
     }
 
     let callsign = args[1].to_uppercase().to_string();
@@ -256,6 +258,10 @@ pub async fn add_user_report(
         let config_guard = app_status.config.read().await;
         config_guard.bot_config.admin_id.clone()
     };
+    if !is_valid_callsign(&callsign) {
+        return ApiResponse::error("呼号参数非法喵".to_string());
+    }
+
     if !callsign_auth(&callsign, payload, &admin_id) {
         return ApiResponse::error("无法验证你的身份喵".to_string());
     }
@@ -269,28 +275,30 @@ pub async fn add_user_report(
         "yellow" | "y" | "黄" => ReportStatus::Yellow.to_string_report_format(),
         "red" | "r" | "红" => ReportStatus::Red.to_string_report_format(),
         "purple" | "p" | "紫" => ReportStatus::Purple.to_string_report_format(),
-        _ => return ApiResponse::<Vec<String>>::error("Rinko不能解析你报告的状态呢")
+        _ => return Reply::error("Rinko不能解析你报告的状态呢")
     };
 
     let tx_filerequest = app_status.file_tx.clone();
     let satellite_lists = match load_satellites_list(tx_filerequest.clone()).await {
         Ok(data) => data,
         Err(e) => {
-            return ApiResponse::<Vec<String>>::error(format!("可用卫星列表加载失败: {}", e))
+            return Reply::error(format!("可用卫星列表加载失败: {}", e))
         }
     };
 
     let match_sat = search_satellites(&sat_name, &satellite_lists, 0.95);
     if match_sat.is_empty() || match_sat.len() != 1 {
-        return ApiResponse::<Vec<String>>::error(format!("无法选中卫星喵，可能的卫星有: {:?}", match_sat));
+        return Reply::error(format!("无法选中卫星喵，可能的卫星有: {:?}", match_sat));
     }
     let match_sat = match_sat[0].clone();
 
     let mut user_report_data = match read_user_report_file(&app_status).await {
         Ok(data) => data,
-        Err(e) => return ApiResponse::<Vec<String>>::error(format!("{}", e)),
+        Err(e) => return Reply::error(format!("{}", e)),
     };
 
+    let source = ReportSource::from_payload(payload, &admin_id);
+    let mut audit_entry: Option<ReportAuditEntry> = None;
     let mut found = false;
     for item in user_report_data.iter_mut() {
         if item.name == match_sat && item.data.len() > 0 {
@@ -303,13 +311,22 @@ pub async fn add_user_report(
                 callsign: callsign.clone(),
                 report: status.clone(),
                 grid_square: grid.clone(),
+                source: Some(source.clone()),
             };
             // check for duplicate reports
-            if element.report.iter().any(|r| r.callsign == callsign) {
+            let mut action = AuditAction::Add;
+            if let Some(old) = element.report.iter().find(|r| r.callsign == callsign) {
+                action = AuditAction::Update;
+                // keep what was replaced for dispute handling
+                let replaced = format!("replaced: {} {}", old.grid_square, old.report);
+                audit_entry = Some(ReportAuditEntry::new(action.clone(), &report, Some(source.clone())).with_note(replaced));
                 // replace the old report for the callsign
                 element.report.retain(|r| r.callsign != callsign);
                 response_data.push(format!("{} 的报告已更新喵", callsign));
             }
+            if action == AuditAction::Add {
+                audit_entry = Some(ReportAuditEntry::new(action, &report, Some(source.clone())));
+            }
             element.report.push(report);
             item.data = vec![element];
             break;
@@ -318,24 +335,20 @@ pub async fn add_user_report(
 
     // return warn if the satellite is not found
     if !found {
-        return ApiResponse::<Vec<String>>::error(format!("{}", i18n::text("cmd_report_user_no_template")));
+        return Reply::error(format!("{}", i18n::text("cmd_report_user_no_template")));
     }
 
-    // check if reports have conflicts
-    let mut report_status_count: HashMap<ReportStatus, usize> = HashMap::new();
-    for item in user_report_data.iter() {
-        if item.name == match_sat {
-            for data in &item.data {
-                for report in &data.report {
-                    let status = ReportStatus::from_string(&report.report);
-                    *report_status_count.entry(status).or_insert(0) += 1;
-                }
-            }
-        }
-    }
-    let report_status = official_report::determine_report_status(&report_status_count);
-    if report_status == ReportStatus::Orange {
-        response_data.push(i18n::text("cmd_report_user_conflict_report"));
+    // check if reports have conflicts, submission is held until resolved
+    let mut hold_created = false;
+    let mut mention = Vec::new();
+    if let Some(element) = user_report_data.iter_mut()
+        .find(|item| item.name == match_sat)
+        .and_then(|item| item.data.first_mut())
+    {
+        let (lines, reporters, created) = refresh_report_hold(&match_sat, element);
+        response_data.extend(lines);
+        mention = reporters;
+        hold_created = created;
     }
 
     let user_report_data = serde_json::to_value(&user_report_data)
@@ -347,7 +360,19 @@ pub async fn add_user_report(
         USER_REPORT_DATA.into(),
         &FileData::Json(user_report_data),
     ).await {
-        return ApiResponse::<Vec<String>>::error(format!("{}", e));
+        return Reply::error(format!("{}", e));
+    }
+
+    if let Some(mut entry) = audit_entry {
+        if source.is_admin && !source.card.to_uppercase().contains(&callsign) {
+            let note = entry.note.take().map(|n| format!("{}, ", n)).unwrap_or_default();
+            entry.note = Some(format!("{}admin edit", note));
+        }
+        let pass_time = entry.pass_time.clone().unwrap_or_default();
+        append_audit_entry(&app_status, entry).await;
+        if hold_created {
+            append_audit_entry(&app_status, ReportAuditEntry::for_pass(AuditAction::Hold, &match_sat, &pass_time, None)).await;
+        }
     }
 
     response.success = true;
@@ -355,19 +380,62 @@ pub async fn add_user_report(
         "{} 的报告已添加喵",
         callsign
     ));
-    response.data = Some(response_data);
+    mention.retain(|user_id| *user_id != payload.user_id);
+    response.data = Some(lines_with_at(&mention, response_data));
     response
 }
 
+/// Reply lines with the mentioned users in front of the first one
+fn lines_with_at(at: &[u64], lines: Vec<String>) -> Vec<Segment> {
+    let mut lines = lines.into_iter();
+    let mut segments = lines.next().map(|first| text_with_at(at, &first)).unwrap_or_default();
+    segments.extend(lines.map(Segment::from));
+    segments
+}
+
+/// Re-runs the conflict check after the reports of a pass changed
+/// - returns the lines to reply with, the reporters to ask for a confirmation and whether a new hold was created
+fn refresh_report_hold(
+    sat_name: &str,
+    element: &mut SatelliteFileElement,
+) -> (Vec<String>, Vec<u64>, bool) {
+    let mut lines = Vec::new();
+    let mut report_status_count: HashMap<ReportStatus, usize> = HashMap::new();
+    for report in &element.report {
+        let status = ReportStatus::from_string(&report.report);
+        *report_status_count.entry(status).or_insert(0) += 1;
+    }
+    let report_status = official_report::determine_report_status(&report_status_count);
+    let mut hold_created = false;
+    let mut reporters = Vec::new();
+    if report_status == ReportStatus::Orange {
+        match element.hold.as_mut() {
+            Some(hold) => hold.refresh(&element.report),
+            None => {
+                element.hold = Some(ReportHold::new(&element.report));
+                hold_created = true;
+            }
+        }
+        if let Some(hold) = &element.hold {
+            lines.push(i18n::text("cmd_report_user_conflict_report"));
+            lines.extend(format_report_hold(sat_name, hold));
+            reporters = hold.pending_reporters();
+        }
+    } else if element.hold.take().is_some() {
+        lines.push("报告冲突已解除，本次过境的报告会按时提交喵".to_string());
+    }
+    (lines, reporters, hold_created)
+}
+
 pub async fn remove_user_report(
     app_status: Arc<AppStatus>,
     args: &String,
     payload: &MessageEvent,
-) -> ApiResponse<Vec<String>> {
+) -> Reply {
     // Args: remove <satellite_name> <Callsign>
     let args: Vec<&str> = args.split_whitespace().collect();
     if args.len() < 3 {
-        return ApiResponse::<Vec<String>>::error("参数不足喵，格式是 /report remove <卫星名称> <呼号> 喵".to_string());
+        return Reply::error("参数不足喵，格式是 /report remove <卫星名称> <呼号> 喵".to_string());
     }
 
     // read satellite name and callsign from args
@@ -379,14 +447,14 @@ pub async fn remove_user_report(
     let satellite_lists = match load_satellites_list(tx_filerequest.clone()).await {
         Ok(data) => data,
         Err(e) => {
-            return ApiResponse::<Vec<String>>::error(format!("可用卫星列表加载失败: {}", e));
+            return Reply::error(format!("可用卫星列表加载失败: {}", e));
         }
     };
 
     // get the satellite name from the list
     let match_sat = search_satellites(satellite_name, &satellite_lists, 0.95);
     if match_sat.is_empty() || match_sat.len() != 1 {
-        return ApiResponse::<Vec<String>>::error(format!("无法选中卫星喵，可能的卫星有: {:?}", match_sat));
+        return Reply::error(format!("无法选中卫星喵，可能的卫星有: {:?}", match_sat));
     }
     let match_sat = match_sat[0].clone();
 
@@ -402,10 +470,13 @@ pub async fn remove_user_report(
     // read user_report_data
     let user_report_data = match read_user_report_file(&app_status).await {
         Ok(data) => data,
-        Err(e) => return ApiResponse::<Vec<String>>::error(format!("{}", e)),
+        Err(e) => return Reply::error(format!("{}", e)),
     };
 
     // remove user report
+    let mut removed_report: Option<SatStatus> = None;
+    let mut hold_lines: Vec<String> = Vec::new();
+    let mut mention: Vec<u64> = Vec::new();
     let mut found_template = false;
     let mut found_callsign = false;
     let mut new_data: Vec<SatelliteFileFormat> = Vec::new();
@@ -419,9 +490,14 @@ pub async fn remove_user_report(
             }
             if found_callsign {
                 // remove the callsign report
+                removed_report = new_element.report.iter().find(|r| r.callsign == callsign).cloned();
                 new_element.report.retain(|r| r.callsign != callsign);
+                // the removed reporter must not keep the pass held
+                if new_element.hold.is_some() {
+                    (hold_lines, mention, _) = refresh_report_hold(&match_sat, &mut new_element);
+                }
             } else {
-                return ApiResponse::<Vec<String>>::error(format!("{} 的报告不存在喵", callsign));
+                return Reply::error(format!("{} 的报告不存在喵", callsign));
             }
             new_data.push(SatelliteFileFormat {
                 name: item.name.clone(),
@@ -434,7 +510,7 @@ pub async fn remove_user_report(
     }
 
     if found_template == false {
-        return ApiResponse::<Vec<String>>::error(format!("{}", i18n::text("cmd_report_user_no_template")));
+        return Reply::error(format!("{}", i18n::text("cmd_report_user_no_template")));
     }
 
     let tx_filerequest = app_status.file_tx.clone();
@@ -446,19 +522,209 @@ pub async fn remove_user_report(
         USER_REPORT_DATA.into(),
         &FileData::Json(user_report_data),
     ).await {
-        return ApiResponse::<Vec<String>>::error(format!("{}", e));
+        return Reply::error(format!("{}", e));
+    }
+
+    if let Some(report) = removed_report {
+        let source = ReportSource::from_payload(payload, &admin_id);
+        append_audit_entry(&app_status, ReportAuditEntry::new(AuditAction::Remove, &report, Some(source))).await;
     }
 
-    ApiResponse::<Vec<String>>::ok(vec![format!("{} 的报告已删除喵", callsign)])
+    let mut response_data = vec![format!("{} 的报告已删除喵", callsign)];
+    response_data.extend(hold_lines);
+    mention.retain(|user_id| *user_id != payload.user_id);
+    Reply::ok(lines_with_at(&mention, response_data))
+}
+
+fn format_report_hold(
+    sat_name: &str,
+    hold: &ReportHold,
+) -> Vec<String> {
+    let mut lines = vec![format!("{} 冲突的报告:", sat_name)];
+    for conflict in &hold.conflicts {
+        let confirmed = if hold.confirmed.contains(&conflict.callsign) { " (已确认)" } else { "" };
+        lines.push(format!("{}: {}{}", conflict.callsign, conflict.status, confirmed));
+    }
+    if hold.released_by.is_some() {
+        lines.push("管理员已放行，本次过境的报告会按时提交喵".to_string());
+    } else {
+        lines.push(format!(
+            "请上述报告者使用 /report confirm {} 确认状态，或者重新 /report 修正喵\n管理员可以使用 /report override {} 直接放行",
+            sat_name, sat_name
+        ));
+    }
+    lines
+}
+
+/// Load the template of a satellite that is currently held
+async fn load_held_template(
+    app_status: &Arc<AppStatus>,
+    sat_name: &str,
+) -> Result<(Vec<SatelliteFileFormat>, String), Reply> {
+    let satellite_lists = match load_satellites_list(app_status.file_tx.clone()).await {
+        Ok(data) => data,
+        Err(e) => return Err(ApiResponse::error(format!("可用卫星列表加载失败: {}", e))),
+    };
+
+    let match_sat = search_satellites(sat_name, &satellite_lists, 0.95);
+    if match_sat.len() != 1 {
+        return Err(ApiResponse::error(format!("无法选中卫星喵，可能的卫星有: {:?}", match_sat)));
+    }
+    let match_sat = match_sat[0].clone();
+
+    let user_report_data = match read_user_report_file(app_status).await {
+        Ok(data) => data,
+        Err(e) => return Err(ApiResponse::error(format!("{}", e))),
+    };
+
+    let held = user_report_data.iter()
+        .find(|item| item.name == match_sat)
+        .and_then(|item| item.data.first())
+        .is_some_and(|element| element.hold.is_some());
+    if !held {
+        return Err(ApiResponse::error(format!("{} 没有需要处理的冲突报告喵", match_sat)));
+    }
+
+    Ok((user_report_data, match_sat))
+}
+
+/// `/report confirm <卫星名称> [呼号]`
+pub async fn confirm_user_report(
+    app_status: Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    // Args: confirm <satellite_name> [Callsign]
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let Some(sat_name) = args.get(1) else {
+        return ApiResponse::error("参数不足喵，格式是 /report confirm <卫星名称> [呼号] 喵".to_string());
+    };
+    let callsign = args.get(2).map(|s| s.to_uppercase());
+
+    let admin_id = app_status.config.read().await.bot_config.admin_id.clone();
+    let (mut user_report_data, match_sat) = match load_held_template(&app_status, sat_name).await {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+
+    let Some(element) = user_report_data.iter_mut()
+        .find(|item| item.name == match_sat)
+        .and_then(|item| item.data.first_mut()) else {
+        return ApiResponse::error(i18n::text("cmd_report_user_no_template"));
+    };
+    let Some(hold) = element.hold.as_mut() else {
+        return ApiResponse::error(format!("{} 没有需要处理的冲突报告喵", match_sat));
+    };
+
+    // confirm the given callsign, or every conflicting report typed by this user
+    let targets: Vec<String> = match &callsign {
+        Some(callsign) => {
+            if !hold.conflicts.iter().any(|c| &c.callsign == callsign) {
+                return ApiResponse::error(format!("{} 不在冲突的报告里喵", callsign));
+            }
+            if !callsign_auth(callsign, payload, &admin_id) {
+                return ApiResponse::error("无法验证你的身份喵".to_string());
+            }
+            vec![callsign.clone()]
+        }
+        None => hold.conflicts.iter()
+            .filter(|c| c.user_id == Some(payload.user_id))
+            .map(|c| c.callsign.clone())
+            .collect(),
+    };
+    if targets.is_empty() {
+        return ApiResponse::error("没有找到你提交的冲突报告喵，可以在命令后加上呼号".to_string());
+    }
+
+    for callsign in &targets {
+        if !hold.confirmed.contains(callsign) {
+            hold.confirmed.push(callsign.clone());
+        }
+    }
+    let mut response_data = vec![format!("{} 的报告已确认喵", targets.join(", "))];
+    if hold.is_active() {
+        response_data.extend(format_report_hold(&match_sat, hold));
+    } else {
+        response_data.push("所有报告者都已确认，本次过境的报告会按时提交喵".to_string());
+    }
+
+    let source = ReportSource::from_payload(payload, &admin_id);
+    let confirmed_reports: Vec<SatStatus> = element.report.iter()
+        .filter(|r| targets.contains(&r.callsign))
+        .cloned()
+        .collect();
+
+    if let Err(e) = write_file(
+        app_status.file_tx.clone(),
+        USER_REPORT_DATA.into(),
+        &FileData::Json(serde_json::to_value(&user_report_data).unwrap_or_default()),
+    ).await {
+        return ApiResponse::error(format!("{}", e));
+    }
+
+    for report in &confirmed_reports {
+        append_audit_entry(&app_status, ReportAuditEntry::new(AuditAction::Confirm, report, Some(source.clone()))).await;
+    }
+
+    Reply::lines(response_data)
+}
+
+/// `/report override <卫星名称>`, admin only
+pub async fn override_report_hold(
+    app_status: Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let admin_id = app_status.config.read().await.bot_config.admin_id.clone();
+    if !admin_id.contains(&payload.user_id) {
+        return ApiResponse::error("只有管理员可以放行冲突报告喵".to_string());
+    }
+
+    // Args: override <satellite_name>
+    let Some(sat_name) = args.split_whitespace().nth(1) else {
+        return ApiResponse::error("参数不足喵，格式是 /report override <卫星名称> 喵".to_string());
+    };
+
+    let (mut user_report_data, match_sat) = match load_held_template(&app_status, sat_name).await {
+        Ok(data) => data,
+        Err(response) => return response,
+    };
+
+    let Some(element) = user_report_data.iter_mut()
+        .find(|item| item.name == match_sat)
+        .and_then(|item| item.data.first_mut()) else {
+        return ApiResponse::error(i18n::text("cmd_report_user_no_template"));
+    };
+    if let Some(hold) = element.hold.as_mut() {
+        hold.released_by = Some(payload.user_id);
+    }
+    let pass_time = element.time.clone();
+
+    if let Err(e) = write_file(
+        app_status.file_tx.clone(),
+        USER_REPORT_DATA.into(),
+        &FileData::Json(serde_json::to_value(&user_report_data).unwrap_or_default()),
+    ).await {
+        return ApiResponse::error(format!("{}", e));
+    }
+
+    let source = ReportSource::from_payload(payload, &admin_id);
+    append_audit_entry(&app_status, ReportAuditEntry::for_pass(AuditAction::Override, &match_sat, &pass_time, Some(source))).await;
+
+    Reply::text(format!("{} 的冲突报告已放行，会按时提交喵", match_sat))
 }
 
 pub async fn push_user_report(
+    app_status: Arc<AppStatus>,
     report: &String,
-) -> ApiResponse<Vec<String>> {
-    let report = match data_parser(report).await {
+    payload: &MessageEvent,
+) -> Reply {
+    let mut report = match data_parser(report).await {
         Ok(r) => r,
         Err(e) => return ApiResponse::error(format!("Failed to parse user report: {}", e)),
     };
+    let admin_id = app_status.config.read().await.bot_config.admin_id.clone();
+    report.source = Some(ReportSource::from_payload(payload, &admin_id));
     // report time user input format: "YYYY-MM-DDTHH:MM:SSZ"
     let reported_time: DateTime<Utc> = match DateTime::parse_from_rfc3339(&report.reported_time) {
         Ok(dt) => dt.with_timezone(&Utc),
@@ -478,18 +744,28 @@ pub async fn push_user_report(
     let client = reqwest::Client::new();
     let response = client.get(&get_url).send().await;
 
-    match response {
+    let result = match response {
         Ok(resp) => {
             if resp.status().is_success() {
                 tracing::info!("{}'s report submitted successfully for {}", report.callsign, report.name);
+                Ok(())
             } else {
-                return ApiResponse::error(format!("Failed to submit user report, status: {}", resp.status()));
+                Err(format!("Failed to submit user report, status: {}", resp.status()))
             }
         },
-        Err(e) => return ApiResponse::error(format!("Error submitting user report: {}", e)),
-    }
+        Err(e) => Err(format!("Error submitting user report: {}", e)),
+    };
 
-    ApiResponse::ok(vec!["User report submitted successfully".to_string()])
+    let entry = match &result {
+        Ok(_) => ReportAuditEntry::new(AuditAction::Submit, &report, report.source.clone()),
+        Err(e) => ReportAuditEntry::new(AuditAction::SubmitFailed, &report, report.source.clone()).with_note(e.clone()),
+    };
+    append_audit_entry(&app_status, entry).await;
+
+    match result {
+        Ok(_) => Reply::text("User report submitted successfully"),
+        Err(e) => ApiResponse::error(e),
+    }
 }
 
 #[allow(non_snake_case)]
@@ -0,0 +1,215 @@
+use std::{collections::HashMap, fs};
+
+/// Bundled prefix table, same layout as cty.dat from country-files.com
+/// - only the entities common in the groups are bundled, replace it with the full file
+///   (about 340 entities) for lookups elsewhere; the override syntax of the full file is understood
+pub const CTY_DAT_PATH: &str = "resources/cty.dat";
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct CtyEntity {
+    pub name: String,
+    pub cq_zone: u8,
+    pub itu_zone: u8,
+    pub continent: String,
+    pub lat: f64,
+    /// cty.dat stores west longitude as positive, converted to east-positive here
+    pub lon: f64,
+    pub utc_offset: f64,
+    pub primary_prefix: String,
+}
+
+/// Result of a prefix lookup, zones may be overridden per prefix
+#[derive(Debug, Clone)]
+pub struct CtyLookup<'a> {
+    pub entity: &'a CtyEntity,
+    pub cq_zone: u8,
+    pub itu_zone: u8,
+}
+
+#[derive(Debug, Clone)]
+struct PrefixRule {
+    entity: usize,
+    cq_zone: Option<u8>,
+    itu_zone: Option<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct CtyTable {
+    entities: Vec<CtyEntity>,
+    prefixes: HashMap<String, PrefixRule>,
+    /// `=CALL` entries, matched against the whole callsign
+    exact: HashMap<String, PrefixRule>,
+}
+
+impl CtyTable {
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut table = CtyTable::default();
+
+        for record in content.split(';') {
+            let record = record.trim();
+            if record.is_empty() {
+                continue;
+            }
+
+            // header: name:cq:itu:continent:lat:lon:offset:prefix:
+            let fields: Vec<&str> = record.splitn(9, ':').collect();
+            if fields.len() < 9 {
+                return Err(anyhow::anyhow!("Invalid cty record: {}", record));
+            }
+
+            let entity = CtyEntity {
+                name: fields[0].trim().to_string(),
+                cq_zone: fields[1].trim().parse()?,
+                itu_zone: fields[2].trim().parse()?,
+                continent: fields[3].trim().to_string(),
+                lat: fields[4].trim().parse()?,
+                lon: -fields[5].trim().parse::<f64>()?,
+                utc_offset: -fields[6].trim().parse::<f64>()?,
+                // `*` marks WAE-only entities
+                primary_prefix: fields[7].trim().trim_start_matches('*').to_string(),
+            };
+            let entity_index = table.entities.len();
+            table.entities.push(entity);
+
+            for alias in fields[8].split(',') {
+                let alias: String = alias.split_whitespace().collect();
+                if alias.is_empty() {
+                    continue;
+                }
+                let (prefix, rule) = parse_alias(&alias, entity_index);
+                if let Some(call) = prefix.strip_prefix('=') {
+                    table.exact.insert(call.to_string(), rule);
+                } else {
+                    table.prefixes.insert(prefix, rule);
+                }
+            }
+        }
+
+        Ok(table)
+    }
+
+    pub fn load(path: &str) -> Self {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::warn!("Failed to read prefix table {}: {}", path, e);
+                return CtyTable::default();
+            }
+        };
+
+        match CtyTable::parse(&content) {
+            Ok(table) => {
+                tracing::info!("Loaded {} entities from prefix table {}", table.entities.len(), path);
+                table
+            }
+            Err(e) => {
+                tracing::error!("Failed to parse prefix table {}: {}", path, e);
+                CtyTable::default()
+            }
+        }
+    }
+
+    pub fn has_exact(&self, callsign: &str) -> bool {
+        self.exact.contains_key(callsign)
+    }
+
+    /// Exact callsign match first, then the longest matching prefix
+    pub fn lookup(&self, callsign: &str, prefix_hint: &str) -> Option<CtyLookup<'_>> {
+        let rule = self.exact.get(callsign).or_else(|| {
+            (1..=prefix_hint.len())
+                .rev()
+                .filter_map(|len| prefix_hint.get(..len))
+                .find_map(|prefix| self.prefixes.get(prefix))
+        })?;
+
+        let entity = &self.entities[rule.entity];
+        Some(CtyLookup {
+            entity,
+            cq_zone: rule.cq_zone.unwrap_or(entity.cq_zone),
+            itu_zone: rule.itu_zone.unwrap_or(entity.itu_zone),
+        })
+    }
+}
+
+/// Split `BY0(23)[42]` into the prefix and its zone overrides
+fn parse_alias(alias: &str, entity: usize) -> (String, PrefixRule) {
+    let end = alias.find(['(', '[', '<', '{', '~']).unwrap_or(alias.len());
+    let prefix = alias[..end].to_string();
+
+    let between = |open: char, close: char| {
+        let start = alias.find(open)? + 1;
+        let stop = alias[start..].find(close)? + start;
+        alias[start..stop].parse::<u8>().ok()
+    };
+
+    (prefix, PrefixRule {
+        entity,
+        cq_zone: between('(', ')'),
+        itu_zone: between('[', ']'),
+    })
+}
+
+lazy_static::lazy_static! {
+    pub static ref CTY_TABLE: CtyTable = CtyTable::load(CTY_DAT_PATH);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "\
+China:                    24:  44:  AS:   36.00:  -102.00:    -8.0:  BY:
+    BA,BG,BG9(23)[43],BY,=BY1RX;
+Hong Kong:                24:  44:  AS:   22.28:  -114.18:    -8.0:  VR:
+    VR,=BG2HK(25)[45];
+";
+
+    #[test]
+    fn parses_entities() {
+        let table = CtyTable::parse(TABLE).unwrap();
+        let lookup = table.lookup("BG2XXX", "BG2XXX").unwrap();
+        assert_eq!(lookup.entity.name, "China");
+        assert_eq!(lookup.entity.primary_prefix, "BY");
+        assert_eq!(lookup.entity.continent, "AS");
+        // cty.dat stores west longitude and inverted offsets
+        assert_eq!(lookup.entity.lon, 102.0);
+        assert_eq!(lookup.entity.utc_offset, 8.0);
+        assert_eq!((lookup.cq_zone, lookup.itu_zone), (24, 44));
+    }
+
+    #[test]
+    fn longest_prefix_carries_zone_overrides() {
+        let table = CtyTable::parse(TABLE).unwrap();
+        let lookup = table.lookup("BG9XXX", "BG9XXX").unwrap();
+        assert_eq!((lookup.cq_zone, lookup.itu_zone), (23, 43));
+        assert!(table.lookup("JA1XYZ", "JA1XYZ").is_none());
+    }
+
+    #[test]
+    fn exact_call_overrides_the_prefix() {
+        let table = CtyTable::parse(TABLE).unwrap();
+        let lookup = table.lookup("BG2HK", "BG2HK").unwrap();
+        assert_eq!(lookup.entity.name, "Hong Kong");
+        assert_eq!((lookup.cq_zone, lookup.itu_zone), (25, 45));
+        // only the whole callsign matches
+        assert_eq!(table.lookup("BG2HK/P", "BG2HK").unwrap().entity.name, "China");
+    }
+
+    #[test]
+    fn parses_alias_overrides() {
+        let (prefix, rule) = parse_alias("BY0(23)[42]", 0);
+        assert_eq!(prefix, "BY0");
+        assert_eq!((rule.cq_zone, rule.itu_zone), (Some(23), Some(42)));
+        let (prefix, rule) = parse_alias("=BY1RX", 0);
+        assert_eq!(prefix, "=BY1RX");
+        assert_eq!((rule.cq_zone, rule.itu_zone), (None, None));
+    }
+
+    #[test]
+    fn rejects_invalid_records() {
+        assert!(CtyTable::parse("China: 24: 44: AS;").is_err());
+        assert!(CtyTable::parse("China: XX: 44: AS: 36.00: -102.00: -8.0: BY: BY;").is_err());
+        assert!(CtyTable::parse("").unwrap().lookup("BG2XXX", "BG2XXX").is_none());
+    }
+}
@@ -0,0 +1,2 @@
+pub mod cty;
+pub mod parser;
\ No newline at end of file
@@ -0,0 +1,273 @@
+use regex::Regex;
+use std::fmt;
+use super::cty::{CtyLookup, CtyTable, CTY_TABLE};
+
+lazy_static::lazy_static! {
+    /// prefix (1-3 chars, at least one letter) + call area digit(s) + suffix letters,
+    /// long enough for `3DA0ABC`, `ZS6ABCDE` and special event calls like `VI2000OLYMPICS`
+    static ref BASE_CALLSIGN_RE: Regex =
+        Regex::new(r"^(?:[A-Z]{1,3}|[A-Z][0-9][A-Z]?|[0-9][A-Z]{1,2})[0-9]{1,4}[A-Z]{1,8}$").unwrap();
+    /// location prefix such as `B0`, `VK9X`, `KH6`
+    static ref LOCATION_PREFIX_RE: Regex =
+        Regex::new(r"^(?:[A-Z]{1,2}|[A-Z][0-9]|[0-9][A-Z])[0-9]?[A-Z]?$").unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortableDesignator {
+    /// `/P`
+    Portable,
+    /// `/M`
+    Mobile,
+    /// `/MM`, no DXCC entity
+    MaritimeMobile,
+    /// `/AM`, no DXCC entity
+    AeronauticalMobile,
+    /// `/QRP`
+    Qrp,
+    /// `/0` - `/9`, operating from another call area
+    CallArea(u8),
+}
+
+impl PortableDesignator {
+    fn parse(part: &str) -> Option<Self> {
+        match part {
+            "P" => Some(PortableDesignator::Portable),
+            "M" => Some(PortableDesignator::Mobile),
+            "MM" => Some(PortableDesignator::MaritimeMobile),
+            "AM" => Some(PortableDesignator::AeronauticalMobile),
+            "QRP" => Some(PortableDesignator::Qrp),
+            _ if part.len() == 1 => part.parse::<u8>().ok().map(PortableDesignator::CallArea),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PortableDesignator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortableDesignator::Portable => write!(f, "P"),
+            PortableDesignator::Mobile => write!(f, "M"),
+            PortableDesignator::MaritimeMobile => write!(f, "MM"),
+            PortableDesignator::AeronauticalMobile => write!(f, "AM"),
+            PortableDesignator::Qrp => write!(f, "QRP"),
+            PortableDesignator::CallArea(area) => write!(f, "{}", area),
+        }
+    }
+}
+
+/// Structured callsign, e.g. `B0/NA1SS`, `BA1ABC/P`, `JA1XYZ/VK2`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Callsign {
+    /// location prefix before the base callsign, `B0` in `B0/NA1SS`
+    pub prefix: Option<String>,
+    /// the licensed callsign, used for identity checks
+    pub base: String,
+    /// location suffix after the base callsign, `VK2` in `JA1XYZ/VK2`
+    pub suffix: Option<String>,
+    pub portable: Option<PortableDesignator>,
+}
+
+impl Callsign {
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim().to_uppercase();
+        if input.is_empty() || !input.chars().all(|c| c.is_ascii_alphanumeric() || c == '/') {
+            return None;
+        }
+
+        let parts: Vec<&str> = input.split('/').collect();
+        if parts.len() > 3 || parts.iter().any(|p| p.is_empty()) {
+            return None;
+        }
+
+        // the longest part that looks like a callsign is the base
+        let base_index = parts
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| BASE_CALLSIGN_RE.is_match(p))
+            .max_by_key(|(_, p)| p.len())
+            .map(|(i, _)| i)?;
+
+        let prefix = match base_index {
+            0 => None,
+            1 if LOCATION_PREFIX_RE.is_match(parts[0]) || BASE_CALLSIGN_RE.is_match(parts[0]) => {
+                Some(parts[0].to_string())
+            }
+            _ => return None,
+        };
+
+        let mut suffix = None;
+        let mut portable = None;
+        for part in &parts[base_index + 1..] {
+            if let Some(designator) = PortableDesignator::parse(part) {
+                if portable.replace(designator).is_some() {
+                    return None;
+                }
+            } else if LOCATION_PREFIX_RE.is_match(part) && suffix.is_none() && prefix.is_none() {
+                suffix = Some(part.to_string());
+            } else {
+                return None;
+            }
+        }
+
+        Some(Callsign {
+            prefix,
+            base: parts[base_index].to_string(),
+            suffix,
+            portable,
+        })
+    }
+
+    /// Prefix used for the entity lookup, `None` for /MM and /AM
+    pub fn effective_prefix(&self) -> Option<String> {
+        if matches!(
+            self.portable,
+            Some(PortableDesignator::MaritimeMobile) | Some(PortableDesignator::AeronauticalMobile)
+        ) {
+            return None;
+        }
+
+        if let Some(location) = self.prefix.as_ref().or(self.suffix.as_ref()) {
+            return Some(location.clone());
+        }
+
+        // BA1ABC/0 is looked up as BA0ABC so call area overrides apply
+        if let Some(PortableDesignator::CallArea(area)) = self.portable {
+            let digit_pos = self.base.char_indices().skip(1).find(|(_, c)| c.is_ascii_digit());
+            if let Some((pos, _)) = digit_pos {
+                let mut replaced = self.base.clone();
+                replaced.replace_range(pos..pos + 1, &area.to_string());
+                return Some(replaced);
+            }
+        }
+
+        Some(self.base.clone())
+    }
+
+    /// Country and zones from the bundled prefix table
+    pub fn lookup(&self) -> Option<CtyLookup<'static>> {
+        self.lookup_in(&CTY_TABLE)
+    }
+
+    fn lookup_in<'a>(&self, table: &'a CtyTable) -> Option<CtyLookup<'a>> {
+        let prefix = self.effective_prefix()?;
+        let full = self.to_string();
+        // /P, /M and /QRP don't move the station, so `=BG2HK` still applies to `BG2HK/P`
+        let keeps_location = self.prefix.is_none()
+            && self.suffix.is_none()
+            && !matches!(self.portable, Some(PortableDesignator::CallArea(_)));
+        let exact = if keeps_location && !table.has_exact(&full) { &self.base } else { &full };
+        table.lookup(exact, &prefix)
+    }
+}
+
+impl fmt::Display for Callsign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(prefix) = &self.prefix {
+            write!(f, "{}/", prefix)?;
+        }
+        write!(f, "{}", self.base)?;
+        if let Some(suffix) = &self.suffix {
+            write!(f, "/{}", suffix)?;
+        }
+        if let Some(portable) = &self.portable {
+            write!(f, "/{}", portable)?;
+        }
+        Ok(())
+    }
+}
+
+/// Short region text for replies, e.g. `China, AS (CQ 24 / ITU 44)`
+pub fn callsign_region_text(callsign: &str) -> Option<String> {
+    let lookup = Callsign::parse(callsign)?.lookup()?;
+    Some(format!(
+        "{}, {} (CQ {} / ITU {})",
+        lookup.entity.name, lookup.entity.continent, lookup.cq_zone, lookup.itu_zone
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_portable_suffix() {
+        let call = Callsign::parse("bg2xxx/p").unwrap();
+        assert_eq!(call.base, "BG2XXX");
+        assert_eq!(call.prefix, None);
+        assert_eq!(call.suffix, None);
+        assert_eq!(call.portable, Some(PortableDesignator::Portable));
+        assert_eq!(call.effective_prefix().as_deref(), Some("BG2XXX"));
+        assert_eq!(call.to_string(), "BG2XXX/P");
+    }
+
+    #[test]
+    fn parses_location_prefix_and_suffix() {
+        let call = Callsign::parse("VR2/BG2XXX").unwrap();
+        assert_eq!(call.prefix.as_deref(), Some("VR2"));
+        assert_eq!(call.base, "BG2XXX");
+        assert_eq!(call.effective_prefix().as_deref(), Some("VR2"));
+
+        let call = Callsign::parse("JA1XYZ/VK2").unwrap();
+        assert_eq!(call.base, "JA1XYZ");
+        assert_eq!(call.suffix.as_deref(), Some("VK2"));
+        assert_eq!(call.effective_prefix().as_deref(), Some("VK2"));
+    }
+
+    #[test]
+    fn call_area_replaces_the_digit() {
+        let call = Callsign::parse("BG2XXX/9").unwrap();
+        assert_eq!(call.portable, Some(PortableDesignator::CallArea(9)));
+        assert_eq!(call.effective_prefix().as_deref(), Some("BG9XXX"));
+    }
+
+    #[test]
+    fn maritime_mobile_has_no_entity() {
+        let call = Callsign::parse("BG2XXX/MM").unwrap();
+        assert_eq!(call.effective_prefix(), None);
+        assert!(call.lookup().is_none());
+    }
+
+    #[test]
+    fn accepts_long_prefixes_and_suffixes() {
+        for input in ["3DA0ABC", "ZS6ABCDE", "VI2000OLYMPICS", "E51ABC", "4U1UN", "3DA0ABC/P"] {
+            assert!(Callsign::parse(input).is_some(), "{}", input);
+        }
+        assert_eq!(Callsign::parse("VI2000OLYMPICS").unwrap().base, "VI2000OLYMPICS");
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        for input in ["", "   ", "BG2-XXX", "BG2XXX//P", "/BG2XXX", "A/B/C/D", "12345", "BG2XXX/P/M", "VR2/BG2XXX/VK2", "BG2XXX/XYZ1"] {
+            assert_eq!(Callsign::parse(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn looks_up_the_bundled_table() {
+        let lookup = Callsign::parse("VR2/BG2XXX").unwrap().lookup().unwrap();
+        assert_eq!(lookup.entity.name, "Hong Kong");
+
+        // BG9 carries zone overrides in cty.dat
+        let lookup = Callsign::parse("BG2XXX/9").unwrap().lookup().unwrap();
+        assert_eq!(lookup.entity.name, "China");
+        assert_eq!((lookup.cq_zone, lookup.itu_zone), (23, 43));
+
+        assert_eq!(callsign_region_text("BG2XXX/P").as_deref(), Some("China, AS (CQ 24 / ITU 44)"));
+    }
+
+    #[test]
+    fn exact_entries_match_the_base_callsign() {
+        let table = CtyTable::parse(
+            "China: 24: 44: AS: 36.00: -102.00: -8.0: BY:\n    BA,BG,BY;\n\
+             Hong Kong: 24: 44: AS: 22.28: -114.18: -8.0: VR:\n    VR,=BG2HK(25)[45];\n",
+        )
+        .unwrap();
+        let name = |call: &str| Callsign::parse(call).unwrap().lookup_in(&table).unwrap().entity.name.clone();
+        assert_eq!(name("BG2HK"), "Hong Kong");
+        assert_eq!(name("BG2HK/P"), "Hong Kong");
+        assert_eq!(name("BG2HK/QRP"), "Hong Kong");
+        // operating from somewhere else moves the station out of the exact entry
+        assert_eq!(name("BG2HK/9"), "China");
+        assert_eq!(name("BY/BG2HK"), "China");
+    }
+}
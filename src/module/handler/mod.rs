@@ -1 +1,3 @@
+pub mod quota;
+pub mod registry;
 pub mod router;
\ No newline at end of file
@@ -0,0 +1,473 @@
+use crate::{
+    app_status::AppStatus,
+    config::QuotaLimit,
+    fs::handler::{check_file_exists, delete_file, load_file, write_file, FileData, FileFormat},
+    module::handler::registry::COMMAND_REGISTRY,
+    msg::prelude::{MessageElement, MessageEvent},
+    response::{ApiResponse, Reply},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use chrono::{DateTime, Utc};
+
+const QUOTA_BANS: &str = "runtime_data/quota_bans.json";
+/// Written by the old roaming submit limiter, its `block` flags are moved into `QUOTA_BANS`
+const LEGACY_ROAMING_SUBMIT_HISTORY: &str = "runtime_data/user_roaming_submit_history.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuotaBan {
+    pub user_id: u64,
+    /// RFC3339, `None` for a permanent ban
+    pub until: Option<String>,
+    pub reason: Option<String>,
+    pub banned_by: u64,
+    /// RFC3339
+    pub time: String,
+}
+
+/// Entry of the old roaming submit history, only the block flag is still of interest
+#[derive(Deserialize, Debug)]
+struct LegacyRoamingSubmit {
+    user_id: u64,
+    #[serde(default)]
+    block: bool,
+}
+
+impl QuotaBan {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        match &self.until {
+            Some(until) => DateTime::parse_from_rfc3339(until)
+                .map(|t| t.with_timezone(&Utc) > now)
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+}
+
+/// (user id, quota key) -> recent uses, oldest first
+type UsageWindows = HashMap<(u64, String), VecDeque<DateTime<Utc>>>;
+
+/// Sliding windows live in memory only, bans are persisted
+#[derive(Debug, Default)]
+pub struct QuotaState {
+    windows: Mutex<UsageWindows>,
+    /// `None` until first read from disk
+    bans: Mutex<Option<Vec<QuotaBan>>>,
+}
+
+impl QuotaState {
+    pub fn new() -> Arc<Self> {
+        Arc::new(QuotaState::default())
+    }
+}
+
+/// Most specific configured limit, then the limits declared in `commands.toml`
+pub fn lookup_limit(limits: &HashMap<String, QuotaLimit>, key: &str) -> Option<QuotaLimit> {
+    let command = key.split('.').next().unwrap_or(key);
+    limits.get(key).cloned()
+        .or_else(|| COMMAND_REGISTRY.rate_limit(key))
+        .or_else(|| limits.get(command).cloned())
+        .or_else(|| COMMAND_REGISTRY.rate_limit(command))
+}
+
+pub fn format_duration(secs: i64) -> String {
+    match secs {
+        s if s >= 86400 => format!("{}天{}小时", s / 86400, s % 86400 / 3600),
+        s if s >= 3600 => format!("{}小时{}分钟", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}分钟", (s + 59) / 60),
+        s => format!("{}秒", s.max(1)),
+    }
+}
+
+async fn read_bans(
+    app_status: &AppStatus,
+) -> anyhow::Result<Vec<QuotaBan>> {
+    let mut cache = app_status.quota.bans.lock().await;
+    if let Some(bans) = cache.as_ref() {
+        return Ok(bans.clone());
+    }
+
+    let tx_filerequest = app_status.file_tx.clone();
+    let bans: Vec<QuotaBan> = if check_file_exists(tx_filerequest.clone(), QUOTA_BANS.to_string()).await {
+        match load_file(tx_filerequest, QUOTA_BANS.to_string(), FileFormat::Json).await? {
+            FileData::Json(data) => serde_json::from_value(data)?,
+            _ => return Err(anyhow::anyhow!("Unexpected file format received")),
+        }
+    } else {
+        Vec::new()
+    };
+    let bans = migrate_legacy_blocks(app_status, bans).await;
+    *cache = Some(bans.clone());
+    Ok(bans)
+}
+
+/// Users blocked in the old roaming submit history become permanent bans, once
+/// - the old file is removed after the bans are written, so an unblock is not undone on restart
+async fn migrate_legacy_blocks(
+    app_status: &AppStatus,
+    mut bans: Vec<QuotaBan>,
+) -> Vec<QuotaBan> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), LEGACY_ROAMING_SUBMIT_HISTORY.to_string()).await {
+        return bans;
+    }
+    let legacy: Vec<LegacyRoamingSubmit> = match load_file(tx_filerequest.clone(), LEGACY_ROAMING_SUBMIT_HISTORY.to_string(), FileFormat::Json).await {
+        Ok(FileData::Json(data)) => match serde_json::from_value(data) {
+            Ok(legacy) => legacy,
+            Err(e) => {
+                tracing::error!("Failed to parse {}: {}", LEGACY_ROAMING_SUBMIT_HISTORY, e);
+                return bans;
+            }
+        },
+        Ok(_) => return bans,
+        Err(e) => {
+            tracing::error!("Failed to read {}: {}", LEGACY_ROAMING_SUBMIT_HISTORY, e);
+            return bans;
+        }
+    };
+
+    let now = Utc::now();
+    let mut migrated = 0;
+    for entry in legacy.iter().filter(|e| e.block) {
+        if active_ban(&bans, entry.user_id, now).is_some() {
+            continue;
+        }
+        bans.push(QuotaBan {
+            user_id: entry.user_id,
+            until: None,
+            reason: Some("旧漫游提交记录中的封禁".to_string()),
+            banned_by: 0,
+            time: now.to_rfc3339(),
+        });
+        migrated += 1;
+    }
+    if migrated > 0
+        && let Err(e) = write_file(tx_filerequest.clone(), QUOTA_BANS.to_string(), &FileData::Json(serde_json::to_value(&bans).unwrap_or_default())).await
+    {
+        // keep the old file so the next start tries again
+        tracing::error!("Failed to write migrated quota bans: {}", e);
+        return bans;
+    }
+    if let Err(e) = delete_file(tx_filerequest, LEGACY_ROAMING_SUBMIT_HISTORY.to_string()).await {
+        tracing::error!("Failed to remove {}: {}", LEGACY_ROAMING_SUBMIT_HISTORY, e);
+    }
+    tracing::info!("Migrated {} blocked users from {}", migrated, LEGACY_ROAMING_SUBMIT_HISTORY);
+    bans
+}
+
+async fn write_bans(
+    app_status: &AppStatus,
+    bans: Vec<QuotaBan>,
+) -> anyhow::Result<()> {
+    write_file(
+        app_status.file_tx.clone(),
+        QUOTA_BANS.to_string(),
+        &FileData::Json(serde_json::to_value(&bans)?),
+    ).await?;
+    *app_status.quota.bans.lock().await = Some(bans);
+    Ok(())
+}
+
+fn active_ban(bans: &[QuotaBan], user_id: u64, now: DateTime<Utc>) -> Option<QuotaBan> {
+    bans.iter().find(|b| b.user_id == user_id && b.is_active(now)).cloned()
+}
+
+/// Called by the router before every command, limited or not
+pub async fn check_ban(
+    app_status: &AppStatus,
+    user_id: u64,
+) -> Result<(), String> {
+    let now = Utc::now();
+    let ban = match read_bans(app_status).await {
+        Ok(bans) => active_ban(&bans, user_id, now),
+        Err(e) => {
+            // do not lock everyone out because of a broken file
+            tracing::error!("Failed to read quota bans: {}", e);
+            None
+        }
+    };
+    if let Some(ban) = ban {
+        let until = ban.until
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+            .map(|t| format!("，{}后解封", format_duration((t.with_timezone(&Utc) - now).num_seconds())))
+            .unwrap_or_default();
+        return Err(format!("你已被封禁，无法使用Rinko喵{}。请联系管理员解封。", until));
+    }
+    Ok(())
+}
+
+/// Called by the router for commands with a quota key, after `check_ban`
+/// - admins skip the limits
+/// - a use is recorded only when it is allowed
+pub async fn check_quota(
+    app_status: &AppStatus,
+    user_id: u64,
+    key: &str,
+) -> Result<(), String> {
+    let now = Utc::now();
+    let (is_admin, limit) = {
+        let config = app_status.config.read().await;
+        (
+            config.bot_config.admin_id.contains(&user_id),
+            lookup_limit(&config.quota_config.limits, key),
+        )
+    };
+    let Some(limit) = limit.filter(|_| !is_admin) else {
+        return Ok(());
+    };
+
+    let window = chrono::Duration::seconds(limit.window_secs as i64);
+    let mut windows = app_status.quota.windows.lock().await;
+    let uses = windows.entry((user_id, key.to_string())).or_default();
+    while uses.front().is_some_and(|t| now - *t >= window) {
+        uses.pop_front();
+    }
+
+    if uses.len() >= limit.max {
+        let retry_after = uses.front().map(|t| (*t + window - now).num_seconds()).unwrap_or(0);
+        return Err(format!(
+            "{} 的使用次数已达上限 ({}次/{})，请在 {} 后再试喵^ ^)/",
+            key,
+            limit.max,
+            format_duration(limit.window_secs as i64),
+            format_duration(retry_after)
+        ));
+    }
+
+    uses.push_back(now);
+    Ok(())
+}
+
+/// QQ id from the argument, or the first @ in the message
+fn target_user(arg: Option<&str>, payload: &MessageEvent) -> Option<u64> {
+    arg.and_then(|a| a.trim_start_matches('@').parse::<u64>().ok()).or_else(|| {
+        payload.message.iter().find_map(|elem| match elem {
+            MessageElement::At { qq, .. } => qq.parse::<u64>().ok(),
+            _ => None,
+        })
+    })
+}
+
+/// Longest timed ban, longer ones should be permanent
+const MAX_BAN_DAYS: i64 = 365;
+
+/// `30m`, `2h`, `3d`, also `30分` `2时` `3天`
+/// - anything else, including zero and over `MAX_BAN_DAYS`, is not a duration and becomes part of the reason
+fn parse_ban_duration(input: &str) -> Option<chrono::Duration> {
+    let (unit_start, unit) = input.char_indices().last()?;
+    let number = input[..unit_start].parse::<i64>().ok().filter(|n| *n > 0)?;
+    let duration = match unit.to_ascii_lowercase() {
+        'm' | '分' => chrono::Duration::try_minutes(number),
+        'h' | '时' => chrono::Duration::try_hours(number),
+        'd' | '天' => chrono::Duration::try_days(number),
+        _ => None,
+    }?;
+    (duration <= chrono::Duration::days(MAX_BAN_DAYS)).then_some(duration)
+}
+
+enum QuotaAction {
+    Info(u64),
+    Block {
+        target: u64,
+        duration: Option<chrono::Duration>,
+        reason: Option<String>,
+    },
+    Unblock(u64),
+}
+
+/// Args: <block|unblock|info> <QQ|@> [时长] [原因]
+fn parse_quota_action(
+    args: &str,
+    payload: &MessageEvent,
+) -> Result<QuotaAction, String> {
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let subcommand = args.first().copied().unwrap_or("");
+    let Some(target) = target_user(args.get(1).copied(), payload) else {
+        return Err("请提供QQ号或@目标用户喵".to_string());
+    };
+    // rest of the args start after the QQ id, or right after the subcommand for an @
+    let rest_start = if args.get(1).is_some_and(|a| a.trim_start_matches('@').parse::<u64>().is_ok()) { 2 } else { 1 };
+
+    match subcommand {
+        "block" => {
+            let mut rest = args[rest_start..].iter().copied().peekable();
+            let duration = rest.peek().and_then(|d| parse_ban_duration(d));
+            if duration.is_some() {
+                rest.next();
+            }
+            let reason = rest.collect::<Vec<&str>>().join(" ");
+            Ok(QuotaAction::Block {
+                target,
+                duration,
+                reason: if reason.is_empty() { None } else { Some(reason) },
+            })
+        }
+        "unblock" => Ok(QuotaAction::Unblock(target)),
+        "info" => Ok(QuotaAction::Info(target)),
+        _ => Err("未知的子命令喵，可用: block | unblock | info".to_string()),
+    }
+}
+
+/// `/quota` for own usage, admins also get `block <QQ> [时长] [原因]`, `unblock <QQ>`, `info <QQ>`
+pub async fn quota_command(
+    app_status: Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    if args.trim().is_empty() {
+        return quota_info(&app_status, payload.user_id).await;
+    }
+
+    let is_admin = {
+        let config_guard = app_status.config.read().await;
+        config_guard.bot_config.admin_id.contains(&payload.user_id)
+    };
+    if !is_admin {
+        return ApiResponse::error("只有管理员可以管理使用限制喵".to_string());
+    }
+
+    match parse_quota_action(args, payload) {
+        Ok(QuotaAction::Info(target)) => quota_info(&app_status, target).await,
+        Ok(QuotaAction::Block { target, duration, reason }) => {
+            block_user(&app_status, target, duration, reason, payload.user_id).await
+        }
+        Ok(QuotaAction::Unblock(target)) => unblock_user(&app_status, target).await,
+        Err(e) => ApiResponse::error(e),
+    }
+}
+
+async fn block_user(
+    app_status: &AppStatus,
+    target: u64,
+    duration: Option<chrono::Duration>,
+    reason: Option<String>,
+    banned_by: u64,
+) -> Reply {
+    let mut bans = match read_bans(app_status).await {
+        Ok(bans) => bans,
+        Err(e) => return ApiResponse::error(format!("读取封禁列表失败: {}", e)),
+    };
+    let now = Utc::now();
+    bans.retain(|b| b.user_id != target);
+    bans.push(QuotaBan {
+        user_id: target,
+        until: duration.map(|d| (now + d).to_rfc3339()),
+        reason,
+        banned_by,
+        time: now.to_rfc3339(),
+    });
+
+    let text = match duration {
+        Some(d) => format!("已封禁 {} {}", target, format_duration(d.num_seconds())),
+        None => format!("已永久封禁 {}", target),
+    };
+    match write_bans(app_status, bans).await {
+        Ok(_) => Reply::text(text),
+        Err(e) => ApiResponse::error(format!("文件写入失败: {}", e)),
+    }
+}
+
+/// Also clears the usage windows so the user starts fresh
+async fn unblock_user(
+    app_status: &AppStatus,
+    target: u64,
+) -> Reply {
+    let mut bans = match read_bans(app_status).await {
+        Ok(bans) => bans,
+        Err(e) => return ApiResponse::error(format!("读取封禁列表失败: {}", e)),
+    };
+    let before = bans.len();
+    bans.retain(|b| b.user_id != target);
+    if before == bans.len() {
+        return Reply::text(format!("{} 没有被封禁喵", target));
+    }
+
+    app_status.quota.windows.lock().await.retain(|key, _| key.0 != target);
+    match write_bans(app_status, bans).await {
+        Ok(_) => Reply::text(format!("已解封 {}，使用记录已清空", target)),
+        Err(e) => ApiResponse::error(format!("文件写入失败: {}", e)),
+    }
+}
+
+async fn quota_info(
+    app_status: &AppStatus,
+    user_id: u64,
+) -> Reply {
+    let now = Utc::now();
+    let mut data = vec![format!("{} 的使用情况:", user_id)];
+
+    match read_bans(app_status).await.map(|bans| active_ban(&bans, user_id, now)) {
+        Ok(Some(ban)) => data.push(format!(
+            "封禁中: {}，原因: {}，操作人: {}",
+            ban.until.as_deref().unwrap_or("永久"),
+            ban.reason.as_deref().unwrap_or("-"),
+            ban.banned_by
+        )),
+        Ok(None) => {}
+        Err(e) => data.push(format!("读取封禁列表失败: {}", e)),
+    }
+
+    let limits = {
+        let config_guard = app_status.config.read().await;
+        config_guard.quota_config.limits.clone()
+    };
+    let windows = app_status.quota.windows.lock().await;
+    let mut usage: Vec<String> = windows
+        .iter()
+        .filter(|((id, _), _)| *id == user_id)
+        .filter_map(|((_, key), uses)| {
+            let limit = lookup_limit(&limits, key)?;
+            let window = chrono::Duration::seconds(limit.window_secs as i64);
+            let count = uses.iter().filter(|t| now - **t < window).count();
+            (count > 0).then(|| format!("{}: {}/{} ({})", key, count, limit.max, format_duration(limit.window_secs as i64)))
+        })
+        .collect();
+    usage.sort();
+
+    if usage.is_empty() {
+        data.push("最近没有使用记录".to_string());
+    } else {
+        data.extend(usage);
+    }
+    Reply::lines(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ascii_units() {
+        assert_eq!(parse_ban_duration("30m"), Some(chrono::Duration::minutes(30)));
+        assert_eq!(parse_ban_duration("2H"), Some(chrono::Duration::hours(2)));
+        assert_eq!(parse_ban_duration("3d"), Some(chrono::Duration::days(3)));
+        assert_eq!(parse_ban_duration("3x"), None);
+        assert_eq!(parse_ban_duration("d"), None);
+        assert_eq!(parse_ban_duration(""), None);
+    }
+
+    #[test]
+    fn cjk_input_does_not_panic() {
+        assert_eq!(parse_ban_duration("3天"), Some(chrono::Duration::days(3)));
+        assert_eq!(parse_ban_duration("2时"), Some(chrono::Duration::hours(2)));
+        assert_eq!(parse_ban_duration("广告"), None);
+        assert_eq!(parse_ban_duration("天"), None);
+    }
+
+    #[test]
+    fn rejects_zero_and_negative() {
+        assert_eq!(parse_ban_duration("0d"), None);
+        assert_eq!(parse_ban_duration("-3d"), None);
+    }
+
+    #[test]
+    fn rejects_overflow_and_overlong_bans() {
+        assert_eq!(parse_ban_duration("99999999999d"), None);
+        assert_eq!(parse_ban_duration("9223372036854775807m"), None);
+        assert_eq!(parse_ban_duration("99999999999999999999d"), None);
+        assert_eq!(parse_ban_duration("366d"), None);
+        assert_eq!(parse_ban_duration("365d"), Some(chrono::Duration::days(365)));
+    }
+}
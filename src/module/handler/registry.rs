@@ -0,0 +1,335 @@
+use crate::{
+    app_status::AppStatus,
+    config::{chat_in, QuotaLimit},
+    module::handler::quota::{format_duration, lookup_limit},
+    msg::prelude::MessageEvent,
+    response::{ApiResponse, Reply},
+    COMMAND_TOML_PATH,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The shipped `commands.toml`, used when the file on disk is missing or broken
+const BUILTIN_COMMANDS: &str = include_str!("../../../commands.toml");
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    #[default]
+    User,
+    Admin,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArgSpec {
+    pub name: String,
+    /// Missing required args are answered with the usage instead of reaching the handler
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubcommandSpec {
+    pub name: String,
+    /// Used when the args match no other sub-command, e.g. `/r <呼号> <网格>` is `roaming.add`
+    #[serde(default)]
+    pub implicit: bool,
+    #[serde(default)]
+    pub args: Vec<ArgSpec>,
+    #[serde(default)]
+    pub permission: Permission,
+    #[serde(default)]
+    pub rate_limit: Option<QuotaLimit>,
+    #[serde(default)]
+    pub help: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommandSpec {
+    /// Canonical name, also the quota key
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<ArgSpec>,
+    #[serde(default)]
+    pub permission: Permission,
+    /// Reply for users without the permission
+    #[serde(default)]
+    pub denied: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Same rules as the other per-chat lists, `None` for every chat the bot answers in
+    #[serde(default)]
+    pub group_id: Option<Vec<u64>>,
+    #[serde(default)]
+    pub user_id: Option<Vec<u64>>,
+    #[serde(default)]
+    pub rate_limit: Option<QuotaLimit>,
+    #[serde(default)]
+    pub help: String,
+    #[serde(default, rename = "subcommand")]
+    pub subcommands: Vec<SubcommandSpec>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CommandRegistry {
+    #[serde(default, rename = "command")]
+    pub commands: Vec<CommandSpec>,
+}
+
+lazy_static::lazy_static! {
+    pub static ref COMMAND_REGISTRY: CommandRegistry = CommandRegistry::load(COMMAND_TOML_PATH);
+}
+
+/// ` <必填> [可选]`
+fn format_args(args: &[ArgSpec]) -> String {
+    args.iter()
+        .map(|arg| if arg.required { format!(" <{}>", arg.name) } else { format!(" [{}]", arg.name) })
+        .collect()
+}
+
+fn format_limit(limit: &QuotaLimit) -> String {
+    format!("{}次/{}", limit.max, format_duration(limit.window_secs as i64))
+}
+
+impl SubcommandSpec {
+    fn usage(&self, command: &str) -> String {
+        if self.implicit {
+            format!("/{}{}", command, format_args(&self.args))
+        } else {
+            format!("/{} {}{}", command, self.name, format_args(&self.args))
+        }
+    }
+}
+
+impl CommandSpec {
+    pub fn available_in(&self, payload: &MessageEvent) -> bool {
+        chat_in(payload, &self.group_id, &self.user_id)
+    }
+
+    fn visible_to(&self, payload: &MessageEvent, is_admin: bool) -> bool {
+        self.available_in(payload) && (self.permission == Permission::User || is_admin)
+    }
+
+    /// Sub-command named by the first word of the args and the args after it,
+    /// an implicit one also matches args that do not name any sub-command
+    pub fn subcommand<'a>(&self, args: &'a str) -> Option<(&SubcommandSpec, &'a str)> {
+        let explicit = self.subcommands
+            .iter()
+            .find_map(|s| {
+                args.strip_prefix(s.name.as_str())
+                    .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+                    .map(|rest| (s, rest))
+            });
+        if explicit.is_some() || args.trim().is_empty() {
+            return explicit;
+        }
+        self.subcommands.iter().find(|s| s.implicit).map(|s| (s, args))
+    }
+
+    /// Whether any limit is declared, commands without one are not counted at all
+    pub fn rate_limited(&self) -> bool {
+        self.rate_limit.is_some() || self.subcommands.iter().any(|s| s.rate_limit.is_some())
+    }
+
+    /// Permission and required args, the error is the reply
+    pub fn check(&self, args: &str, is_admin: bool) -> Result<(), String> {
+        if self.permission == Permission::Admin && !is_admin {
+            return Err(self.denied.clone().unwrap_or_else(|| "该命令只开放给管理员喵".to_string()));
+        }
+
+        let (spec_args, rest, usage) = match self.subcommand(args) {
+            Some((subcommand, rest)) => {
+                if subcommand.permission == Permission::Admin && !is_admin {
+                    return Err(format!("/{} {} 只开放给管理员喵", self.name, subcommand.name));
+                }
+                (&subcommand.args, rest, subcommand.usage(&self.name))
+            }
+            // a bare command with sub-commands is left to the handler
+            None if !self.subcommands.is_empty() => return Ok(()),
+            None => (&self.args, args, format!("/{}{}", self.name, format_args(&self.args))),
+        };
+        let required = spec_args.iter().filter(|arg| arg.required).count();
+        if rest.split_whitespace().count() < required {
+            return Err(format!("参数不足喵，用法: {}", usage));
+        }
+        Ok(())
+    }
+}
+
+impl CommandRegistry {
+    /// `path` if it can be read and parsed, the built-in registry otherwise
+    fn load(path: &str) -> Self {
+        let builtin = || -> CommandRegistry {
+            toml::from_str(BUILTIN_COMMANDS).expect("Built-in commands.toml is invalid")
+        };
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::warn!("Failed to read {}: {}, using the built-in commands", path, e);
+                return builtin();
+            }
+        };
+        let registry: CommandRegistry = match toml::from_str(&content) {
+            Ok(registry) => registry,
+            Err(e) => {
+                tracing::error!("Failed to parse {}: {}, using the built-in commands", path, e);
+                return builtin();
+            }
+        };
+
+        let mut seen = HashSet::new();
+        for spec in registry.commands.iter().filter(|c| c.enabled) {
+            for name in std::iter::once(&spec.name).chain(&spec.aliases) {
+                if !seen.insert(name.as_str()) {
+                    tracing::warn!("Command name {} is declared twice in {}, the first one wins", name, path);
+                }
+            }
+        }
+        registry
+    }
+
+    /// Enabled command by name or alias
+    pub fn resolve(&self, command: &str) -> Option<&CommandSpec> {
+        self.commands
+            .iter()
+            .filter(|c| c.enabled)
+            .find(|c| c.name == command || c.aliases.iter().any(|a| a == command))
+    }
+
+    /// Announced to the core in the handshake, aliases included
+    pub fn names(&self) -> Vec<String> {
+        self.commands
+            .iter()
+            .filter(|c| c.enabled)
+            .flat_map(|c| std::iter::once(c.name.clone()).chain(c.aliases.iter().cloned()))
+            .collect()
+    }
+
+    /// Declared limit for a quota key, `command` or `command.subcommand`
+    pub fn rate_limit(&self, key: &str) -> Option<QuotaLimit> {
+        let (command, subcommand) = match key.split_once('.') {
+            Some((command, subcommand)) => (command, Some(subcommand)),
+            None => (key, None),
+        };
+        let spec = self.commands.iter().find(|c| c.name == command)?;
+        match subcommand {
+            Some(subcommand) => spec.subcommands.iter().find(|s| s.name == subcommand)?.rate_limit.clone(),
+            None => spec.rate_limit.clone(),
+        }
+    }
+}
+
+/// `/help` lists the commands usable in this chat, `/help <命令>` shows the usage of one
+pub async fn help_command(
+    app_status: &AppStatus,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let (is_admin, limits) = {
+        let config_guard = app_status.config.read().await;
+        (
+            config_guard.bot_config.admin_id.contains(&payload.user_id),
+            config_guard.quota_config.limits.clone(),
+        )
+    };
+
+    let Some(name) = args.split_whitespace().next() else {
+        let mut data = vec!["Rinko 可用的命令:".to_string()];
+        for spec in COMMAND_REGISTRY.commands.iter().filter(|c| c.enabled && c.visible_to(payload, is_admin)) {
+            data.push(format!("/{} {}", spec.name, spec.help));
+        }
+        data.push("/help <命令> 查看详细用法".to_string());
+        return Reply::lines(data);
+    };
+
+    let Some(spec) = COMMAND_REGISTRY
+        .resolve(name.trim_start_matches('/'))
+        .filter(|spec| spec.visible_to(payload, is_admin))
+    else {
+        return ApiResponse::error(format!("没有 /{} 这个命令喵，/help 查看命令列表", name.trim_start_matches('/')));
+    };
+
+    let mut data = vec![format!("/{} {}", spec.name, spec.help)];
+    if !spec.aliases.is_empty() {
+        let aliases: Vec<String> = spec.aliases.iter().map(|a| format!("/{}", a)).collect();
+        data.push(format!("别名: {}", aliases.join(" ")));
+    }
+    data.push("用法:".to_string());
+    if spec.subcommands.is_empty() {
+        data.push(format!("/{}{}", spec.name, format_args(&spec.args)));
+    }
+    for subcommand in &spec.subcommands {
+        if subcommand.permission == Permission::Admin && !is_admin {
+            continue;
+        }
+        let mut line = format!("{} {}", subcommand.usage(&spec.name), subcommand.help);
+        if subcommand.permission == Permission::Admin {
+            line.push_str(" (管理员)");
+        }
+        if subcommand.rate_limit.is_some()
+            && let Some(limit) = lookup_limit(&limits, &format!("{}.{}", spec.name, subcommand.name))
+        {
+            line.push_str(&format!(" ({})", format_limit(&limit)));
+        }
+        data.push(line);
+    }
+    if spec.rate_limited()
+        && let Some(limit) = lookup_limit(&limits, &spec.name)
+    {
+        data.push(format!("次数限制: {}，管理员不受限制", format_limit(&limit)));
+    }
+    Reply::lines(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin() -> CommandRegistry {
+        toml::from_str(BUILTIN_COMMANDS).unwrap()
+    }
+
+    #[test]
+    fn implicit_subcommand_matches_by_name() {
+        let registry = builtin();
+        let report = registry.resolve("report").unwrap();
+        let (subcommand, rest) = report.subcommand("add SO-50 BG1ABC OM89 b").unwrap();
+        assert_eq!(subcommand.name, "add");
+        assert_eq!(rest.trim_start(), "SO-50 BG1ABC OM89 b");
+
+        let roaming = registry.resolve("r").unwrap();
+        let (subcommand, rest) = roaming.subcommand("add BG1ABC OM89").unwrap();
+        assert_eq!(subcommand.name, "add");
+        assert_eq!(rest.trim_start(), "BG1ABC OM89");
+    }
+
+    #[test]
+    fn implicit_subcommand_takes_unnamed_args() {
+        let registry = builtin();
+        let report = registry.resolve("report").unwrap();
+        let (subcommand, rest) = report.subcommand("SO-50 BG1ABC OM89 b").unwrap();
+        assert_eq!(subcommand.name, "add");
+        assert_eq!(rest, "SO-50 BG1ABC OM89 b");
+
+        let (subcommand, _) = report.subcommand("remove SO-50 BG1ABC").unwrap();
+        assert_eq!(subcommand.name, "remove");
+        // a word that only starts like a sub-command is not one
+        let (subcommand, _) = report.subcommand("removed BG1ABC OM89").unwrap();
+        assert_eq!(subcommand.name, "add");
+        assert!(report.subcommand("  ").is_none());
+    }
+
+    #[test]
+    fn check_counts_args_after_a_written_out_implicit_name() {
+        let registry = builtin();
+        let report = registry.resolve("report").unwrap();
+        assert!(report.check("add SO-50 BG1ABC OM89 b", false).is_ok());
+        assert!(report.check("add SO-50 BG1ABC OM89", false).is_err());
+    }
+}
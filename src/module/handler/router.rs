@@ -3,22 +3,40 @@ use crate::{
     module::{
         amsat::{
             official_report::query_satellite_status,
-            user_report::{add_user_report, create_report_template, push_user_report, remove_user_report}
+            report_audit::query_report_history,
+            report_stats::{query_leaderboard, query_report_stats},
+            user_report::{add_user_report, confirm_user_report, create_report_template, override_report_hold, push_user_report, remove_user_report}
+        },
+        handler::{
+            quota::{check_ban, check_quota, quota_command},
+            registry::{help_command, COMMAND_REGISTRY},
+        },
+        solar_image::space_weather::space_weather_command,
+        logbook::{
+            grid_tracker::grids_command,
+            user_log::{add_qso, confirm_qso, export_logbook, import_logbook, list_qsos, logbook_stats, remove_qso, set_station},
+        },
+        pass_query::sat_pass_predict::pass_command,
+        tools::{
+            render::{ROAMING_LIST_PIC_PATH, SPACE_WEATHER_PIC_PATH},
+            roaming::*,
+            roaming_feed::{subscribe_roaming, unsubscribe_roaming},
+            roaming_io::{export_roaming_command, import_roaming_command},
         },
-        tools::roaming::*,
     },
-    msg::prelude::{BinMessageEvent, FromBinMessageEvent, MessageElement, MessageEvent},
-    response::ApiResponse,
+    msg::{prelude::{BinMessageEvent, FromBinMessageEvent, MessageElement, MessageEvent}, segment::Segment},
+    response::{ApiResponse, Reply},
     socket::MsgContent
 };
-use std::{sync::Arc, vec};
+use futures::future::BoxFuture;
+use std::{collections::HashMap, sync::Arc, vec};
 
 pub async fn bot_message_handler(
     msg: MsgContent,
     app_status: Arc<AppStatus>,
-) -> ApiResponse<Vec<String>> {
+) -> Reply {
     #[allow(unused_mut)]
-    let mut response: ApiResponse<Vec<String>> = ApiResponse {
+    let mut response: Reply = ApiResponse {
         success: false,
         data: None,
         message: None,
@@ -44,8 +62,8 @@ async fn router(
     _command: String,
     payload: MessageEvent,
     app_status: Arc<AppStatus>,
-) -> ApiResponse<Vec<String>> {
-    let mut response: ApiResponse<Vec<String>> = ApiResponse {
+) -> Reply {
+    let response: Reply = ApiResponse {
         success: false,
         data: None,
         message: None,
@@ -60,62 +78,193 @@ async fn router(
     let text = message_text.trim().to_string();
     let (command, args) = get_command_and_args(&text);
 
-    match command.as_str() {
-        "q" | "query" => {
-            response = query_satellite_status(&args, &app_status, &payload).await;
-        }
-        "s" | "sun" => {
-            // let uri = match solar_image::get_image::file_uri("runtime_data/pic/solar_image_latest.png") {
-            //     Ok(uri) => uri,
-            //     Err(e) => {
-            //         tracing::error!("Failed to encode solar image path: {}", e);
-            //         return response;
-            //     }
-            // };
-            let uri: String = "file:///server_runtime_data/pic/solar_image_latest.png".to_string();
-            response = ApiResponse {
-                success: true,
-                data: Some(vec![uri]),
-                message: Some("solar image".to_string()),
-            };
-        }
-        "spot" => {
-            let user_id = payload.user_id.clone();
-            let admin_id = app_status.config.read().await.bot_config.admin_id.clone();
-            if !admin_id.contains(&user_id) {
-                return ApiResponse::error("测试阶段只开放给FNA测试喵".to_string());
-            }
-            response = push_user_report(&args).await;
-        }
-        "create" => {
-            response = match create_report_template(&args, app_status).await {
-                Ok(_) => ApiResponse::ok(vec!["报告模板创建成功喵".to_string()]),
-                Err(e) => ApiResponse::error(format!("报告模板创建失败喵: {}", e)),
-            };
-        }
-        "report" => {
-            if args.starts_with("remove") {
-                response = remove_user_report(app_status, &args, &payload).await;
-            } else {
-                response = add_user_report(app_status, &args, &payload).await;
-            }
+    // the core only forwards configured groups, private chats are checked here
+    if payload.is_private() && !app_status.config.read().await.bot_config.chat_allowed(&payload) {
+        return response;
+    }
+
+    // aliases, enabled chats, permissions and required args come from commands.toml
+    let Some(spec) = COMMAND_REGISTRY.resolve(&command) else {
+        return response;
+    };
+    if !spec.available_in(&payload) {
+        return response;
+    }
+    let is_admin = {
+        let config_guard = app_status.config.read().await;
+        config_guard.bot_config.admin_id.contains(&payload.user_id)
+    };
+    if let Err(e) = spec.check(&args, is_admin) {
+        return ApiResponse::error(e);
+    }
+
+    // bans hold for every command, unknown sub-commands included
+    if let Err(e) = check_ban(&app_status, payload.user_id).await {
+        return ApiResponse::error(e);
+    }
+
+    // the handler of the matched sub-command, or of the command itself when nothing matched
+    let (key, args) = match spec.subcommand(&args) {
+        // an implicit sub-command may be written out, `/r add <呼号> <网格>` is `/r <呼号> <网格>`
+        Some((subcommand, rest)) if subcommand.implicit => {
+            (format!("{}.{}", spec.name, subcommand.name), rest.trim_start().to_string())
         }
-        "roaming" | "r" => {
-            tracing::warn!("Received roaming command with args: {}", args);
-            if args.is_empty() {
-                response.message = Some("image".to_string());
-                response.data = Some(vec!["file:///server_runtime_data/pic/roaming_list.png".to_string()]);
-            } else if args.starts_with("list") {
-                response = list_roaming(&app_status, &args).await;
-            } else if args.starts_with("remove") {
-                response = remove_roaming(&app_status, &args, &payload).await;
-            } else {
-                response = add_roaming(&app_status, &args, &payload).await;
-            }
+        Some((subcommand, _)) => (format!("{}.{}", spec.name, subcommand.name), args),
+        None if !spec.subcommands.is_empty() && !args.trim().is_empty() => {
+            return ApiResponse::error(format!("没有这个子命令喵，/help {} 查看用法", spec.name));
         }
-        _ => {}
+        None => (spec.name.clone(), args),
+    };
+    // quotas are counted per resolved key, so `/r <呼号>` and `/r add <呼号>` share one window
+    if spec.rate_limited()
+        && let Err(e) = check_quota(&app_status, payload.user_id, &key).await
+    {
+        return ApiResponse::error(e);
     }
-    response
+    let Some(handler) = HANDLERS.get(key.as_str()) else {
+        tracing::warn!("Command {} is declared in commands.toml but has no handler", key);
+        return response;
+    };
+    handler(app_status, args, payload).await
+}
+
+/// Explicit sub-commands get the full args with their name in front, everything else only what follows
+type Handler = fn(Arc<AppStatus>, String, MessageEvent) -> BoxFuture<'static, Reply>;
+
+lazy_static::lazy_static! {
+    /// Keyed by `command` or `command.subcommand` as declared in commands.toml
+    static ref HANDLERS: HashMap<&'static str, Handler> = {
+        let handlers: Vec<(&'static str, Handler)> = vec![
+            ("help", |app_status, args, payload| Box::pin(async move {
+                help_command(&app_status, &args, &payload).await
+            })),
+            ("query", |app_status, args, payload| Box::pin(async move {
+                query_satellite_status(&args, &app_status, &payload).await
+            })),
+            // a bare /s stays the picture it always was
+            ("s", |_, _, _| Box::pin(async move {
+                Reply::new(true, vec![Segment::image(SPACE_WEATHER_PIC_PATH)], "solar image")
+            })),
+            ("sun", |app_status, args, _| Box::pin(async move {
+                match args.trim() {
+                    "" => space_weather_command(&app_status).await,
+                    arg if arg.eq_ignore_ascii_case("img") => {
+                        Reply::new(true, vec![Segment::image(SPACE_WEATHER_PIC_PATH)], "solar image")
+                    }
+                    _ => ApiResponse::error("没有这个参数喵，用法: /sun [img]"),
+                }
+            })),
+            ("pass", |app_status, args, payload| Box::pin(async move {
+                pass_command(&app_status, &args, &payload).await
+            })),
+            ("spot", |app_status, args, payload| Box::pin(async move {
+                push_user_report(app_status, &args, &payload).await
+            })),
+            ("create", |app_status, args, _| Box::pin(async move {
+                match create_report_template(&args, app_status).await {
+                    Ok(_) => Reply::text("报告模板创建成功喵"),
+                    Err(e) => ApiResponse::error(format!("报告模板创建失败喵: {}", e)),
+                }
+            })),
+            // a bare /report answers with the usage of add
+            ("report", |app_status, args, payload| Box::pin(async move {
+                add_user_report(app_status, &args, &payload).await
+            })),
+            ("report.add", |app_status, args, payload| Box::pin(async move {
+                add_user_report(app_status, &args, &payload).await
+            })),
+            ("report.remove", |app_status, args, payload| Box::pin(async move {
+                remove_user_report(app_status, &args, &payload).await
+            })),
+            ("report.confirm", |app_status, args, payload| Box::pin(async move {
+                confirm_user_report(app_status, &args, &payload).await
+            })),
+            ("report.override", |app_status, args, payload| Box::pin(async move {
+                override_report_hold(app_status, &args, &payload).await
+            })),
+            ("report.history", |app_status, args, payload| Box::pin(async move {
+                query_report_history(app_status, &args, &payload).await
+            })),
+            ("stats", |app_status, args, payload| Box::pin(async move {
+                query_report_stats(app_status, &args, &payload).await
+            })),
+            ("rank", |app_status, args, payload| Box::pin(async move {
+                query_leaderboard(app_status, &args, &payload).await
+            })),
+            ("roaming", |_, _, _| Box::pin(async move {
+                Reply::image(ROAMING_LIST_PIC_PATH)
+            })),
+            ("roaming.add", |app_status, args, payload| Box::pin(async move {
+                add_roaming(&app_status, &args, &payload).await
+            })),
+            ("roaming.map", |app_status, _, _| Box::pin(async move {
+                roaming_map(&app_status).await
+            })),
+            ("roaming.list", |app_status, args, payload| Box::pin(async move {
+                list_roaming(&app_status, &args, &payload).await
+            })),
+            ("roaming.remove", |app_status, args, payload| Box::pin(async move {
+                remove_roaming(&app_status, &args, &payload).await
+            })),
+            ("roaming.sub", |app_status, args, payload| Box::pin(async move {
+                subscribe_roaming(&app_status, &args, &payload).await
+            })),
+            ("roaming.unsub", |app_status, args, payload| Box::pin(async move {
+                unsubscribe_roaming(&app_status, &args, &payload).await
+            })),
+            ("roaming.export", |app_status, args, _| Box::pin(async move {
+                export_roaming_command(&app_status, &args).await
+            })),
+            ("roaming.import", |app_status, args, payload| Box::pin(async move {
+                import_roaming_command(&app_status, &args, &payload).await
+            })),
+            // a bare /log lists recent QSOs
+            ("log", |app_status, args, payload| Box::pin(async move {
+                list_qsos(&app_status, &args, &payload).await
+            })),
+            ("log.add", |app_status, args, payload| Box::pin(async move {
+                add_qso(&app_status, &args, &payload).await
+            })),
+            ("log.list", |app_status, args, payload| Box::pin(async move {
+                list_qsos(&app_status, &args, &payload).await
+            })),
+            ("log.remove", |app_status, args, payload| Box::pin(async move {
+                remove_qso(&app_status, &args, &payload).await
+            })),
+            ("log.confirm", |app_status, args, payload| Box::pin(async move {
+                confirm_qso(&app_status, &args, &payload).await
+            })),
+            ("log.stats", |app_status, _, payload| Box::pin(async move {
+                logbook_stats(&app_status, &payload).await
+            })),
+            ("log.call", |app_status, args, payload| Box::pin(async move {
+                set_station(&app_status, &args, &payload).await
+            })),
+            ("log.export", |app_status, _, payload| Box::pin(async move {
+                export_logbook(&app_status, &payload).await
+            })),
+            ("log.import", |app_status, args, payload| Box::pin(async move {
+                import_logbook(&app_status, &args, &payload).await
+            })),
+            ("grids", |app_status, args, payload| Box::pin(async move {
+                grids_command(app_status, &args, &payload).await
+            })),
+            // block, unblock and info are parsed by the quota command itself
+            ("quota", |app_status, args, payload| Box::pin(async move {
+                quota_command(app_status, &args, &payload).await
+            })),
+            ("quota.block", |app_status, args, payload| Box::pin(async move {
+                quota_command(app_status, &args, &payload).await
+            })),
+            ("quota.unblock", |app_status, args, payload| Box::pin(async move {
+                quota_command(app_status, &args, &payload).await
+            })),
+            ("quota.info", |app_status, args, payload| Box::pin(async move {
+                quota_command(app_status, &args, &payload).await
+            })),
+        ];
+        handlers.into_iter().collect()
+    };
 }
 
 /// - Split by whitespace and normalize
@@ -139,4 +288,4 @@ pub fn get_command_and_args(input: &str) -> (String, String) {
     } else {
         (String::new(), String::new())
     }
-}
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,193 @@
+use crate::module::logbook::prelude::Qso;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+pub const ADIF_VERSION: &str = "3.1.4";
+const PROGRAM_ID: &str = "CiRCLE_sat_bot";
+
+/// One record, field names upper-cased
+pub type AdifRecord = Vec<(String, String)>;
+
+pub fn record_field<'a>(record: &'a AdifRecord, name: &str) -> Option<&'a str> {
+    record.iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| value.trim())
+        .filter(|value| !value.is_empty())
+}
+
+/// ADI format: `<NAME:LEN[:TYPE]>value`, records end with `<EOR>`
+/// - text before the first `<` or up to `<EOH>` is the header and skipped
+/// - LEN counts characters, so UTF-8 comments from other loggers survive
+pub fn parse_adif(content: &str) -> Vec<AdifRecord> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut records = Vec::new();
+    let mut record = AdifRecord::new();
+    let has_header = !content.trim_start().starts_with('<');
+    let mut in_header = has_header;
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i] != '<' {
+            i += 1;
+            continue;
+        }
+        let Some(close) = chars[i..].iter().position(|c| *c == '>').map(|p| i + p) else {
+            break;
+        };
+        let specifier: String = chars[i + 1..close].iter().collect();
+        let mut parts = specifier.split(':');
+        let name = parts.next().unwrap_or_default().trim().to_uppercase();
+        let length = parts.next().and_then(|l| l.trim().parse::<usize>().ok());
+        i = close + 1;
+
+        match name.as_str() {
+            "EOH" => {
+                in_header = false;
+                record.clear();
+            }
+            "EOR" => {
+                if !in_header && !record.is_empty() {
+                    records.push(std::mem::take(&mut record));
+                }
+            }
+            _ => {
+                let length = length.unwrap_or(0);
+                // the length comes from the imported file
+                let end = i.saturating_add(length).min(chars.len());
+                let value: String = chars[i..end].iter().collect();
+                i = end;
+                if !in_header {
+                    record.push((name, value));
+                }
+            }
+        }
+    }
+
+    // a header without <EOH> is not a header
+    if records.is_empty() && in_header && has_header {
+        return parse_adif(&format!("<EOH>{}", content));
+    }
+    records
+}
+
+/// QSO_DATE `YYYYMMDD` and TIME_ON `HHMM[SS]`
+pub fn parse_adif_datetime(date: &str, time: &str) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(date, "%Y%m%d").ok()?;
+    let time = match time.len() {
+        4 => NaiveTime::parse_from_str(time, "%H%M").ok()?,
+        6 => NaiveTime::parse_from_str(time, "%H%M%S").ok()?,
+        _ => return None,
+    };
+    Some(date.and_time(time).and_utc())
+}
+
+fn write_field(adif: &mut String, name: &str, value: &str) {
+    adif.push_str(&format!("<{}:{}>{} ", name, value.chars().count(), value));
+}
+
+pub fn write_adif(
+    qsos: &[Qso],
+    station_callsign: Option<&str>,
+    now: DateTime<Utc>,
+) -> String {
+    let mut adif = format!("Rinko satellite logbook export, {} QSOs\n", qsos.len());
+    write_field(&mut adif, "ADIF_VER", ADIF_VERSION);
+    write_field(&mut adif, "PROGRAMID", PROGRAM_ID);
+    write_field(&mut adif, "PROGRAMVERSION", env!("CARGO_PKG_VERSION"));
+    write_field(&mut adif, "CREATED_TIMESTAMP", &now.format("%Y%m%d %H%M%S").to_string());
+    adif.push_str("<EOH>\n\n");
+
+    for qso in qsos {
+        let Some(time) = qso.datetime() else {
+            continue;
+        };
+        write_field(&mut adif, "CALL", &qso.call);
+        write_field(&mut adif, "QSO_DATE", &time.format("%Y%m%d").to_string());
+        write_field(&mut adif, "TIME_ON", &time.format("%H%M%S").to_string());
+        write_field(&mut adif, "MODE", &qso.mode);
+        let optional = [
+            ("SUBMODE", qso.submode.as_deref()),
+            ("BAND", qso.band.as_deref()),
+            ("BAND_RX", qso.band_rx.as_deref()),
+            ("GRIDSQUARE", qso.gridsquare.as_deref()),
+            ("MY_GRIDSQUARE", qso.my_gridsquare.as_deref()),
+            ("RST_SENT", qso.rst_sent.as_deref()),
+            ("RST_RCVD", qso.rst_rcvd.as_deref()),
+            ("STATION_CALLSIGN", station_callsign),
+            ("COMMENT", qso.comment.as_deref()),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                write_field(&mut adif, name, value);
+            }
+        }
+        if !qso.vucc_grids.is_empty() {
+            write_field(&mut adif, "VUCC_GRIDS", &qso.vucc_grids.join(","));
+        }
+        if qso.confirmed {
+            write_field(&mut adif, "QSL_RCVD", "Y");
+        }
+        write_field(&mut adif, "PROP_MODE", "SAT");
+        write_field(&mut adif, "SAT_NAME", &qso.sat_name);
+        adif.push_str("<EOR>\n");
+    }
+    adif
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(records: &[AdifRecord], index: usize, name: &str) -> Option<String> {
+        records.get(index).and_then(|r| record_field(r, name)).map(str::to_string)
+    }
+
+    #[test]
+    fn parses_records_after_header() {
+        let adif = "exported by some logger\n<ADIF_VER:5>3.1.4 <EOH>\n\
+            <CALL:5>BG1XX <SAT_NAME:5>SO-50 <EOR>\n\
+            <call:6>BA4YYY <sat_name:6>AO-91 <QSO_DATE:8:D>20250801 <eor>";
+        let records = parse_adif(adif);
+        assert_eq!(records.len(), 2);
+        assert_eq!(field(&records, 0, "CALL").as_deref(), Some("BG1XX"));
+        assert_eq!(field(&records, 1, "SAT_NAME").as_deref(), Some("AO-91"));
+        assert_eq!(field(&records, 1, "QSO_DATE").as_deref(), Some("20250801"));
+        assert!(records[0].iter().all(|(name, _)| name != "ADIF_VER"));
+    }
+
+    #[test]
+    fn parses_without_header() {
+        let records = parse_adif("<CALL:5>BG1XX<EOR>");
+        assert_eq!(field(&records, 0, "CALL").as_deref(), Some("BG1XX"));
+    }
+
+    #[test]
+    fn length_counts_characters() {
+        let records = parse_adif("<EOH><COMMENT:4>你好73<CALL:5>BG1XX<EOR>");
+        assert_eq!(field(&records, 0, "COMMENT").as_deref(), Some("你好73"));
+        assert_eq!(field(&records, 0, "CALL").as_deref(), Some("BG1XX"));
+    }
+
+    #[test]
+    fn oversized_length_does_not_panic() {
+        let records = parse_adif("<EOH><CALL:18446744073709551615>X");
+        assert!(records.is_empty());
+        let records = parse_adif("<EOH><CALL:99>BG1XX<EOR>");
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn unterminated_tag_is_ignored() {
+        assert!(parse_adif("<EOH><CALL:5").is_empty());
+    }
+
+    #[test]
+    fn parses_datetime() {
+        let time = parse_adif_datetime("20250801", "1234").unwrap();
+        assert_eq!(time.to_rfc3339(), "2025-08-01T12:34:00+00:00");
+        let time = parse_adif_datetime("20250801", "123456").unwrap();
+        assert_eq!(time.to_rfc3339(), "2025-08-01T12:34:56+00:00");
+        assert!(parse_adif_datetime("20250801", "12345").is_none());
+        assert!(parse_adif_datetime("20251301", "1234").is_none());
+        assert!(parse_adif_datetime("20250801", "2460").is_none());
+    }
+}
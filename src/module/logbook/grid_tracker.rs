@@ -0,0 +1,244 @@
+use crate::{
+    app_status::AppStatus,
+    module::{
+        logbook::{prelude::*, user_log::{read_logbook, station_callsign}},
+        prelude::{grid_square, maidenhead_to_lat_lon},
+        tools::{render, roaming::{load_roaming_list, roaming_distance_km, RoamingState}},
+    },
+    msg::{prelude::MessageEvent, segment::Segment},
+    response::{ApiResponse, Reply},
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use chrono::Utc;
+
+/// Confirmed squares needed for the VUCC satellite award
+const VUCC_THRESHOLD: usize = 100;
+const ROAMING_LIST_MAX: usize = 10;
+
+#[derive(Default, Clone, Copy)]
+struct SquareStatus {
+    qsos: usize,
+    confirmed: bool,
+}
+
+/// Square -> status, a square is confirmed if any of its QSOs is
+fn worked_squares(logbook: &Logbook) -> BTreeMap<String, SquareStatus> {
+    let mut squares: BTreeMap<String, SquareStatus> = BTreeMap::new();
+    for qso in &logbook.qsos {
+        for square in qso.squares() {
+            let status = squares.entry(square).or_default();
+            status.qsos += 1;
+            status.confirmed |= qso.confirmed;
+        }
+    }
+    squares
+}
+
+/// Field -> (worked, confirmed)
+fn field_counts(squares: &BTreeMap<String, SquareStatus>) -> BTreeMap<String, (usize, usize)> {
+    let mut fields: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for (square, status) in squares {
+        let count = fields.entry(square.chars().take(2).collect()).or_default();
+        count.0 += 1;
+        if status.confirmed {
+            count.1 += 1;
+        }
+    }
+    fields
+}
+
+/// Home grid of the logbook, else the one of the latest QSO
+fn home_grid(logbook: &Logbook) -> Option<String> {
+    logbook.my_gridsquare.clone().or_else(|| {
+        logbook.qsos.iter().rev().find_map(|q| q.my_gridsquare.clone())
+    })
+}
+
+fn is_valid_field(field: &str) -> bool {
+    field.len() == 2 && field.chars().all(|c| ('A'..='R').contains(&c))
+}
+
+/// `/grids` sub-commands, a bare `/grids` shows the summary
+pub async fn grids_command(
+    app_status: Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let logbook = match read_logbook(&app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+    if logbook.qsos.is_empty() {
+        return ApiResponse::error("日志还是空的喵，可以使用 /log add <呼号> <卫星> [网格] 记录".to_string());
+    }
+    let squares = worked_squares(&logbook);
+
+    let mut args = args.split_whitespace();
+    match args.next().unwrap_or("summary") {
+        "summary" => grids_summary(&app_status, &logbook, &squares).await,
+        "map" => grids_map(&app_status, &logbook, &squares, payload).await,
+        "need" => grids_needed(&logbook, &squares, args.next()),
+        "roaming" => grids_roaming(&app_status, &logbook, &squares).await,
+        _ => ApiResponse::error("可用的子命令: map | need [大网格] | roaming".to_string()),
+    }
+}
+
+async fn grids_summary(
+    app_status: &Arc<AppStatus>,
+    logbook: &Logbook,
+    squares: &BTreeMap<String, SquareStatus>,
+) -> Reply {
+    let callsign = station_callsign(app_status, logbook).await.unwrap_or_else(|| logbook.user_id.to_string());
+    let confirmed = squares.values().filter(|s| s.confirmed).count();
+
+    let mut fields: Vec<(String, (usize, usize))> = field_counts(squares).into_iter().collect();
+    fields.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.0.cmp(&b.0)));
+
+    let mut lines = vec![
+        format!("{} 的卫星网格:", callsign),
+        format!(
+            "已通联 {} 个，已确认 {} 个 (VUCC 需要 {} 个确认网格)",
+            squares.len(),
+            confirmed,
+            VUCC_THRESHOLD
+        ),
+    ];
+    if squares.is_empty() {
+        lines.push("日志里还没有带网格的 QSO 喵".to_string());
+    }
+    for (field, (worked, confirmed)) in fields {
+        lines.push(format!("{} {} (确认 {})", field, worked, confirmed));
+    }
+    Reply::text(lines.join("\n"))
+}
+
+async fn grids_map(
+    app_status: &Arc<AppStatus>,
+    logbook: &Logbook,
+    squares: &BTreeMap<String, SquareStatus>,
+    payload: &MessageEvent,
+) -> Reply {
+    if squares.is_empty() {
+        return ApiResponse::error("日志里还没有带网格的 QSO 喵".to_string());
+    }
+    let callsign = station_callsign(app_status, logbook).await.unwrap_or_else(|| logbook.user_id.to_string());
+    let squares: Vec<(String, bool)> = squares
+        .iter()
+        .map(|(square, status)| (square.clone(), status.confirmed))
+        .collect();
+
+    match render::render_worked_grids_map(&callsign, &squares, payload).await {
+        Ok(path) => {
+            let caption = format!("{} 已通联 {} 个网格", callsign, squares.len());
+            // mentions only make sense in groups
+            let mut segments = if payload.is_private() {
+                vec![Segment::text(caption)]
+            } else {
+                vec![Segment::At { qq: payload.user_id }, Segment::text(format!(" {}", caption))]
+            };
+            segments.push(Segment::image(path));
+            Reply::ok(segments)
+        }
+        Err(e) => ApiResponse::error(format!("渲染网格地图失败: {}", e)),
+    }
+}
+
+/// With a field, its unworked squares; without, the needed count of every field touched
+fn grids_needed(
+    logbook: &Logbook,
+    squares: &BTreeMap<String, SquareStatus>,
+    field: Option<&str>,
+) -> Reply {
+    if let Some(field) = field {
+        let field = field.to_uppercase();
+        if !is_valid_field(&field) {
+            return ApiResponse::error(format!("{}不是有效的大网格喵，例如 OM", field));
+        }
+        let needed: Vec<String> = (0..100)
+            .map(|n| format!("{}{:02}", field, n))
+            .filter(|square| !squares.contains_key(square))
+            .collect();
+        if needed.is_empty() {
+            return Reply::text(format!("{} 的 100 个网格都通联过了喵", field));
+        }
+
+        let mut lines = vec![format!("{} 还需要 {} 个网格:", field, needed.len())];
+        for row in needed.chunks(10) {
+            lines.push(row.join(" "));
+        }
+        return Reply::text(lines.join("\n"));
+    }
+
+    let mut fields = field_counts(squares);
+    if let Some(square) = home_grid(logbook).and_then(|g| grid_square(&g)) {
+        fields.entry(square.chars().take(2).collect()).or_default();
+    }
+    let mut lines = vec!["各大网格还需要的网格数:".to_string()];
+    for (field, (worked, _)) in fields {
+        lines.push(format!("{} 需要 {} (已通联 {})", field, 100 - worked, worked));
+    }
+    lines.push("使用 /grids need <大网格> 查看具体网格".to_string());
+    Reply::text(lines.join("\n"))
+}
+
+/// Active and upcoming roamers that would give new squares, nearest first
+async fn grids_roaming(
+    app_status: &Arc<AppStatus>,
+    logbook: &Logbook,
+    squares: &BTreeMap<String, SquareStatus>,
+) -> Reply {
+    let roaming_data = match load_roaming_list(app_status).await {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to read roaming data: {}", e);
+            return ApiResponse::error(format!("文件读取失败: {}", e));
+        }
+    };
+
+    let now = Utc::now();
+    let home = home_grid(logbook).and_then(|g| maidenhead_to_lat_lon(&g));
+    let mut matched: Vec<(Option<f64>, String)> = roaming_data
+        .iter()
+        .filter(|r| r.state(now) != RoamingState::Expired)
+        .filter_map(|r| {
+            let mut needed: Vec<String> = r.roaming_data.grid
+                .split_whitespace()
+                .filter_map(grid_square)
+                .filter(|square| !squares.contains_key(square))
+                .collect();
+            needed.sort();
+            needed.dedup();
+            if needed.is_empty() {
+                return None;
+            }
+
+            let mut line = format!("{}: {}", r.roaming_data.callsign, needed.join(" "));
+            if r.state(now) == RoamingState::Upcoming {
+                line.push_str(" [即将开始]");
+            }
+            if let Some(window) = r.window_text() {
+                line.push_str(&format!(" ({})", window));
+            }
+            let distance = home.and_then(|home| roaming_distance_km(r, home));
+            if let Some(distance) = distance {
+                line.push_str(&format!(" {:.0}km", distance));
+            }
+            Some((distance, line))
+        })
+        .collect();
+
+    if matched.is_empty() {
+        return Reply::text("当前没有在你未通联网格里的漫游喵");
+    }
+    matched.sort_by(|a, b| match (a.0, b.0) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+
+    let mut lines = vec![format!("有 {} 个漫游在你未通联的网格:", matched.len())];
+    lines.extend(matched.into_iter().take(ROAMING_LIST_MAX).map(|(_, line)| line));
+    Reply::text(lines.join("\n"))
+}
@@ -0,0 +1,4 @@
+pub mod adif;
+pub mod grid_tracker;
+pub mod prelude;
+pub mod user_log;
\ No newline at end of file
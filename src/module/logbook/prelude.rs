@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, FixedOffset, Utc};
+use crate::module::prelude::grid_square;
+
+/// One file per QQ account, `<user_id>.json`
+pub const LOGBOOK_DIR: &str = "runtime_data/logbook";
+pub const LOGBOOK_EXPORT_PATH_PREFIX: &str = "runtime_data/export/logbook_";
+
+/// ADIF 3 band enumeration, the ones reachable through amateur satellites
+pub const SAT_BANDS: &[&str] = &["15m", "10m", "6m", "2m", "1.25m", "70cm", "33cm", "23cm", "13cm", "9cm", "6cm", "3cm", "1.25cm"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Qso {
+    /// per logbook, used by `/log remove`
+    pub id: u64,
+    pub call: String,
+    /// `official_name` from the satellite list when it could be resolved
+    pub sat_name: String,
+    /// RFC3339, UTC
+    pub time: String,
+    /// ADIF MODE
+    pub mode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submode: Option<String>,
+    /// uplink band
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub band: Option<String>,
+    /// downlink band
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub band_rx: Option<String>,
+    /// grid of the other station
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gridsquare: Option<String>,
+    /// ADIF VUCC_GRIDS, for stations on a grid line or corner
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vucc_grids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub my_gridsquare: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rst_sent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rst_rcvd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// QSL received, by card or LoTW
+    #[serde(default)]
+    pub confirmed: bool,
+}
+
+impl Qso {
+    pub fn datetime(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.time).ok().map(|t| t.with_timezone(&Utc))
+    }
+
+    /// Same station, satellite and minute
+    pub fn is_duplicate_of(&self, other: &Qso) -> bool {
+        self.call == other.call
+            && self.sat_name == other.sat_name
+            && self.datetime().map(|t| t.format("%Y%m%d%H%M").to_string())
+                == other.datetime().map(|t| t.format("%Y%m%d%H%M").to_string())
+    }
+
+    /// 4-char squares this QSO counts for
+    pub fn squares(&self) -> Vec<String> {
+        let mut squares: Vec<String> = self.gridsquare
+            .iter()
+            .chain(self.vucc_grids.iter())
+            .filter_map(|g| grid_square(g))
+            .collect();
+        squares.sort();
+        squares.dedup();
+        squares
+    }
+
+    /// `submode` if there is one, e.g. `USB` instead of `SSB`
+    pub fn mode_text(&self) -> &str {
+        self.submode.as_deref().unwrap_or(&self.mode)
+    }
+
+    /// e.g. `#3 08-01 12:30 BJT BG2XXX SO-50 FM OM89`
+    pub fn summary(&self) -> String {
+        let bjt = FixedOffset::east_opt(8 * 3600).unwrap();
+        let time = self.datetime()
+            .map(|t| t.with_timezone(&bjt).format("%Y-%m-%d %H:%M BJT").to_string())
+            .unwrap_or_else(|| self.time.clone());
+        let mut text = format!("#{} {} {} {} {}", self.id, time, self.call, self.sat_name, self.mode_text());
+        if let Some(grid) = &self.gridsquare {
+            text.push_str(&format!(" {}", grid));
+        }
+        if self.confirmed {
+            text.push_str(" ✓");
+        }
+        text
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Logbook {
+    pub user_id: u64,
+    /// set with `/log call`, otherwise taken from the report audit log
+    #[serde(default)]
+    pub station_callsign: Option<String>,
+    /// default MY_GRIDSQUARE for new QSOs
+    #[serde(default)]
+    pub my_gridsquare: Option<String>,
+    #[serde(default)]
+    pub qsos: Vec<Qso>,
+}
+
+impl Logbook {
+    pub fn new(user_id: u64) -> Self {
+        Logbook {
+            user_id,
+            station_callsign: None,
+            my_gridsquare: None,
+            qsos: Vec::new(),
+        }
+    }
+
+    pub fn next_id(&self) -> u64 {
+        self.qsos.iter().map(|q| q.id).max().unwrap_or(0) + 1
+    }
+
+    /// Returns false if the QSO is already logged
+    pub fn insert(&mut self, mut qso: Qso) -> bool {
+        if self.qsos.iter().any(|q| q.is_duplicate_of(&qso)) {
+            return false;
+        }
+        qso.id = self.next_id();
+        self.qsos.push(qso);
+        self.qsos.sort_by(|a, b| a.time.cmp(&b.time));
+        true
+    }
+}
+
+/// Chat input to ADIF (MODE, SUBMODE)
+pub fn parse_mode(input: &str) -> Option<(String, Option<String>)> {
+    let mode = input.to_uppercase();
+    let (mode, submode) = match mode.as_str() {
+        "USB" | "LSB" => ("SSB", Some(mode.as_str())),
+        "FT4" => ("MFSK", Some("FT4")),
+        "PSK31" => ("PSK", Some("PSK31")),
+        "FM" | "SSB" | "CW" | "FT8" | "AM" | "RTTY" | "PKT" | "SSTV" => (mode.as_str(), None),
+        _ => return None,
+    };
+    Some((mode.to_string(), submode.map(|s| s.to_string())))
+}
+
+/// `2m` or `2m/70cm` as uplink/downlink
+pub fn parse_bands(input: &str) -> Option<(String, Option<String>)> {
+    let band = |b: &str| {
+        let b = b.to_lowercase();
+        SAT_BANDS.contains(&b.as_str()).then_some(b)
+    };
+    match input.split_once('/') {
+        Some((up, down)) => Some((band(up)?, Some(band(down)?))),
+        None => Some((band(input)?, None)),
+    }
+}
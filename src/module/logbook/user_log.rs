@@ -0,0 +1,494 @@
+use crate::{
+    app_status::AppStatus,
+    fs::handler::{check_file_exists, load_file, write_file, FileData, FileFormat},
+    module::{
+        amsat::{
+            official_report::load_satellites_list,
+            prelude::{_parse_user_datetime, search_satellites, SatelliteList},
+            report_audit::read_audit_log,
+            report_stats::callsign_of_user,
+        },
+        logbook::{adif::*, prelude::*},
+        prelude::{callsign_auth, is_valid_callsign, is_valid_grid_any_case},
+    },
+    msg::prelude::MessageEvent,
+    response::{ApiResponse, Reply},
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use chrono::{FixedOffset, Utc};
+
+const LIST_DEFAULT: usize = 10;
+const LIST_MAX: usize = 50;
+
+lazy_static::lazy_static! {
+    /// One lock per user, held from reading a logbook to writing it back
+    static ref LOGBOOK_LOCKS: std::sync::Mutex<HashMap<u64, Arc<tokio::sync::Mutex<()>>>> = std::sync::Mutex::new(HashMap::new());
+}
+
+/// Serialises the commands that modify a user's logbook, e.g. two `/log add` or an add during an import
+async fn lock_logbook(user_id: u64) -> tokio::sync::OwnedMutexGuard<()> {
+    let lock = LOGBOOK_LOCKS.lock().unwrap().entry(user_id).or_default().clone();
+    lock.lock_owned().await
+}
+
+fn logbook_path(user_id: u64) -> String {
+    format!("{}/{}.json", LOGBOOK_DIR, user_id)
+}
+
+pub async fn read_logbook(
+    app_status: &AppStatus,
+    user_id: u64,
+) -> anyhow::Result<Logbook> {
+    let tx_filerequest = app_status.file_tx.clone();
+    let path = logbook_path(user_id);
+    if !check_file_exists(tx_filerequest.clone(), path.clone()).await {
+        return Ok(Logbook::new(user_id));
+    }
+
+    match load_file(tx_filerequest, path, FileFormat::Json).await? {
+        FileData::Json(data) => serde_json::from_value(data)
+            .map_err(|e| anyhow::anyhow!("Failed to parse logbook: {}", e)),
+        _ => Err(anyhow::anyhow!("Unexpected file format received")),
+    }
+}
+
+async fn write_logbook(
+    app_status: &AppStatus,
+    logbook: &Logbook,
+) -> anyhow::Result<()> {
+    write_file(
+        app_status.file_tx.clone(),
+        logbook_path(logbook.user_id),
+        &FileData::Json(serde_json::to_value(logbook)?),
+    ).await
+}
+
+/// Configured station callsign, else the one last used for reports
+pub async fn station_callsign(
+    app_status: &AppStatus,
+    logbook: &Logbook,
+) -> Option<String> {
+    if logbook.station_callsign.is_some() {
+        return logbook.station_callsign.clone();
+    }
+    let audit_log = read_audit_log(app_status).await.ok()?;
+    callsign_of_user(&audit_log, logbook.user_id)
+}
+
+/// Exactly one `official_name`, same rule as `/report`
+fn resolve_satellite(
+    input: &str,
+    satellite_list: &SatelliteList,
+) -> Result<String, String> {
+    let matched = search_satellites(input, satellite_list, 0.95);
+    match matched.as_slice() {
+        [name] => Ok(name.clone()),
+        [] => Err(format!("没有找到卫星 {} 喵", input)),
+        _ => Err(format!("无法选中卫星喵，可能的卫星有: {:?}", matched)),
+    }
+}
+
+/// Args: add <呼号> <卫星> [对方网格] [我的网格] [模式] [波段如 2m/70cm] [日期] [时间] [z|bjt]
+/// - time defaults to now, date to today, zone to BJT
+pub async fn add_qso(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let args: Vec<&str> = args.split_whitespace().skip(1).collect();
+    if args.len() < 2 {
+        return ApiResponse::error("参数不足喵，格式是 /log add <呼号> <卫星> [网格] [模式] [波段] [日期 时间]".to_string());
+    }
+
+    let call = args[0].to_uppercase();
+    if !is_valid_callsign(&call) {
+        return ApiResponse::error(format!("{}不是有效的呼号喵", call));
+    }
+
+    let mut grids = Vec::new();
+    let mut mode = None;
+    let mut bands = None;
+    let mut date = None;
+    let mut time = None;
+    let mut zone = "bjt";
+    for token in &args[2..] {
+        if is_valid_grid_any_case(token) {
+            grids.push(token.to_uppercase());
+        } else if let Some(parsed) = parse_mode(token) {
+            mode = Some(parsed);
+        } else if let Some(parsed) = parse_bands(token) {
+            bands = Some(parsed);
+        } else if token.eq_ignore_ascii_case("z") || token.eq_ignore_ascii_case("utc") {
+            zone = "z";
+        } else if token.eq_ignore_ascii_case("bjt") {
+            zone = "bjt";
+        } else if token.contains('-') {
+            date = Some(*token);
+        } else if token.contains(':') {
+            time = Some(*token);
+        } else {
+            return ApiResponse::error(format!("无法识别参数 {} 喵", token));
+        }
+    }
+    if grids.len() > 2 {
+        return ApiResponse::error("最多两个网格喵，依次是对方网格和自己的网格".to_string());
+    }
+
+    let now = Utc::now();
+    let qso_time = if date.is_none() && time.is_none() {
+        now
+    } else {
+        let offset = if zone == "z" { 0 } else { 8 * 3600 };
+        let today = now.with_timezone(&FixedOffset::east_opt(offset).unwrap()).format("%Y-%m-%d").to_string();
+        let input = format!("{} {} {}", date.unwrap_or(&today), time.unwrap_or("00:00"), zone);
+        match _parse_user_datetime(&input) {
+            Ok(t) => t,
+            Err(e) => return ApiResponse::error(format!("{}", e)),
+        }
+    };
+    if qso_time > now + chrono::Duration::minutes(5) {
+        return ApiResponse::error("QSO时间在未来喵".to_string());
+    }
+
+    let satellite_list = match load_satellites_list(app_status.file_tx.clone()).await {
+        Ok(list) => list,
+        Err(e) => return ApiResponse::error(format!("可用卫星列表加载失败: {}", e)),
+    };
+    let sat_name = match resolve_satellite(args[1], &satellite_list) {
+        Ok(name) => name,
+        Err(e) => return ApiResponse::error(e),
+    };
+
+    let _logbook_guard = lock_logbook(payload.user_id).await;
+    let mut logbook = match read_logbook(app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+
+    let (mode, submode) = mode.unwrap_or(("FM".to_string(), None));
+    let (band, band_rx) = match bands {
+        Some((band, band_rx)) => (Some(band), band_rx),
+        None => (None, None),
+    };
+    let mut grids = grids.into_iter();
+    let qso = Qso {
+        id: 0,
+        call,
+        sat_name,
+        time: qso_time.to_rfc3339(),
+        mode,
+        submode,
+        band,
+        band_rx,
+        gridsquare: grids.next(),
+        vucc_grids: Vec::new(),
+        my_gridsquare: grids.next().or_else(|| logbook.my_gridsquare.clone()),
+        rst_sent: None,
+        rst_rcvd: None,
+        comment: None,
+        confirmed: false,
+    };
+    if !logbook.insert(qso.clone()) {
+        return ApiResponse::error("这个QSO已经记录过了喵".to_string());
+    }
+
+    if let Err(e) = write_logbook(app_status, &logbook).await {
+        return ApiResponse::error(format!("文件写入失败: {}", e));
+    }
+    let summary = logbook.qsos.iter()
+        .find(|q| q.is_duplicate_of(&qso))
+        .map(|q| q.summary())
+        .unwrap_or_default();
+    Reply::text(format!("已记录: {}", summary))
+}
+
+/// Args: list [数量]
+pub async fn list_qsos(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let count = args.split_whitespace()
+        .nth(1)
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(LIST_DEFAULT)
+        .clamp(1, LIST_MAX);
+
+    let logbook = match read_logbook(app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+    if logbook.qsos.is_empty() {
+        return ApiResponse::error("日志还是空的喵，可以使用 /log add <呼号> <卫星> 记录".to_string());
+    }
+
+    let mut data = vec![format!("最近 {} 条QSO (共 {} 条):", count.min(logbook.qsos.len()), logbook.qsos.len())];
+    data.extend(logbook.qsos.iter().rev().take(count).map(|q| q.summary()));
+    Reply::text(data.join("\n"))
+}
+
+/// Args: remove <编号>
+pub async fn remove_qso(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let Some(id) = args.split_whitespace().nth(1).and_then(|id| id.trim_start_matches('#').parse::<u64>().ok()) else {
+        return ApiResponse::error("请提供QSO编号喵，可以用 /log list 查看".to_string());
+    };
+
+    let _logbook_guard = lock_logbook(payload.user_id).await;
+    let mut logbook = match read_logbook(app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+    let Some(index) = logbook.qsos.iter().position(|q| q.id == id) else {
+        return ApiResponse::error(format!("没有编号为 {} 的QSO喵", id));
+    };
+    let removed = logbook.qsos.remove(index);
+
+    match write_logbook(app_status, &logbook).await {
+        Ok(_) => Reply::text(format!("已删除: {}", removed.summary())),
+        Err(e) => ApiResponse::error(format!("文件写入失败: {}", e)),
+    }
+}
+
+/// Args: confirm <编号>, marks the QSL as received, again to undo
+pub async fn confirm_qso(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let Some(id) = args.split_whitespace().nth(1).and_then(|id| id.trim_start_matches('#').parse::<u64>().ok()) else {
+        return ApiResponse::error("请提供QSO编号喵，可以用 /log list 查看".to_string());
+    };
+
+    let _logbook_guard = lock_logbook(payload.user_id).await;
+    let mut logbook = match read_logbook(app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+    let Some(qso) = logbook.qsos.iter_mut().find(|q| q.id == id) else {
+        return ApiResponse::error(format!("没有编号为 {} 的QSO喵", id));
+    };
+    qso.confirmed = !qso.confirmed;
+    let text = if qso.confirmed {
+        format!("已确认: {}", qso.summary())
+    } else {
+        format!("已取消确认: {}", qso.summary())
+    };
+
+    match write_logbook(app_status, &logbook).await {
+        Ok(_) => Reply::text(text),
+        Err(e) => ApiResponse::error(format!("文件写入失败: {}", e)),
+    }
+}
+
+/// Args: call <呼号> [网格]
+pub async fn set_station(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let args: Vec<&str> = args.split_whitespace().skip(1).collect();
+    let Some(callsign) = args.first().map(|c| c.to_uppercase()) else {
+        return ApiResponse::error("请提供你的呼号喵，格式是 /log call <呼号> [网格]".to_string());
+    };
+    if !is_valid_callsign(&callsign) {
+        return ApiResponse::error(format!("{}不是有效的呼号喵", callsign));
+    }
+
+    let admin_id = {
+        let config_guard = app_status.config.read().await;
+        config_guard.bot_config.admin_id.clone()
+    };
+    if !callsign_auth(&callsign, payload, &admin_id) {
+        return ApiResponse::error("Rinko无法验证你的身份喵".to_string());
+    }
+
+    let my_grid = match args.get(1) {
+        Some(grid) if is_valid_grid_any_case(grid) => Some(grid.to_uppercase()),
+        Some(grid) => return ApiResponse::error(format!("{}不是有效的梅登黑格网格喵", grid)),
+        None => None,
+    };
+
+    let _logbook_guard = lock_logbook(payload.user_id).await;
+    let mut logbook = match read_logbook(app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+    logbook.station_callsign = Some(callsign.clone());
+    if my_grid.is_some() {
+        logbook.my_gridsquare = my_grid;
+    }
+
+    match write_logbook(app_status, &logbook).await {
+        Ok(_) => Reply::lines(vec![format!(
+            "日志呼号已设置为 {}{}",
+            callsign,
+            logbook.my_gridsquare.as_ref().map(|g| format!("，默认网格 {}", g)).unwrap_or_default()
+        )]),
+        Err(e) => ApiResponse::error(format!("文件写入失败: {}", e)),
+    }
+}
+
+fn top_counts(counts: HashMap<String, usize>, limit: usize) -> String {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.iter()
+        .take(limit)
+        .map(|(name, count)| format!("{} {}", name, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub async fn logbook_stats(
+    app_status: &Arc<AppStatus>,
+    payload: &MessageEvent,
+) -> Reply {
+    let logbook = match read_logbook(app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+    if logbook.qsos.is_empty() {
+        return ApiResponse::error("日志还是空的喵，可以使用 /log add <呼号> <卫星> 记录".to_string());
+    }
+
+    let mut satellites: HashMap<String, usize> = HashMap::new();
+    let mut modes: HashMap<String, usize> = HashMap::new();
+    let mut calls = HashSet::new();
+    let mut grids = HashSet::new();
+    let this_month = Utc::now().format("%Y-%m").to_string();
+    let mut month_count = 0;
+    for qso in &logbook.qsos {
+        *satellites.entry(qso.sat_name.clone()).or_default() += 1;
+        *modes.entry(qso.mode_text().to_string()).or_default() += 1;
+        calls.insert(qso.call.clone());
+        if let Some(grid) = &qso.gridsquare {
+            grids.insert(grid.chars().take(4).collect::<String>());
+        }
+        if qso.datetime().is_some_and(|t| t.format("%Y-%m").to_string() == this_month) {
+            month_count += 1;
+        }
+    }
+
+    let callsign = station_callsign(app_status, &logbook).await.unwrap_or_else(|| payload.user_id.to_string());
+    let data = [
+        format!("{} 的卫星日志:", callsign),
+        format!("QSO: {}，本月: {}", logbook.qsos.len(), month_count),
+        format!("呼号: {}，网格: {}", calls.len(), grids.len()),
+        format!("卫星: {}", top_counts(satellites, 5)),
+        format!("模式: {}", top_counts(modes, 5)),
+        format!("最近: {}", logbook.qsos.last().map(|q| q.summary()).unwrap_or_default()),
+    ];
+    Reply::text(data.join("\n"))
+}
+
+pub async fn export_logbook(
+    app_status: &Arc<AppStatus>,
+    payload: &MessageEvent,
+) -> Reply {
+    let logbook = match read_logbook(app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+    if logbook.qsos.is_empty() {
+        return ApiResponse::error("日志还是空的喵".to_string());
+    }
+
+    let callsign = station_callsign(app_status, &logbook).await;
+    let adif = write_adif(&logbook.qsos, callsign.as_deref(), Utc::now());
+    let path = format!("{}{}.adi", LOGBOOK_EXPORT_PATH_PREFIX, payload.user_id);
+    if let Err(e) = write_file(app_status.file_tx.clone(), path.clone(), &FileData::Text(adif)).await {
+        return ApiResponse::error(format!("文件写入失败: {}", e));
+    }
+    Reply::file(path, format!("{}.adi", callsign.as_deref().unwrap_or("logbook")))
+}
+
+/// `/log import` followed by ADIF text
+/// - only satellite QSOs (SAT_NAME present) are imported
+/// - satellite names that cannot be resolved are kept as written
+pub async fn import_logbook(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let content = args.trim_start().strip_prefix("import").unwrap_or_default();
+    let records = parse_adif(content);
+    if records.is_empty() {
+        return ApiResponse::error("没有找到ADIF记录喵，请在 /log import 后换行粘贴ADIF内容".to_string());
+    }
+
+    let satellite_list = match load_satellites_list(app_status.file_tx.clone()).await {
+        Ok(list) => list,
+        Err(e) => return ApiResponse::error(format!("可用卫星列表加载失败: {}", e)),
+    };
+    let _logbook_guard = lock_logbook(payload.user_id).await;
+    let mut logbook = match read_logbook(app_status, payload.user_id).await {
+        Ok(logbook) => logbook,
+        Err(e) => return ApiResponse::error(format!("读取日志失败: {}", e)),
+    };
+
+    let (mut added, mut duplicates, mut skipped) = (0, 0, 0);
+    let mut unresolved = HashSet::new();
+    for record in &records {
+        let field = |name: &str| record_field(record, name).map(|v| v.to_string());
+        let (Some(call), Some(sat_name), Some(time)) = (
+            field("CALL"),
+            field("SAT_NAME"),
+            record_field(record, "QSO_DATE")
+                .zip(record_field(record, "TIME_ON"))
+                .and_then(|(date, time)| parse_adif_datetime(date, time)),
+        ) else {
+            skipped += 1;
+            continue;
+        };
+
+        let sat_name = resolve_satellite(&sat_name, &satellite_list).unwrap_or_else(|_| {
+            unresolved.insert(sat_name.clone());
+            sat_name
+        });
+        let qso = Qso {
+            id: 0,
+            call: call.to_uppercase(),
+            sat_name,
+            time: time.to_rfc3339(),
+            mode: field("MODE").map(|m| m.to_uppercase()).unwrap_or_else(|| "FM".to_string()),
+            submode: field("SUBMODE").map(|m| m.to_uppercase()),
+            band: field("BAND").map(|b| b.to_lowercase()),
+            band_rx: field("BAND_RX").map(|b| b.to_lowercase()),
+            gridsquare: field("GRIDSQUARE").filter(|g| is_valid_grid_any_case(g)).map(|g| g.to_uppercase()),
+            vucc_grids: field("VUCC_GRIDS")
+                .map(|g| g.split(',').map(|g| g.trim().to_uppercase()).filter(|g| is_valid_grid_any_case(g)).collect())
+                .unwrap_or_default(),
+            my_gridsquare: field("MY_GRIDSQUARE").filter(|g| is_valid_grid_any_case(g)).map(|g| g.to_uppercase()),
+            rst_sent: field("RST_SENT"),
+            rst_rcvd: field("RST_RCVD"),
+            comment: field("COMMENT"),
+            // Y: yes, V: verified
+            confirmed: ["QSL_RCVD", "LOTW_QSL_RCVD"]
+                .iter()
+                .any(|name| record_field(record, name).is_some_and(|v| v.eq_ignore_ascii_case("Y") || v.eq_ignore_ascii_case("V"))),
+        };
+        if logbook.insert(qso) {
+            added += 1;
+        } else {
+            duplicates += 1;
+        }
+    }
+
+    if added > 0
+        && let Err(e) = write_logbook(app_status, &logbook).await
+    {
+        return ApiResponse::error(format!("文件写入失败: {}", e));
+    }
+
+    let mut data = vec![format!("导入完成: 新增{}条，重复{}条，跳过{}条非卫星或缺少字段的记录", added, duplicates, skipped)];
+    if !unresolved.is_empty() {
+        let mut unresolved: Vec<String> = unresolved.into_iter().collect();
+        unresolved.sort();
+        data.push(format!("这些卫星名没有匹配到卫星列表，按原样保存: {}", unresolved.join(", ")));
+    }
+    Reply::text(data.join("\n"))
+}
@@ -1,7 +1,9 @@
 pub mod amsat;
+pub mod callsign;
 pub mod handler;
+pub mod logbook;
 pub mod prelude;
 pub mod scheduled;
 pub mod solar_image;
 pub mod tools;
-// pub mod pass_query;
\ No newline at end of file
+pub mod pass_query;
\ No newline at end of file
@@ -1,7 +1,7 @@
 pub mod all_pass_notify;
-pub mod sat_cache_clean;
-pub mod sat_hotload;
-pub mod sat_list;
+// pub mod sat_cache_clean;
+// pub mod sat_hotload;
+// pub mod sat_list;
 pub mod sat_pass_notify;
 pub mod sat_pass_predict;
-pub mod satellites;
\ No newline at end of file
+pub mod satellites;
@@ -1,32 +1,20 @@
-use chrono::{Utc, TimeZone, Duration, Timelike};
-use std::collections::HashMap;
-use tokio::fs;
-use super::sat_pass_predict::SatPassData;
-use super::satellites::{SATELLITE_LIST, get_notify_id_list};
+use chrono::Utc;
+use std::{collections::HashMap, sync::Arc};
+use super::sat_pass_predict::{bjt, load_pass_cache, SatPassData};
+use super::satellites::{get_notify_id_list, load_satellite_list};
+use crate::{
+    app_status::AppStatus,
+    msg::group_msg::{send_group_message_to_groups, send_private_messages},
+    response::Reply,
+};
 
-const CACHE_FILE: &str = "sat_pass_cache.json";
-
-pub async fn check_upcoming_passes() -> Vec<String> {
-    let content = match fs::read_to_string(CACHE_FILE).await {
-        Ok(c) => c,
-        Err(e) => {
-            tracing::error!("读取缓存文件失败: {}", e);
-            return Vec::new();
-        }
-    };
-
-    let data: HashMap<String, SatPassData> = match serde_json::from_str(&content) {
-        Ok(d) => d,
-        Err(e) => {
-            tracing::error!("解析缓存数据失败: {}", e);
-            return Vec::new();
-        }
-    };
-
-    let sat_map = SATELLITE_LIST.read().unwrap();
-    let notify_ids = get_notify_id_list(&sat_map);
-
-    let now = Utc::now().timestamp();
+/// Reminders due in the minute before `now + 1h` and the minute before the pass starts
+/// - the reminder task runs once a minute, so each pass is announced once per window
+pub fn check_upcoming_passes(
+    data: &HashMap<String, SatPassData>,
+    notify_ids: &[u32],
+    now: i64,
+) -> Vec<String> {
     let mut result = Vec::new();
 
     for sat in data.values() {
@@ -43,16 +31,11 @@ pub async fn check_upcoming_passes() -> Vec<String> {
                     sat.satname
                 ));
             } else if countdown <= 60 && countdown > 0 {
-                let start_bjt = Utc.timestamp_opt(pass.startUTC, 0).single().unwrap_or(Utc::now()) + Duration::hours(8);
-                let end_bjt = Utc.timestamp_opt(pass.endUTC, 0).single().unwrap_or(Utc::now()) + Duration::hours(8);
-
                 result.push(format!(
-                    "[提醒]\n>>> 卫星过境中 >>>\n{:02}:{:02} -> [{}] -> {:02}:{:02}\n速来建工楼顶喵！",
-                    start_bjt.hour(),
-                    start_bjt.minute(),
+                    "[提醒]\n>>> 卫星过境中 >>>\n{} -> [{}] -> {}\n速来建工楼顶喵！",
+                    bjt(pass.startUTC).format("%H:%M"),
                     sat.satname,
-                    end_bjt.hour(),
-                    end_bjt.minute()
+                    bjt(pass.endUTC).format("%H:%M"),
                 ));
             }
         }
@@ -60,3 +43,33 @@ pub async fn check_upcoming_passes() -> Vec<String> {
 
     result
 }
+
+/// Send the due reminders to `pass_predict_group_id` and privately to `pass_predict_user_id`
+pub async fn send_pass_reminders(
+    app_status: &Arc<AppStatus>,
+) -> anyhow::Result<()> {
+    let notify_ids = get_notify_id_list(&load_satellite_list(app_status).await?);
+    if notify_ids.is_empty() {
+        return Ok(());
+    }
+    let data = load_pass_cache(app_status).await?;
+    let reminders = check_upcoming_passes(&data, &notify_ids, Utc::now().timestamp());
+    if reminders.is_empty() {
+        return Ok(());
+    }
+
+    let (groups, users) = {
+        let config_guard = app_status.config.read().await;
+        (
+            config_guard.backend_config.pass_predict_group_id.clone().unwrap_or_default(),
+            config_guard.backend_config.pass_predict_user_id.clone().unwrap_or_default(),
+        )
+    };
+    for reminder in reminders {
+        tokio::join!(
+            send_group_message_to_groups(Reply::text(reminder.clone()), &groups, app_status),
+            send_private_messages(&users, &reminder, app_status),
+        );
+    }
+    Ok(())
+}
@@ -1,16 +1,22 @@
-use chrono::{Duration, Local, TimeZone};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::Path};
-use super::satellites::{SATELLITE_LIST, get_track_sat_list};
-use crate::app_status;
-use crate::config::Config;
+use std::collections::HashMap;
+use super::{
+    all_pass_notify::get_all_sats_pass,
+    satellites::{find_alias_match, get_track_sat_list, load_satellite_list},
+};
 use crate::{
-    fs::handler::{FileData, FileRequest, FileFormat},
     app_status::AppStatus,
+    fs::handler::{check_file_exists, load_file, write_file, FileData, FileFormat},
+    msg::prelude::MessageEvent,
+    response::{ApiResponse, Reply},
 };
 
-const CACHE_FILE: &str = "sat_pass_cache.json";
+/// Satellite name -> `SatPassData`, also served by `GET /api/passes`
+pub const CACHE_FILE: &str = "sat_pass_cache.json";
+/// Passes listed by `/pass <卫星>`
+const MAX_LISTED_PASSES: usize = 3;
 
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,183 +36,168 @@ pub struct SatPassData {
     pub last_update: i64,
 }
 
+pub fn bjt(timestamp: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default() + Duration::hours(8)
+}
+
+pub async fn load_pass_cache(
+    app_status: &AppStatus,
+) -> anyhow::Result<HashMap<String, SatPassData>> {
+    if !check_file_exists(app_status.file_tx.clone(), CACHE_FILE.to_string()).await {
+        return Ok(HashMap::new());
+    }
+    match load_file(app_status.file_tx.clone(), CACHE_FILE.to_string(), FileFormat::Json).await? {
+        FileData::Json(data) => Ok(serde_json::from_value(data)?),
+        _ => Err(anyhow::anyhow!("Unexpected file format received")),
+    }
+}
+
+/// Fetch the passes of every tracked satellite from `pass_api_config.host`
+/// - a satellite whose request fails keeps its old entry
 pub async fn update_sat_pass_cache(
     app_status: &AppStatus
-) -> anyhow::Result<()> {
+) -> anyhow::Result<usize> {
     let client = Client::new();
-    let mut cache: HashMap<String, SatPassData> = HashMap::new();
-    let config = app_status.config.read().await;
-    let conf = config.pass_api_config.clone();
-
-    let track_sat_list = {
-        let sat_list = SATELLITE_LIST.read().unwrap();
-        get_track_sat_list(&sat_list)
-    };
-
-    let tx_filerequest = app_status.file_tx.clone();
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    let file_request = FileRequest::Read {
-        path: CACHE_FILE.into(),
-        format: FileFormat::Json,
-        responder: tx,
-    };
+    let conf = app_status.config.read().await.pass_api_config.clone();
+    if conf.host.is_empty() {
+        return Err(anyhow::anyhow!("pass_api_config.host is not configured"));
+    }
 
-    let tx_filerequest = tx_filerequest.write().await;
-    if let Err(e) = tx_filerequest.send(file_request).await {
-        return Err(anyhow::anyhow!("Failed to send file read request: {}", e));
-    };
+    let track_sat_list = get_track_sat_list(&load_satellite_list(app_status).await?);
+    let mut cache = load_pass_cache(app_status).await.unwrap_or_else(|e| {
+        tracing::warn!("读取缓存文件失败: {}，重新生成", e);
+        HashMap::new()
+    });
 
+    let mut updated = 0;
     for (name, sat_info) in track_sat_list.iter() {
         let url = format!(
             "{}/{}/{}/{}/{}/{}/{}&apikey={}",
             conf.host, sat_info.id, conf.lat, conf.lon, conf.alt, conf.day, conf.min_elevation, conf.api_key
         );
 
-        match client.get(&url).send().await {
-            Ok(response) => match response.text().await {
-                Ok(body) => match serde_json::from_str::<serde_json::Value>(&body) {
-                    Ok(json) => {
-                        let info = &json["info"];
-                        let defaut_vec = Vec::new();
-                        let passes = json["passes"].as_array().unwrap_or(&defaut_vec);
-
-                        let parsed_passes = passes
-                            .iter()
-                            .map(|p| {
-                                let start = p["startUTC"].as_i64().unwrap_or(0);
-                                let end = p["endUTC"].as_i64().unwrap_or(0);
-                                let duration = if end > start {(end - start) as u64} else {0};
-
-                                PassInfo {
-                                    startUTC: start,
-                                    maxEl: p["maxEl"].as_f64().unwrap_or(0.0),
-                                    maxUTC: p["maxUTC"].as_i64().unwrap_or(0),
-                                    endUTC: end,
-                                    duration,
-                                }
-                            })
-                            .collect();
-
-                        cache.insert(
-                            name.clone(),
-                            SatPassData {
-                                satid: info["satid"].as_u64().unwrap_or(0) as u32,
-                                satname: info["satname"]
-                                    .as_str()
-                                    .unwrap_or(name)
-                                    .to_string(),
-                                passes: parsed_passes,
-                                last_update: chrono::Utc::now().timestamp(),
-                            },
-                        );
-                    }
-                    Err(e) => {
-                        tracing::error!("解析 JSON 失败：{} ({})", name, e);
-                    }
-                },
+        let json = match client.get(&url).send().await {
+            Ok(response) => match response.json::<serde_json::Value>().await {
+                Ok(json) => json,
                 Err(e) => {
-                    tracing::error!("读取响应正文失败：{} ({})", name, e);
+                    tracing::error!("解析 JSON 失败：{} ({})", name, e);
+                    continue;
                 }
             },
             Err(e) => {
                 tracing::error!("请求失败：{} ({})", name, e);
+                continue;
             }
-        }
+        };
+
+        let info = &json["info"];
+        let parsed_passes = json["passes"]
+            .as_array()
+            .map(|passes| passes.iter().map(|p| {
+                let start = p["startUTC"].as_i64().unwrap_or(0);
+                let end = p["endUTC"].as_i64().unwrap_or(0);
+                let duration = if end > start {(end - start) as u64} else {0};
+
+                PassInfo {
+                    startUTC: start,
+                    maxEl: p["maxEl"].as_f64().unwrap_or(0.0),
+                    maxUTC: p["maxUTC"].as_i64().unwrap_or(0),
+                    endUTC: end,
+                    duration,
+                }
+            }).collect())
+            .unwrap_or_default();
+
+        cache.insert(
+            name.clone(),
+            SatPassData {
+                satid: info["satid"].as_u64().map(|id| id as u32).unwrap_or(sat_info.id),
+                satname: info["satname"]
+                    .as_str()
+                    .unwrap_or(name)
+                    .to_string(),
+                passes: parsed_passes,
+                last_update: Utc::now().timestamp(),
+            },
+        );
+        updated += 1;
     }
 
-    let serialized = serde_json::to_string_pretty(&cache)?;
-    fs::write(CACHE_FILE, serialized)?;
-
-    let bj_now = chrono::Utc::now() + Duration::hours(8);
-    let fmt_time = bj_now.format("%Y年%m月%d日%H时%M分").to_string();
-    tracing::info!("卫星预测信息更新时间: {}", fmt_time);
+    if updated > 0 {
+        write_file(
+            app_status.file_tx.clone(),
+            CACHE_FILE.to_string(),
+            &FileData::Json(serde_json::to_value(&cache)?),
+        ).await?;
+        tracing::info!("卫星预测信息更新时间: {}", bjt(Utc::now().timestamp()).format("%Y年%m月%d日%H时%M分"));
+    }
 
-    Ok(())
+    Ok(updated)
 }
 
-fn _need_update_cache() -> bool {
-    if !Path::new(CACHE_FILE).exists() {
-        return true;
+fn format_pass(pass: &PassInfo, now: i64) -> String {
+    if pass.startUTC <= now {
+        let remaining = pass.endUTC - now;
+        format!("过境中 | 剩{}m{}s | 最高 {:.0}°", remaining / 60, remaining % 60, pass.maxEl)
+    } else {
+        format!(
+            "{} -> {} | 最高 {:.0}°",
+            bjt(pass.startUTC).format("%m-%d %H:%M"),
+            bjt(pass.endUTC).format("%H:%M"),
+            pass.maxEl
+        )
     }
-    let content = match fs::read_to_string(CACHE_FILE) {
-        Ok(c) => c,
-        Err(e) => {
-            tracing::error!("读取缓存文件失败: {}", e);
-            return true;
-        }
-    };
-
-    // if let Ok(data): Result<HashMap<String, SatPassData>, _> = serde_json::from_str(&content) {
-    //     let latest = data.values().map(|d| d.last_update).max().unwrap_or(0);
-    //     let now = chrono::Utc::now().timestamp();
-    //     return now - latest > 60 * 60 * 24 * 2;
-    // }
-
-    let data: HashMap<String, SatPassData> = match serde_json::from_str(&content) {
-        Ok(d) => d,
-        Err(e) => {
-            tracing::error!("解析缓存数据失败: {}", e);
-            return true;
-        }
-    };
-    let latest = data.values().map(|d| d.last_update).max().unwrap_or(0);
-    let now = chrono::Utc::now().timestamp();
-
-    return now - latest > 60 * 60 * 24 * 2;
 }
 
-pub fn query_satellite(name: Option<String>) -> Vec<String> {
-    let content = fs::read_to_string(CACHE_FILE).unwrap_or_default();
-    let data: HashMap<String, SatPassData> = serde_json::from_str(&content).unwrap_or_default();
-    let mut result = Vec::new();
-    let now = chrono::Utc::now().timestamp();
-
-    match name {
-        Some(n) => {
-            let match_name = find_alias_match(&n);
-            if let Some(key) = match_name {
-                if let Some(sat) = data.get(&key) {
-                    if let Some(p) = sat.passes.iter().find(|p| p.endUTC > now) {
-                        let start = Local.timestamp_opt(p.startUTC, 0)
-                            .unwrap();
-                        let end = Local.timestamp_opt(p.endUTC, 0)
-                            .unwrap();
-                        result.push(format!(
-                            "[预测]\n{} 下一次过境将在{}开始，最高仰角{:.1}°，{}结束喵",
-                            sat.satname,
-                            start.format("%m-%d %H:%M"),
-                            p.maxEl,
-                            end.format("%m-%d %H:%M")
-                        ));
-                    } else {
-                        result.push("没有即将过境的信息呢".to_string());
-                    }
-                } else {
-                    result.push("缓存里没有它的数据呢".to_string());
-                }
-            } else {
-                result.push("这是什么？能吃吗".to_string());
-            }
-        }
-        None => {
-            result.push("Rinko不知道你要查什么喵...".to_string());
-        }
+/// The next few passes of one satellite, by name, alias or NORAD id
+pub async fn query_satellite(
+    app_status: &AppStatus,
+    name: &str,
+) -> anyhow::Result<Vec<String>> {
+    let satellite_list = load_satellite_list(app_status).await?;
+    let data = load_pass_cache(app_status).await?;
+    let now = Utc::now().timestamp();
+
+    let Some(key) = find_alias_match(name, &satellite_list) else {
+        return Ok(vec!["这是什么？能吃吗".to_string()]);
+    };
+    let Some(sat) = data.get(&key) else {
+        return Ok(vec!["缓存里没有它的数据呢".to_string()]);
+    };
+    let passes: Vec<&PassInfo> = sat.passes.iter().filter(|p| p.endUTC > now).collect();
+    if passes.is_empty() {
+        return Ok(vec!["没有即将过境的信息呢".to_string()]);
     }
 
-    result
+    let mut result = vec![format!("[预测]\n{} 的过境 (北京时间):", sat.satname)];
+    result.extend(passes.into_iter().take(MAX_LISTED_PASSES).map(|p| format_pass(p, now)));
+    Ok(result)
 }
 
-pub fn find_alias_match(query: &str) -> Option<String> {
-    use crate::pass_query::satellites::get_satellite_aliases;
-
-    let norm_query = sat_name_normalize(query);
-    let alias_map = get_satellite_aliases();
+/// `/pass` lists the next pass of every reminded satellite, `/pass <卫星>` the next few of one
+pub async fn pass_command(
+    app_status: &AppStatus,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let enabled = {
+        let config_guard = app_status.config.read().await;
+        config_guard.pass_predict_configured() && config_guard.backend_config.pass_predict_enabled(payload)
+    };
+    if !enabled {
+        return ApiResponse::error("这里没有开放过境查询喵");
+    }
 
-    for (key, aliases) in alias_map.iter() {
-        if sat_name_normalize(key) == norm_query || aliases.iter().any(|a| a == &norm_query || a == query) {
-            return Some(key.to_string());
+    let lines = match args.trim() {
+        "" => get_all_sats_pass(app_status).await,
+        name => query_satellite(app_status, name).await,
+    };
+    match lines {
+        Ok(lines) if lines.is_empty() => Reply::text("暂无过境预测喵"),
+        Ok(lines) => Reply::lines(lines),
+        Err(e) => {
+            tracing::error!("读取过境预测失败: {}", e);
+            ApiResponse::error("读取过境预测失败喵")
         }
     }
-
-    None
-}
\ No newline at end of file
+}
@@ -1,16 +1,22 @@
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs};
-use std::sync::RwLock;
+use std::collections::HashMap;
+use crate::{
+    app_status::AppStatus,
+    fs::handler::{check_file_exists, load_file, FileData, FileFormat},
+};
 
 const MAIN_FILE: &str = "satellites.toml";
+/// Written by the hot-load commands, its entries override `MAIN_FILE`
 const TEMP_FILE: &str = "temp_sat_cache.toml";
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AllSatInfo {
     pub aliases: Option<Vec<String>>,
     pub id: Option<u32>,
+    /// Passes are predicted for tracked satellites
     #[serde(default)]
     pub track: bool,
+    /// Reminders are sent for these, they are also the ones listed by a bare `/pass`
     #[serde(default)]
     pub notify: bool,
 }
@@ -20,44 +26,36 @@ pub struct SatInfo {
     pub id: u32,
 }
 
-fn load_combined_satellites() -> HashMap<String, AllSatInfo> {
-    let main_content = fs::read_to_string(MAIN_FILE).unwrap_or_else(|_| {
-        eprintln!("无法读取 {}", MAIN_FILE);
-        String::new()
-    });
-
-    let mut main_map: HashMap<String, AllSatInfo> =
-        toml::from_str(&main_content).unwrap_or_else(|_| {
-            eprintln!("解析 {} 失败", MAIN_FILE);
-            HashMap::new()
-        });
-
-    let temp_content = fs::read_to_string(TEMP_FILE).unwrap_or_else(|_| {
-        eprintln!("无法读取 {}", TEMP_FILE);
-        String::new()
-    });
-
-    let temp_map: HashMap<String, AllSatInfo> =
-        toml::from_str(&temp_content).unwrap_or_else(|_| {
-            eprintln!("解析 {} 失败", TEMP_FILE);
-            HashMap::new()
-        });
-
-    for (name, info) in temp_map {
-        main_map.insert(name, info);
-    }
+/// Lowercase alphanumerics, `AO-91`, `ao91` and `AO 91` are the same satellite
+pub fn sat_name_normalize(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase()
+}
 
-    main_map
+async fn load_satellite_file(
+    app_status: &AppStatus,
+    path: &str,
+) -> anyhow::Result<HashMap<String, AllSatInfo>> {
+    if !check_file_exists(app_status.file_tx.clone(), path.to_string()).await {
+        return Ok(HashMap::new());
+    }
+    match load_file(app_status.file_tx.clone(), path.to_string(), FileFormat::Toml).await? {
+        FileData::Toml(data) => Ok(data.try_into()?),
+        _ => Err(anyhow::anyhow!("Unexpected file format received")),
+    }
 }
 
-pub static SATELLITE_LIST: Lazy<RwLock<HashMap<String, AllSatInfo>>> = Lazy::new(|| {
-    RwLock::new(load_combined_satellites())
-});
+/// `MAIN_FILE` merged with `TEMP_FILE`, read on every call so hot-loaded satellites apply at once
+pub async fn load_satellite_list(
+    app_status: &AppStatus,
+) -> anyhow::Result<HashMap<String, AllSatInfo>> {
+    let mut main_map = load_satellite_file(app_status, MAIN_FILE).await?;
+    main_map.extend(load_satellite_file(app_status, TEMP_FILE).await?);
+    Ok(main_map)
+}
 
-pub fn get_satellite_aliases() -> HashMap<String, Vec<String>> {
-    use crate::query::sat_query::sat_name_normalize;
-    
-    let satellite_list = SATELLITE_LIST.read().unwrap();
+pub fn get_satellite_aliases(
+    satellite_list: &HashMap<String, AllSatInfo>
+) -> HashMap<String, Vec<String>> {
     let mut map = HashMap::new();
 
     for (name, info) in satellite_list.iter() {
@@ -79,10 +77,17 @@ pub fn get_satellite_aliases() -> HashMap<String, Vec<String>> {
     map
 }
 
-pub fn refresh_satellite_list() {
-    let new_map = load_combined_satellites();
-    let mut map = SATELLITE_LIST.write().unwrap();
-    *map = new_map;
+/// The satellite list's name for a name, alias or NORAD id
+pub fn find_alias_match(
+    query: &str,
+    satellite_list: &HashMap<String, AllSatInfo>,
+) -> Option<String> {
+    let norm_query = sat_name_normalize(query);
+
+    get_satellite_aliases(satellite_list)
+        .into_iter()
+        .find(|(_, aliases)| aliases.iter().any(|a| a == &norm_query || a == query))
+        .map(|(key, _)| key)
 }
 
 pub fn get_track_sat_list(
@@ -108,4 +113,4 @@ pub fn get_notify_id_list(
         .filter(|s| s.notify)
         .filter_map(|s| s.id)
         .collect()
-}
\ No newline at end of file
+}
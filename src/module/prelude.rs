@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use crate::{
-    module::amsat::prelude::*,
+    module::{amsat::prelude::*, callsign::parser::Callsign},
     msg::prelude::MessageEvent
 };
 
@@ -27,17 +27,8 @@ impl ReportStatus {
 pub fn is_valid_callsign(
     callsign: &String,
 ) -> bool {
-    
-    if callsign.is_empty() {
-        return false;
-    }
-
-    // no CJK and no special characters except `/`
-    if !callsign.is_ascii() || callsign.chars().any(|c| !c.is_ascii_alphanumeric() && c != '/') {
-        return false;
-    }
-
-    true
+    // prefix/base/suffix structure, e.g. B0/NA1SS, BA1ABC/P, JA1XYZ/VK2
+    Callsign::parse(callsign).is_some()
 }
 
 /// Check if the grid is a valid maidenhead grid square, accepts 4 or 6 characters or more
@@ -60,21 +51,116 @@ pub fn is_valid_maidenhead_grid(grid: &str) -> bool {
         return false;
     }
 
-    // third pair: lowercase a to x
-    if len >= 6 {
-        if !valid_lowercase(chars[4]) || !valid_lowercase(chars[5]) {
-            return false;
+    // then lowercase a to x and 0 to 9 by turns, every pair is checked
+    chars[4..].chunks(2).enumerate().all(|(index, pair)| {
+        if index % 2 == 0 {
+            pair.iter().all(|c| valid_lowercase(*c))
+        } else {
+            pair.iter().all(|c| c.is_ascii_digit())
         }
+    })
+}
+
+/// Same check in any letter case, as other loggers write `OM89AB`
+pub fn is_valid_grid_any_case(grid: &str) -> bool {
+    is_valid_maidenhead_grid(&grid.to_lowercase())
+}
+
+/// Grid of any case in the usual form, `om89AB` -> `OM89ab`, `None` when invalid
+pub fn normalize_grid(grid: &str) -> Option<String> {
+    is_valid_grid_any_case(grid).then(|| {
+        grid.chars()
+            .enumerate()
+            .map(|(index, c)| if index < 2 { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() })
+            .collect()
+    })
+}
+
+/// `OM89` of any valid grid, `None` otherwise
+pub fn grid_square(grid: &str) -> Option<String> {
+    is_valid_grid_any_case(grid).then(|| grid.chars().take(4).collect::<String>().to_uppercase())
+}
+
+/// Lat/lon box covered by a maidenhead grid, degrees east/north
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridBounds {
+    pub lon_min: f64,
+    pub lon_max: f64,
+    pub lat_min: f64,
+    pub lat_max: f64,
+}
+
+impl GridBounds {
+    pub fn center(&self) -> (f64, f64) {
+        ((self.lat_min + self.lat_max) / 2.0, (self.lon_min + self.lon_max) / 2.0)
     }
 
-    // fourth pair: 0 to 9
-    if len == 8 {
-        if !chars[6].is_ascii_digit() || !chars[7].is_ascii_digit() {
-            return false;
+    /// Smallest box containing both
+    pub fn union(&self, other: &GridBounds) -> GridBounds {
+        GridBounds {
+            lon_min: self.lon_min.min(other.lon_min),
+            lon_max: self.lon_max.max(other.lon_max),
+            lat_min: self.lat_min.min(other.lat_min),
+            lat_max: self.lat_max.max(other.lat_max),
         }
     }
+}
 
-    true
+/// Field 20x10 deg, square 2x1 deg, subsquare 5'x2.5', extended square 30"x15"
+pub fn maidenhead_bounds(grid: &str) -> Option<GridBounds> {
+    if !is_valid_grid_any_case(grid) {
+        return None;
+    }
+
+    let chars: Vec<char> = grid.to_ascii_uppercase().chars().collect();
+    let mut lon = -180.0;
+    let mut lat = -90.0;
+    let mut lon_size = 360.0;
+    let mut lat_size = 180.0;
+
+    for (index, pair) in chars.chunks(2).enumerate() {
+        let digits = index % 2 == 1;
+        let divisions = match index {
+            0 => 18.0,
+            _ if digits => 10.0,
+            _ => 24.0,
+        };
+        let value = |c: char| {
+            if digits {
+                c.to_digit(10).map(|d| d as f64)
+            } else {
+                (c as u32).checked_sub('A' as u32).map(|v| v as f64)
+            }
+        };
+
+        lon_size /= divisions;
+        lat_size /= divisions;
+        lon += value(pair[0])? * lon_size;
+        lat += value(pair[1])? * lat_size;
+    }
+
+    Some(GridBounds {
+        lon_min: lon,
+        lon_max: lon + lon_size,
+        lat_min: lat,
+        lat_max: lat + lat_size,
+    })
+}
+
+/// Center of the grid as (lat, lon)
+pub fn maidenhead_to_lat_lon(grid: &str) -> Option<(f64, f64)> {
+    maidenhead_bounds(grid).map(|bounds| bounds.center())
+}
+
+pub const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Haversine distance between two (lat, lon) points
+pub fn great_circle_distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
 }
 
 // Check if char is in A-R
@@ -95,12 +181,15 @@ pub fn callsign_auth(
     // callsign may contains `/`
     // e.g. NA1SS/0 or B0/NA1SS
     // extracting real callsign for auth
-    let mut callsign = callsign.to_uppercase();
-    if callsign.contains('/') {
-        let parts: Vec<&str> = callsign.split('/').collect();
-        // longest part is callsign
-        callsign = parts.iter().max_by_key(|s| s.len()).unwrap_or(&callsign.as_str()).to_string();
-    }
+    let callsign = match Callsign::parse(callsign) {
+        Some(parsed) => parsed.base,
+        None => {
+            // fall back to the longest part for unusual callsigns
+            let callsign = callsign.to_uppercase();
+            callsign.split('/').max_by_key(|s| s.len()).unwrap_or_default().to_string()
+        }
+    };
+    // the group card is checked, private chats have none so only admins pass there
     let nickname = payload.sender.card.clone();
     let user_id = payload.sender.user_id.clone();
     if !nickname.to_uppercase().contains(&callsign) && !admin_list.contains(&user_id) {
@@ -3,10 +3,11 @@ use std::time::Duration;
 use chrono::{self, DateTime, Timelike, Utc};
 use crate::{
     app_status::AppStatus, fs, module::{
-        amsat::{self, prelude::*},
+        amsat::{self, prelude::*, report_audit::*},
         solar_image,
-        tools::render::SATSTATUS_PIC_PATH_PREFIX,
-    }, msg::group_msg::send_group_message_to_multiple_groups, response
+        pass_query::{sat_pass_notify, sat_pass_predict},
+        tools::{render::{SATSTATUS_PIC_PATH_PREFIX, STATS_PIC_PATH_PREFIX}, roaming, roaming_feed},
+    }, msg::group_msg::{send_group_message_to_groups, send_group_message_to_multiple_groups, send_private_messages}, response
 };
 
 pub async fn scheduled_task_handler(
@@ -154,7 +155,7 @@ pub async fn scheduled_task_handler(
                         tracing::error!("太阳活动图更新失败: {}", e);
                         if attempt >= MAX_RETRIES {
                             tracing::error!("太阳活动图更新失败，尝试次数: {}", MAX_RETRIES);
-                            let response = response::ApiResponse::<Vec<String>>::error(
+                            let response = response::Reply::error(
                                 format!("太阳活动图更新失败: {}", e),
                             );
                             send_group_message_to_multiple_groups(response, &app_status_cp2).await;
@@ -168,6 +169,7 @@ pub async fn scheduled_task_handler(
         }
     });
     
+    const REPORT_HOLD_EXPIRE_HOURS: i64 = 24;
     let app_status_cp3 = Arc::clone(app_status);
     let _user_report_task = tokio::spawn(async move {
         loop {
@@ -205,14 +207,38 @@ pub async fn scheduled_task_handler(
                     };
 
                     let now = Utc::now();
+                    // conflicting reports wait for confirmation or an admin override
+                    if let Some(hold) = file_element.hold.as_ref().filter(|h| h.is_active()) {
+                        let hold_start = DateTime::parse_from_rfc3339(&hold.created_at)
+                            .map(|dt| dt.with_timezone(&Utc))
+                            .unwrap_or(now);
+                        if now - hold_start <= chrono::Duration::hours(REPORT_HOLD_EXPIRE_HOURS) {
+                            data_to_keep.push(file_element);
+                            continue;
+                        }
+                        tracing::warn!("{} 的冲突报告超时未处理，数据将被丢弃", satellite_file_format.name);
+                        notify_expired_hold(&app_status_cp3, &satellite_file_format.name, &file_element, REPORT_HOLD_EXPIRE_HOURS).await;
+                        for report in &file_element.report {
+                            let entry = ReportAuditEntry::new(AuditAction::Discard, report, None)
+                                .with_note(format!("hold expired after {}h", REPORT_HOLD_EXPIRE_HOURS));
+                            append_audit_entry(&app_status_cp3, entry).await;
+                        }
+                        continue;
+                    }
+
                     if now - time_block > chrono::Duration::minutes(20) {
                         if file_element.report.is_empty() {
                             tracing::warn!("没有可以处理的数据");
                         }
                         for report in &file_element.report {
-                            if let Err(e) = amsat::user_report::push_user_report_from_SatStatus(report).await {
-                                tracing::error!("上传用户数据失败，数据将被丢弃: {}", e);
-                            }
+                            let entry = match amsat::user_report::push_user_report_from_SatStatus(report).await {
+                                Ok(_) => ReportAuditEntry::new(AuditAction::Submit, report, None),
+                                Err(e) => {
+                                    tracing::error!("上传用户数据失败，数据将被丢弃: {}", e);
+                                    ReportAuditEntry::new(AuditAction::SubmitFailed, report, None).with_note(e.to_string())
+                                }
+                            };
+                            append_audit_entry(&app_status_cp3, entry).await;
                         }
                         // discard the processed data
                     } else {
@@ -242,23 +268,130 @@ pub async fn scheduled_task_handler(
         }
     });
 
+    let app_status_cp4 = Arc::clone(app_status);
+    let _roaming_archive_task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match roaming::archive_expired_roaming(&app_status_cp4).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("已归档 {} 条过期漫游信息", count),
+                Err(e) => tracing::error!("归档过期漫游信息失败: {}", e),
+            }
+        }
+    });
+
+    let app_status_cp5 = Arc::clone(app_status);
+    let _roaming_digest_task = tokio::spawn(async move {
+        loop {
+            // wake up on every full hour, send when it matches the configured BJT hour
+            let now = Utc::now();
+            let next_hour = (now + chrono::Duration::hours(1))
+                .with_minute(0)
+                .and_then(|t| t.with_second(0))
+                .unwrap_or(now + chrono::Duration::hours(1));
+            tokio::time::sleep((next_hour - now).to_std().unwrap_or(Duration::from_secs(60 * 60))).await;
+
+            let digest_hour = match app_status_cp5.config.read().await.roaming_announce_config.as_ref() {
+                Some(config) if config.digest => config.digest_hour,
+                _ => continue,
+            };
+            if (Utc::now() + chrono::Duration::hours(8)).hour() != digest_hour {
+                continue;
+            }
+            if let Err(e) = roaming_feed::send_roaming_digest(&app_status_cp5).await {
+                tracing::error!("发送漫游汇总失败: {}", e);
+            }
+        }
+    });
+
+    // without an API host and a chat to serve, pass prediction stays off
+    if app_status.config.read().await.pass_predict_configured() {
+        spawn_pass_tasks(app_status);
+    } else {
+        tracing::info!("未配置过境预测，不启动过境预测和提醒任务");
+    }
+
     let _old_satstatus_img_cleanup_task = tokio::spawn(start_cleanup_task());
 }
 
+fn spawn_pass_tasks(app_status: &Arc<AppStatus>) {
+    let app_status_cp6 = Arc::clone(app_status);
+    let _pass_cache_task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60 * 6));
+        loop {
+            interval.tick().await;
+            match sat_pass_predict::update_sat_pass_cache(&app_status_cp6).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("已更新 {} 颗卫星的过境预测", count),
+                Err(e) => tracing::error!("更新过境预测失败: {}", e),
+            }
+        }
+    });
+
+    // the cache is refreshed on its own task, a slow refresh must not skip a reminder window
+    let app_status_cp7 = Arc::clone(app_status);
+    let _pass_notify_task = tokio::spawn(async move {
+        loop {
+            if let Err(e) = sat_pass_notify::send_pass_reminders(&app_status_cp7).await {
+                tracing::error!("发送过境提醒失败: {}", e);
+            }
+
+            // reminders are checked at the start of every minute
+            let now = Utc::now();
+            let next_minute = (now + chrono::Duration::minutes(1))
+                .with_second(0)
+                .and_then(|t| t.with_nanosecond(0))
+                .unwrap_or(now + chrono::Duration::minutes(1));
+            tokio::time::sleep((next_minute - now).to_std().unwrap_or(Duration::from_secs(60))).await;
+        }
+    });
+}
+
+/// Tell the admins privately and the groups the reports came from that a held pass was discarded
+async fn notify_expired_hold(
+    app_status: &Arc<AppStatus>,
+    sat_name: &str,
+    element: &SatelliteFileElement,
+    expire_hours: i64,
+) {
+    let mut lines = vec![format!(
+        "{} 的冲突报告 {} 小时内没有确认或放行，以下报告已丢弃，不会提交到AMSAT:",
+        sat_name, expire_hours
+    )];
+    lines.extend(element.report.iter().map(|r| format!("{}: {}", r.callsign, r.report)));
+    let text = lines.join("\n");
+
+    let mut groups: Vec<u64> = Vec::new();
+    for group_id in element.report.iter().filter_map(|r| r.source.as_ref()).map(|s| s.group_id) {
+        // 0 is a private chat
+        if group_id != 0 && !groups.contains(&group_id) {
+            groups.push(group_id);
+        }
+    }
+    let admin_id = app_status.config.read().await.bot_config.admin_id.clone();
+    tokio::join!(
+        send_group_message_to_groups(response::Reply::text(text.clone()), &groups, app_status),
+        send_private_messages(&admin_id, &text, app_status),
+    );
+}
+
 async fn start_cleanup_task() {
     let mut interval = tokio::time::interval(Duration::from_secs(60 * 10));
 
     loop {
         interval.tick().await;
-        if let Err(e) = cleanup_old_files().await {
-            tracing::error!("Cleanup task failed: {}", e);
+        for dir in [SATSTATUS_PIC_PATH_PREFIX, STATS_PIC_PATH_PREFIX] {
+            if let Err(e) = cleanup_old_files(dir).await {
+                tracing::error!("Cleanup task failed for {}: {}", dir, e);
+            }
         }
     }
 }
 
-async fn cleanup_old_files() -> anyhow::Result<()> {
+async fn cleanup_old_files(dir: &str) -> anyhow::Result<()> {
     let now = Utc::now();
-    let dir = std::path::Path::new(SATSTATUS_PIC_PATH_PREFIX);
+    let dir = std::path::Path::new(dir);
 
     if !dir.exists() {
         std::fs::create_dir_all(dir)?;
@@ -1,71 +1,29 @@
-use reqwest::Client;
-use std::path::PathBuf;
+use std::sync::Arc;
 use crate::{
     app_status,
-    fs::handler::*,
+    module::{
+        solar_image::space_weather::{read_space_weather_history, update_space_weather},
+        tools::render::render_space_weather,
+    },
 };
 use std::path::Path;
 use url::Url;
 
-const IMAGE_PATH_PREFIX: &str = "runtime_data/pic/";
-
+/// Fetch the space-weather feed and regenerate the dashboard
 pub async fn get_solar_image(
-    app_status: &app_status::AppStatus,
+    app_status: &Arc<app_status::AppStatus>,
 ) -> anyhow::Result<()> {
-    let client = Client::new();
-    let img_url = "https://www.hamqsl.com/solarn0nbh.php?image=random";
-
-    // ensure the directory exists
-    //let now = Local::now();
-    let temp_file = format!(
-        "{}solar_image_latest.png",
-        IMAGE_PATH_PREFIX,
-    );
-
-    // download
-    let response = client.get(img_url)
-        .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-        .send()
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to download image: {}", e))?;
-
-    let img_bytes = response.bytes()
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to read image bytes: {}", e))?;
-
-    // save file
-    let tx_filerequest = app_status.file_tx.clone();
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    let exist_request = FileRequest::Write {
-        path: temp_file.clone().into(),
-        format: FileFormat::Png,
-        data: FileData::Png(img_bytes.to_vec()),
-        responder: tx,
-    };
-    let tx_filerequest = tx_filerequest.write().await;
-    if let Err(e) = tx_filerequest.send(exist_request).await {
-        return Err(anyhow::anyhow!("Failed to send file write request: {}", e));
-    }
-
-    match rx.await {
-        Ok(Ok(())) => {}
-        Ok(Err(e)) => {
-            return Err(anyhow::anyhow!("Failed to write solar image: {}", e));
-        }
+    let weather = update_space_weather(app_status).await?;
+    let history = match read_space_weather_history(app_status).await {
+        Ok(history) => history,
         Err(e) => {
-            return Err(anyhow::anyhow!("Failed to receive file write response: {}", e));
+            tracing::warn!("Failed to read space weather history: {}", e);
+            Vec::new()
         }
-    }
-
-    // get absolute path after ensured the file exists
-    let abs_path: PathBuf = tokio::task::spawn_blocking(move || {
-        PathBuf::from(&temp_file).canonicalize()
-    })
-    .await
-    .map_err(|e| anyhow::anyhow!("Failed to join canonicalize task: {}", e))?
-    .map_err(|e| anyhow::anyhow!("Failed to canonicalize path: {}", e))?;
+    };
+    render_space_weather(&weather, &history).await?;
 
-    tracing::info!("Solar image saved to {:?}", abs_path);
+    tracing::info!("Space weather dashboard rendered");
     Ok(())
 }
 
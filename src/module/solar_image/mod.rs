@@ -1 +1,2 @@
-pub mod get_image;
\ No newline at end of file
+pub mod get_image;
+pub mod space_weather;
\ No newline at end of file
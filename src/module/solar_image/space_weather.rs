@@ -0,0 +1,520 @@
+use crate::{
+    app_status::AppStatus,
+    fs::handler::{check_file_exists, load_file, write_file, FileData, FileFormat},
+    msg::group_msg::send_group_message_to_groups,
+    response::{ApiResponse, Reply},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+
+const SOLAR_XML_URL: &str = "https://www.hamqsl.com/solarxml.php";
+pub const SPACE_WEATHER_LATEST: &str = "runtime_data/space_weather_latest.json";
+/// One sample per update, used for trend charts
+pub const SPACE_WEATHER_HISTORY: &str = "runtime_data/space_weather_history.json";
+const HISTORY_RETENTION_DAYS: i64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BandCondition {
+    /// e.g. `80m-40m`
+    pub band: String,
+    /// `day` or `night`
+    pub time: String,
+    /// `Good`, `Fair` or `Poor`
+    pub condition: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VhfCondition {
+    /// e.g. `E-Skip`
+    pub phenomenon: String,
+    /// e.g. `europe_6m`
+    pub location: String,
+    pub condition: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpaceWeather {
+    /// RFC3339, when the bot fetched it
+    pub fetched_at: String,
+    /// RFC3339, `updated` of the feed
+    #[serde(default)]
+    pub source_updated: Option<String>,
+    pub solar_flux: Option<f64>,
+    pub sunspots: Option<u32>,
+    pub a_index: Option<f64>,
+    pub k_index: Option<f64>,
+    /// e.g. `C1.2`
+    pub xray: Option<String>,
+    pub proton_flux: Option<f64>,
+    pub electron_flux: Option<f64>,
+    /// km/s
+    pub solar_wind: Option<f64>,
+    /// Bz, nT
+    pub magnetic_field: Option<f64>,
+    pub geomag_field: Option<String>,
+    pub signal_noise: Option<String>,
+    #[serde(default)]
+    pub band_conditions: Vec<BandCondition>,
+    #[serde(default)]
+    pub vhf_conditions: Vec<VhfCondition>,
+}
+
+/// The numbers of one update, kept in the history file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpaceWeatherSample {
+    pub time: String,
+    pub solar_flux: Option<f64>,
+    pub sunspots: Option<u32>,
+    pub a_index: Option<f64>,
+    pub k_index: Option<f64>,
+    pub xray: Option<String>,
+}
+
+impl SpaceWeather {
+    pub fn sample(&self) -> SpaceWeatherSample {
+        SpaceWeatherSample {
+            time: self.source_updated.clone().unwrap_or_else(|| self.fetched_at.clone()),
+            solar_flux: self.solar_flux,
+            sunspots: self.sunspots,
+            a_index: self.a_index,
+            k_index: self.k_index,
+            xray: self.xray.clone(),
+        }
+    }
+
+    pub fn xray_flux(&self) -> Option<f64> {
+        self.xray.as_deref().and_then(xray_flux)
+    }
+
+    /// HF bands once each in feed order, the feed lists every day entry before the night ones
+    pub fn hf_bands(&self) -> Vec<&str> {
+        let mut seen = std::collections::HashSet::new();
+        self.band_conditions
+            .iter()
+            .map(|b| b.band.as_str())
+            .filter(|band| seen.insert(*band))
+            .collect()
+    }
+}
+
+/// GOES class to peak flux in W/m², `M2.5` -> 2.5e-5, a bare `X` is X1
+pub fn xray_flux(class: &str) -> Option<f64> {
+    let class = class.trim().to_uppercase();
+    let mut chars = class.chars();
+    let base = match chars.next()? {
+        'A' => 1e-8,
+        'B' => 1e-7,
+        'C' => 1e-6,
+        'M' => 1e-5,
+        'X' => 1e-4,
+        _ => return None,
+    };
+    let magnitude = match chars.as_str() {
+        "" => 1.0,
+        m => m.parse::<f64>().ok()?,
+    };
+    Some(base * magnitude)
+}
+
+/// hamqsl pads values and writes `No Report` when a value is missing
+fn parse_number<T: std::str::FromStr>(value: Option<&str>) -> Option<T> {
+    value.and_then(|v| v.trim().parse::<T>().ok())
+}
+
+fn parse_text(value: Option<&str>) -> Option<String> {
+    value
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty() && !v.eq_ignore_ascii_case("No Report"))
+        .map(|v| v.to_string())
+}
+
+/// `18 Oct 2026 1203 GMT`
+fn parse_updated(value: &str) -> Option<String> {
+    let value = value.trim().trim_end_matches("GMT").trim();
+    NaiveDateTime::parse_from_str(value, "%d %b %Y %H%M")
+        .ok()
+        .map(|t| t.and_utc().to_rfc3339())
+}
+
+/// `solarxml.php`, everything lives under `<solar><solardata>`
+pub fn parse_solar_xml(
+    content: &str,
+    now: DateTime<Utc>,
+) -> anyhow::Result<SpaceWeather> {
+    // the feed comes with blank lines before the declaration
+    let doc = roxmltree::Document::parse(content.trim_start_matches('\u{feff}').trim())
+        .map_err(|e| anyhow::anyhow!("Failed to parse solar XML: {}", e))?;
+    let data = doc
+        .descendants()
+        .find(|n| n.has_tag_name("solardata"))
+        .ok_or_else(|| anyhow::anyhow!("No solardata element in solar XML"))?;
+    let field = |name: &str| {
+        data.children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+    };
+
+    let band_conditions = data
+        .descendants()
+        .filter(|n| n.has_tag_name("band"))
+        .filter_map(|n| Some(BandCondition {
+            band: n.attribute("name")?.to_string(),
+            time: n.attribute("time")?.to_string(),
+            condition: n.text()?.trim().to_string(),
+        }))
+        .collect();
+    let vhf_conditions = data
+        .descendants()
+        .filter(|n| n.has_tag_name("phenomenon"))
+        .filter_map(|n| Some(VhfCondition {
+            phenomenon: n.attribute("name")?.to_string(),
+            location: n.attribute("location")?.to_string(),
+            condition: n.text()?.trim().to_string(),
+        }))
+        .collect();
+
+    Ok(SpaceWeather {
+        fetched_at: now.to_rfc3339(),
+        source_updated: field("updated").and_then(parse_updated),
+        solar_flux: parse_number(field("solarflux")),
+        sunspots: parse_number(field("sunspots")),
+        a_index: parse_number(field("aindex")),
+        k_index: parse_number(field("kindex")),
+        xray: parse_text(field("xray")),
+        proton_flux: parse_number(field("protonflux")),
+        // sic, the feed spells it this way
+        electron_flux: parse_number(field("electonflux").or_else(|| field("electronflux"))),
+        solar_wind: parse_number(field("solarwind")),
+        magnetic_field: parse_number(field("magneticfield")),
+        geomag_field: parse_text(field("geomagfield")),
+        signal_noise: parse_text(field("signalnoise")),
+        band_conditions,
+        vhf_conditions,
+    })
+}
+
+pub async fn fetch_space_weather() -> anyhow::Result<SpaceWeather> {
+    let client = reqwest::Client::new();
+    let content = client.get(SOLAR_XML_URL)
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to download solar XML: {}", e))?
+        .error_for_status()?
+        .text()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read solar XML: {}", e))?;
+    parse_solar_xml(&content, Utc::now())
+}
+
+/// Last stored reading, None before the first update
+pub async fn read_space_weather(
+    app_status: &AppStatus,
+) -> anyhow::Result<Option<SpaceWeather>> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), SPACE_WEATHER_LATEST.to_string()).await {
+        return Ok(None);
+    }
+    match load_file(tx_filerequest, SPACE_WEATHER_LATEST.to_string(), FileFormat::Json).await? {
+        FileData::Json(data) => serde_json::from_value(data)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Failed to parse space weather: {}", e)),
+        _ => Err(anyhow::anyhow!("Unexpected file format received")),
+    }
+}
+
+pub async fn read_space_weather_history(
+    app_status: &AppStatus,
+) -> anyhow::Result<Vec<SpaceWeatherSample>> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), SPACE_WEATHER_HISTORY.to_string()).await {
+        return Ok(Vec::new());
+    }
+    match load_file(tx_filerequest, SPACE_WEATHER_HISTORY.to_string(), FileFormat::Json).await? {
+        FileData::Json(data) => serde_json::from_value(data)
+            .map_err(|e| anyhow::anyhow!("Failed to parse space weather history: {}", e)),
+        _ => Err(anyhow::anyhow!("Unexpected file format received")),
+    }
+}
+
+/// Appends unless the feed has not changed since the last sample
+async fn append_history(
+    app_status: &AppStatus,
+    weather: &SpaceWeather,
+) -> anyhow::Result<()> {
+    let mut history = read_space_weather_history(app_status).await?;
+    let sample = weather.sample();
+    if history.last().is_some_and(|last| last.time == sample.time) {
+        return Ok(());
+    }
+    history.push(sample);
+
+    let cutoff = Utc::now() - chrono::Duration::days(HISTORY_RETENTION_DAYS);
+    history.retain(|s| {
+        DateTime::parse_from_rfc3339(&s.time).is_ok_and(|t| t.with_timezone(&Utc) >= cutoff)
+    });
+    write_file(
+        app_status.file_tx.clone(),
+        SPACE_WEATHER_HISTORY.to_string(),
+        &FileData::Json(serde_json::to_value(&history)?),
+    ).await
+}
+
+/// Alerts for thresholds crossed since the previous reading, so each storm is announced once
+fn threshold_alerts(
+    previous: Option<&SpaceWeather>,
+    current: &SpaceWeather,
+    k_threshold: f64,
+    xray_threshold: &str,
+) -> Vec<String> {
+    let mut alerts = Vec::new();
+
+    let crossed = |prev: Option<f64>, cur: Option<f64>, threshold: f64| {
+        cur.is_some_and(|cur| cur >= threshold) && !prev.is_some_and(|prev| prev >= threshold)
+    };
+    if crossed(previous.and_then(|p| p.k_index), current.k_index, k_threshold) {
+        alerts.push(format!(
+            "地磁暴警报: K 指数 {}，A 指数 {}，高纬度通联可能受影响",
+            current.k_index.unwrap_or_default(),
+            current.a_index.map(|a| a.to_string()).unwrap_or_else(|| "-".to_string())
+        ));
+    }
+    if let Some(threshold) = xray_flux(xray_threshold)
+        && crossed(previous.and_then(|p| p.xray_flux()), current.xray_flux(), threshold)
+    {
+        alerts.push(format!(
+            "太阳耀斑警报: X 射线 {}，日照面短波可能中断",
+            current.xray.as_deref().unwrap_or_default()
+        ));
+    }
+    alerts
+}
+
+/// Fetch, store and push alerts, run by the solar image task
+pub async fn update_space_weather(
+    app_status: &Arc<AppStatus>,
+) -> anyhow::Result<SpaceWeather> {
+    let weather = fetch_space_weather().await?;
+    let previous = match read_space_weather(app_status).await {
+        Ok(previous) => previous,
+        Err(e) => {
+            tracing::warn!("Failed to read previous space weather: {}", e);
+            None
+        }
+    };
+
+    write_file(
+        app_status.file_tx.clone(),
+        SPACE_WEATHER_LATEST.to_string(),
+        &FileData::Json(serde_json::to_value(&weather)?),
+    ).await?;
+    if let Err(e) = append_history(app_status, &weather).await {
+        tracing::error!("Failed to append space weather history: {}", e);
+    }
+
+    let alert_config = {
+        let config_guard = app_status.config.read().await;
+        config_guard.space_weather_alert_config.clone()
+    };
+    if let Some(alert_config) = alert_config {
+        let alerts = threshold_alerts(
+            previous.as_ref(),
+            &weather,
+            alert_config.k_index,
+            &alert_config.xray_class,
+        );
+        for alert in alerts {
+            tracing::info!("Space weather alert: {}", alert);
+            send_group_message_to_groups(Reply::text(alert), &alert_config.group_id, app_status).await;
+        }
+    }
+    Ok(weather)
+}
+
+fn value_text<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+/// `/sun` text
+pub fn space_weather_summary(weather: &SpaceWeather) -> String {
+    let bjt = FixedOffset::east_opt(8 * 3600).unwrap();
+    let updated = weather.source_updated.as_ref().unwrap_or(&weather.fetched_at);
+    let updated = DateTime::parse_from_rfc3339(updated)
+        .map(|t| t.with_timezone(&bjt).format("%m-%d %H:%M BJT").to_string())
+        .unwrap_or_else(|_| updated.clone());
+
+    let mut lines = vec![
+        format!("空间天气 (更新于 {}):", updated),
+        format!(
+            "太阳通量 SFI {}，黑子数 SSN {}",
+            value_text(weather.solar_flux),
+            value_text(weather.sunspots)
+        ),
+        format!(
+            "A 指数 {}，K 指数 {}，地磁 {}",
+            value_text(weather.a_index),
+            value_text(weather.k_index),
+            value_text(weather.geomag_field.as_deref())
+        ),
+        format!(
+            "X 射线 {}，太阳风 {} km/s，Bz {} nT",
+            value_text(weather.xray.as_deref()),
+            value_text(weather.solar_wind),
+            value_text(weather.magnetic_field)
+        ),
+    ];
+
+    let bands = weather.hf_bands();
+    if !bands.is_empty() {
+        lines.push("短波 (白天/夜间):".to_string());
+    }
+    for band in bands {
+        let condition = |time: &str| {
+            weather.band_conditions
+                .iter()
+                .find(|b| b.band == band && b.time == time)
+                .map(|b| b.condition.as_str())
+                .unwrap_or("-")
+        };
+        lines.push(format!("{} {}/{}", band, condition("day"), condition("night")));
+    }
+
+    let open: Vec<String> = weather.vhf_conditions
+        .iter()
+        .filter(|v| !v.condition.eq_ignore_ascii_case("Band Closed"))
+        .map(|v| format!("{} {} {}", v.phenomenon, v.location, v.condition))
+        .collect();
+    if !open.is_empty() {
+        lines.push(format!("VHF: {}", open.join("，")));
+    }
+    lines.join("\n")
+}
+
+pub async fn space_weather_command(
+    app_status: &Arc<AppStatus>,
+) -> Reply {
+    match read_space_weather(app_status).await {
+        Ok(Some(weather)) => Reply::text(space_weather_summary(&weather)),
+        Ok(None) => ApiResponse::error("还没有获取到空间天气数据喵，请稍后再试".to_string()),
+        Err(e) => {
+            tracing::error!("Failed to read space weather: {}", e);
+            ApiResponse::error(format!("文件读取失败: {}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// solarxml.php as served, with the leading blank lines and padded values
+    const SAMPLE: &str = r#"
+
+<?xml version="1.0" encoding="ISO-8859-1"?>
+<solar>
+	<solardata>
+		<source url="http://www.hamqsl.com/solar.html">N0NBH</source>
+		<updated> 18 Oct 2026 1203 GMT</updated>
+		<solarflux>142</solarflux>
+		<aindex> 8</aindex>
+		<kindex> 2</kindex>
+		<kindexnt>No Report</kindexnt>
+		<xray>C1.2</xray>
+		<sunspots>105</sunspots>
+		<heliumline>133.5</heliumline>
+		<protonflux>No Report</protonflux>
+		<electonflux>1460</electonflux>
+		<aurora> 2</aurora>
+		<normalization>1.99</normalization>
+		<latdegree>67.5</latdegree>
+		<solarwind>No Report</solarwind>
+		<magneticfield> -2.1</magneticfield>
+		<calculatedconditions>
+			<band name="80m-40m" time="day">Fair</band>
+			<band name="30m-20m" time="day">Good</band>
+			<band name="17m-15m" time="day">Good</band>
+			<band name="12m-10m" time="day">Fair</band>
+			<band name="80m-40m" time="night">Good</band>
+			<band name="30m-20m" time="night">Good</band>
+			<band name="17m-15m" time="night">Fair</band>
+			<band name="12m-10m" time="night">Poor</band>
+		</calculatedconditions>
+		<calculatedvhfconditions>
+			<phenomenon name="vhf-aurora" location="northern_hemi">Band Closed</phenomenon>
+			<phenomenon name="E-Skip" location="europe_6m">50MHz ES</phenomenon>
+		</calculatedvhfconditions>
+		<geomagfield>QUIET</geomagfield>
+		<signalnoise>S0-S1</signalnoise>
+		<fof2>No Report</fof2>
+		<muffactor>No Report</muffactor>
+		<muf>No Report</muf>
+	</solardata>
+</solar>
+"#;
+
+    fn sample() -> SpaceWeather {
+        parse_solar_xml(SAMPLE, Utc::now()).unwrap()
+    }
+
+    #[test]
+    fn parses_the_feed() {
+        let weather = sample();
+        assert_eq!(weather.source_updated.as_deref(), Some("2026-10-18T12:03:00+00:00"));
+        assert_eq!(weather.solar_flux, Some(142.0));
+        assert_eq!(weather.sunspots, Some(105));
+        assert_eq!(weather.a_index, Some(8.0));
+        assert_eq!(weather.k_index, Some(2.0));
+        assert_eq!(weather.xray.as_deref(), Some("C1.2"));
+        assert_eq!(weather.magnetic_field, Some(-2.1));
+        assert_eq!(weather.geomag_field.as_deref(), Some("QUIET"));
+        assert_eq!(weather.signal_noise.as_deref(), Some("S0-S1"));
+        assert_eq!(weather.hf_bands(), vec!["80m-40m", "30m-20m", "17m-15m", "12m-10m"]);
+        assert_eq!(weather.band_conditions.len(), 8);
+        assert_eq!(weather.vhf_conditions[1].location, "europe_6m");
+    }
+
+    #[test]
+    fn no_report_is_missing_and_electonflux_is_read() {
+        let weather = sample();
+        assert_eq!(weather.proton_flux, None);
+        assert_eq!(weather.solar_wind, None);
+        assert_eq!(weather.electron_flux, Some(1460.0));
+
+        let fixed = SAMPLE.replace("electonflux", "electronflux");
+        assert_eq!(parse_solar_xml(&fixed, Utc::now()).unwrap().electron_flux, Some(1460.0));
+        let missing = SAMPLE.replace("<xray>C1.2</xray>", "<xray>No Report</xray>");
+        assert_eq!(parse_solar_xml(&missing, Utc::now()).unwrap().xray, None);
+    }
+
+    #[test]
+    fn converts_xray_classes() {
+        assert_eq!(xray_flux("M2.5"), Some(2.5e-5));
+        assert_eq!(xray_flux("x"), Some(1e-4));
+        assert_eq!(xray_flux("C1.2"), Some(1.2e-6));
+        assert_eq!(xray_flux("No Report"), None);
+        assert_eq!(xray_flux(""), None);
+    }
+
+    #[test]
+    fn alerts_only_when_a_threshold_is_newly_crossed() {
+        let quiet = sample();
+        let mut storm = sample();
+        storm.k_index = Some(6.0);
+        storm.xray = Some("M2.5".to_string());
+
+        // first reading and rising past both thresholds
+        assert_eq!(threshold_alerts(None, &storm, 5.0, "M1").len(), 2);
+        assert_eq!(threshold_alerts(Some(&quiet), &storm, 5.0, "M1").len(), 2);
+        // still above, already announced
+        assert!(threshold_alerts(Some(&storm), &storm, 5.0, "M1").is_empty());
+        // below the thresholds
+        assert!(threshold_alerts(Some(&storm), &quiet, 5.0, "M1").is_empty());
+
+        // a missing previous value counts as below
+        let mut unknown = sample();
+        unknown.k_index = None;
+        let alerts = threshold_alerts(Some(&unknown), &storm, 5.0, "X");
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("K 指数 6"));
+    }
+}
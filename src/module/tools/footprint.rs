@@ -0,0 +1,92 @@
+use crate::{
+    app_status::AppStatus,
+    module::{
+        amsat::{official_report::load_satellites_list, prelude::search_satellites},
+        pass_query::satellites::{load_satellite_list, sat_name_normalize},
+        prelude::EARTH_RADIUS_KM,
+    },
+};
+
+/// Sub-satellite point and the radius of the area that can see the satellite
+#[derive(Debug, Clone)]
+pub struct SatFootprint {
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// km
+    pub altitude: f64,
+    /// km along the ground, horizon at 0 deg elevation
+    pub radius: f64,
+}
+
+/// Ground radius of the footprint for a satellite at `altitude` km
+pub fn footprint_radius_km(altitude: f64) -> f64 {
+    EARTH_RADIUS_KM * (EARTH_RADIUS_KM / (EARTH_RADIUS_KM + altitude)).acos()
+}
+
+/// Accepts a NORAD id, or a name/alias known to the pass predictor or the AMSAT satellite list
+async fn resolve_norad_id(
+    app_status: &AppStatus,
+    satellite: &str,
+) -> anyhow::Result<(String, u32)> {
+    if let Ok(id) = satellite.parse::<u32>() {
+        return Ok((satellite.to_string(), id));
+    }
+
+    let mut wanted = vec![sat_name_normalize(satellite)];
+    // the AMSAT official name of a fuzzy match, e.g. `so50` -> `SO-50`
+    let satellite_list = load_satellites_list(app_status.file_tx.clone()).await?;
+    if let Some(name) = search_satellites(satellite, &satellite_list, 0.95).first() {
+        wanted.push(sat_name_normalize(name));
+    }
+
+    let satellites = load_satellite_list(app_status).await?;
+    let Some((name, info)) = satellites.iter().find(|(name, info)| {
+        std::iter::once(name.as_str())
+            .chain(info.aliases.iter().flatten().map(String::as_str))
+            .any(|n| wanted.contains(&sat_name_normalize(n)))
+    }) else {
+        return Err(anyhow::anyhow!("过境预测的卫星列表中没有 {}，可以直接使用 NORAD 编号查询", satellite));
+    };
+    info.id
+        .map(|id| (name.clone(), id))
+        .ok_or_else(|| anyhow::anyhow!("{} 没有 NORAD 编号，可以直接使用编号查询", name))
+}
+
+/// Current position from `pass_api_config.positions_url`, the response follows n2yo's `positions`
+pub async fn fetch_sat_footprint(
+    app_status: &AppStatus,
+    satellite: &str,
+) -> anyhow::Result<SatFootprint> {
+    let conf = app_status.config.read().await.pass_api_config.clone();
+    let Some(template) = conf.positions_url.as_deref().filter(|t| !t.is_empty()) else {
+        return Err(anyhow::anyhow!("没有配置卫星位置接口 pass_api_config.positions_url"));
+    };
+    let (name, norad_id) = resolve_norad_id(app_status, satellite).await?;
+
+    let url = template
+        .replace("{id}", &norad_id.to_string())
+        .replace("{lat}", &conf.lat.to_string())
+        .replace("{lon}", &conf.lon.to_string())
+        .replace("{alt}", &conf.alt.to_string())
+        .replace("{apikey}", &conf.api_key);
+    let client = reqwest::Client::new();
+    let json: serde_json::Value = client.get(&url).send().await?.error_for_status()?.json().await?;
+
+    let position = &json["positions"][0];
+    let (Some(lat), Some(lon), Some(altitude)) = (
+        position["satlatitude"].as_f64(),
+        position["satlongitude"].as_f64(),
+        position["sataltitude"].as_f64(),
+    ) else {
+        return Err(anyhow::anyhow!("无法解析 {} 的位置数据", name));
+    };
+
+    Ok(SatFootprint {
+        name: json["info"]["satname"].as_str().map(|s| s.to_string()).unwrap_or(name),
+        lat,
+        lon,
+        altitude,
+        radius: footprint_radius_km(altitude),
+    })
+}
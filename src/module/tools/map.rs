@@ -0,0 +1,166 @@
+use std::fs;
+use crate::module::prelude::GridBounds;
+
+/// Coarse outline bundled with the bot, any GeoJSON FeatureCollection in lon/lat works
+/// e.g. Natural Earth admin-1 for province borders
+pub const BASEMAP_PATH: &str = "resources/basemap.geojson";
+
+/// Default view, mainland China and neighbours, snapped to grid squares
+pub const DEFAULT_MAP_BOUNDS: GridBounds = GridBounds {
+    lon_min: 72.0,
+    lon_max: 136.0,
+    lat_min: 16.0,
+    lat_max: 54.0,
+};
+
+#[derive(Debug, Clone)]
+pub struct BasemapPath {
+    /// (lon, lat)
+    pub points: Vec<(f64, f64)>,
+    /// polygon rings are filled, line strings are only stroked
+    pub closed: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Basemap {
+    pub paths: Vec<BasemapPath>,
+}
+
+impl Basemap {
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        let mut basemap = Basemap::default();
+
+        let features = match value["type"].as_str() {
+            Some("FeatureCollection") => value["features"].as_array().cloned().unwrap_or_default(),
+            Some("Feature") => vec![value],
+            _ => return Err(anyhow::anyhow!("Basemap must be a GeoJSON Feature or FeatureCollection")),
+        };
+
+        for feature in &features {
+            basemap.add_geometry(&feature["geometry"]);
+        }
+
+        Ok(basemap)
+    }
+
+    pub fn load(path: &str) -> Self {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::warn!("Failed to read basemap {}: {}", path, e);
+                return Basemap::default();
+            }
+        };
+
+        match Basemap::parse(&content) {
+            Ok(basemap) => basemap,
+            Err(e) => {
+                tracing::error!("Failed to parse basemap {}: {}", path, e);
+                Basemap::default()
+            }
+        }
+    }
+
+    fn add_geometry(&mut self, geometry: &serde_json::Value) {
+        let coordinates = &geometry["coordinates"];
+        match geometry["type"].as_str() {
+            Some("LineString") => self.add_ring(coordinates, false),
+            Some("MultiLineString") | Some("Polygon") => {
+                let closed = geometry["type"] == "Polygon";
+                for ring in coordinates.as_array().into_iter().flatten() {
+                    self.add_ring(ring, closed);
+                }
+            }
+            Some("MultiPolygon") => {
+                for polygon in coordinates.as_array().into_iter().flatten() {
+                    for ring in polygon.as_array().into_iter().flatten() {
+                        self.add_ring(ring, true);
+                    }
+                }
+            }
+            Some("GeometryCollection") => {
+                for geometry in geometry["geometries"].as_array().into_iter().flatten() {
+                    self.add_geometry(geometry);
+                }
+            }
+            other => tracing::debug!("Skipping basemap geometry {:?}", other),
+        }
+    }
+
+    fn add_ring(&mut self, ring: &serde_json::Value, closed: bool) {
+        let points: Vec<(f64, f64)> = ring
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|point| Some((point[0].as_f64()?, point[1].as_f64()?)))
+            .collect();
+        if points.len() >= 2 {
+            self.paths.push(BasemapPath { points, closed });
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref BASEMAP: Basemap = Basemap::load(BASEMAP_PATH);
+}
+
+/// Equirectangular projection, longitude scaled by cos(mid latitude)
+#[derive(Debug, Clone)]
+pub struct MapProjection {
+    pub bounds: GridBounds,
+    pub width: f64,
+    pub height: f64,
+    lon_scale: f64,
+    lat_scale: f64,
+}
+
+impl MapProjection {
+    pub fn new(bounds: GridBounds, width: f64) -> Self {
+        let mid_lat = ((bounds.lat_min + bounds.lat_max) / 2.0).to_radians();
+        let lon_scale = width / (bounds.lon_max - bounds.lon_min);
+        let lat_scale = lon_scale / mid_lat.cos().max(0.2);
+        MapProjection {
+            bounds,
+            width,
+            height: (bounds.lat_max - bounds.lat_min) * lat_scale,
+            lon_scale,
+            lat_scale,
+        }
+    }
+
+    /// (lon, lat) to SVG (x, y)
+    pub fn project(&self, lon: f64, lat: f64) -> (f64, f64) {
+        (
+            (lon - self.bounds.lon_min) * self.lon_scale,
+            (self.bounds.lat_max - lat) * self.lat_scale,
+        )
+    }
+
+    /// SVG path data, `None` if fewer than two points
+    pub fn path_data(&self, path: &BasemapPath) -> Option<String> {
+        let mut points = path.points.iter().map(|(lon, lat)| self.project(*lon, *lat));
+        let (x, y) = points.next()?;
+        let mut data = format!("M{:.1} {:.1}", x, y);
+        for (x, y) in points {
+            data.push_str(&format!(" L{:.1} {:.1}", x, y));
+        }
+        if path.closed {
+            data.push_str(" Z");
+        }
+        Some(data)
+    }
+}
+
+/// Expand the default view to include every grid, snapped to 2x1 deg squares
+pub fn map_bounds_for(grids: &[GridBounds]) -> GridBounds {
+    let bounds = grids
+        .iter()
+        .fold(DEFAULT_MAP_BOUNDS, |acc, grid| acc.union(grid));
+    GridBounds {
+        lon_min: ((bounds.lon_min / 2.0).floor() * 2.0).max(-180.0),
+        lon_max: ((bounds.lon_max / 2.0).ceil() * 2.0).min(180.0),
+        lat_min: bounds.lat_min.floor().max(-90.0),
+        lat_max: bounds.lat_max.ceil().min(90.0),
+    }
+}
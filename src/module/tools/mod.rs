@@ -1,2 +1,6 @@
+pub mod footprint;
+pub mod map;
 pub mod roaming;
+pub mod roaming_feed;
+pub mod roaming_io;
 pub mod render;
\ No newline at end of file
@@ -9,13 +9,29 @@ use crate::{
         amsat::prelude::{
             ReportStatus, SatelliteFileFormat
         },
-        tools::roaming::{UserRoamingData}
-    }, msg::prelude::MessageEvent, response::ApiResponse
+        amsat::report_stats::{CallsignStats, LeaderboardEntry},
+        callsign::parser::Callsign,
+        prelude::{maidenhead_bounds, GridBounds},
+        solar_image::space_weather::{SpaceWeather, SpaceWeatherSample},
+        tools::{
+            map::{map_bounds_for, MapProjection, BASEMAP},
+            roaming::{RoamingState, UserRoamingData},
+        }
+    }, msg::prelude::MessageEvent, response::{ApiResponse, Reply}
 };
 
 const SVG_ROAMING_TEMPLATE_PATH: &str = "resources/svg_roaming_template.svg";
 const SVG_SATSTATUS_TEMPLATE_PATH: &str = "resources/svg_satstatus_template.svg";
+const SVG_STATS_TEMPLATE_PATH: &str = "resources/svg_stats_template.svg";
+const SVG_ROAMING_MAP_TEMPLATE_PATH: &str = "resources/svg_roaming_map_template.svg";
+pub const ROAMING_MAP_PIC_PATH: &str = "runtime_data/pic/roaming_map.png";
+pub const ROAMING_LIST_PIC_PATH: &str = "runtime_data/pic/roaming_list.png";
 pub const SATSTATUS_PIC_PATH_PREFIX: &str = "runtime_data/pic/satstatus_pics/";
+pub const STATS_PIC_PATH_PREFIX: &str = "runtime_data/pic/stats_pics/";
+/// Regenerated by the solar image task, served by `/sun img`
+pub const SPACE_WEATHER_PIC_PATH: &str = "runtime_data/pic/space_weather.png";
+const FOOTER_HEIGHT: f32 = 32.0;
+const FOOTER_COLOR: &str = "#f0f2f5";
 
 pub async fn render_roaming_data(
     roaming_data: &Vec<UserRoamingData>,
@@ -33,11 +49,11 @@ pub async fn render_roaming_data(
     const COLOR_BLOCK_WIDTH: f32 = 12.0;
     const COLOR_BLOCK_HEIGHT: f32 = 18.0;
     const COLOR_BLOCK_TEXT_SPACING: f32 = 8.0;
-    const FOOTER_HEIGHT: f32 = 32.0;
+    const SECTION_HEIGHT: f32 = 30.0;
 
     const EVEN_ROW_COLOR: &str = "#f6f8fa";
     const ODD_ROW_COLOR: &str = "#ffffff";
-    const FOOTER_COLOR: &str = "#f0f2f5";
+    const SECTION_COLOR: &str = "#ddf4ff";
 
     let template_content = match read_svg_template_file(SVG_ROAMING_TEMPLATE_PATH).await {
         Ok(content) => content,
@@ -49,100 +65,144 @@ pub async fn render_roaming_data(
 
     let mut rows_svg = String::new();
     let mut total_height = HEADER_HEIGHT;
-    let now_time = Utc::now().to_rfc3339();
+    let now = Utc::now();
+    let now_time = now.to_rfc3339();
 
-    for (index, item) in roaming_data.iter().enumerate() {
-        // 处理备注文本换行
-        let (remark_lines, line_count) = if let Some(remark) = &item.roaming_data.remark {
-            wrap_text(remark, MAX_REMARK_WIDTH, MAX_LINES)
-        } else {
-            (vec!["".to_string()], 1)
-        };
-        
-        // 计算当前行高度（基础高度 + 额外行高）
-        let row_height = BASE_ROW_HEIGHT + (line_count.saturating_sub(1) as f32 * LINE_HEIGHT);
-        let y_pos = total_height - HEADER_HEIGHT; // 相对于rows-container的y位置
-        
-        // 计算文本垂直位置（相对于每行的位置）
-        let text_y = y_pos + (BASE_ROW_HEIGHT / 2.0);
-
-        // 计算行的背景颜色
-        let row_color = if index % 2 == 0 { EVEN_ROW_COLOR } else { ODD_ROW_COLOR };
-
-        // 计算更新时间的矩形位置和大小
-        let rect_y = y_pos + (BASE_ROW_HEIGHT - COLOR_BLOCK_HEIGHT) / 2.0;
-        let rect_w = COLOR_BLOCK_WIDTH;
-        let rect_h = COLOR_BLOCK_HEIGHT;
-        // submit_time format: YYYY-MM-DD HH:MM:SS BJT
-        // should convert to ISO 8601 UTC first
-        let submit_time_utc = match convert_bjt_to_utc_iso8601(&item.submit_time) {
-            Ok(utc_string) => utc_string,
-            Err(e) => {
-                tracing::error!("Failed to parse submit_time: {}", e);
-                continue;
-            }
-        };
-        let color = match map_time_to_color(&submit_time_utc, &now_time, 0.0, 168.0) {
-            Ok(color) => color,
-            Err(e) => {
-                tracing::error!("Failed to map time to color: {}", e);
-                continue;
-            }
-        };
+    for (title, state) in [("正在漫游", RoamingState::Active), ("即将开始", RoamingState::Upcoming)] {
+        let items: Vec<&UserRoamingData> = roaming_data.iter().filter(|r| r.state(now) == state).collect();
+        if items.is_empty() {
+            continue;
+        }
 
-        // 生成行SVG
+        // 分组标题
+        let section_y = total_height - HEADER_HEIGHT;
         writeln!(
             rows_svg,
-            r##"      <g class="data-row">
-        <rect x="0" y="{y_pos}" width="100%" height="{row_height}" fill="{row_color}" />
-        <text x="{x_callsign}" y="{text_y}" class="table-text row-text monospace">{callsign}</text>
-        <text x="{x_grids}" y="{text_y}" class="table-text row-text monospace">{grids}</text>
-        <rect x="{x_update_time_rect}" y="{rect_y}" width="{rect_w}" height="{rect_h}" fill="{color}" rx="1" />
-        <text x="{x_update_time}" y="{text_y}" class="table-text row-text monospace">{update_time}</text>
-        <text x="{x_user_id}" y="{text_y}" class="table-text row-text monospace">{user_id}</text>
-        <g transform="translate({x_remark}, 0)">
-          {remark_lines}
-        </g>
+            r##"      <g class="section-row">
+        <rect x="0" y="{section_y}" width="100%" height="{SECTION_HEIGHT}" fill="{SECTION_COLOR}" />
+        <text x="{x}" y="{text_y}" class="table-text header-text">{title} ({count})</text>
       </g>"##,
-            y_pos = y_pos,
-            row_height = row_height,
-            text_y = text_y,
-            x_callsign = X_PADDING_CALLSIGN,
-            callsign = &item.roaming_data.callsign,
-            x_grids = X_PADDING_GRIDS,
-            grids = &item.roaming_data.grid,
-            x_remark = X_PADDING_REMARK,
-            x_update_time_rect = X_PADDING_UPDATE_TIME,
-            x_update_time = X_PADDING_UPDATE_TIME + COLOR_BLOCK_WIDTH + COLOR_BLOCK_TEXT_SPACING,
-            update_time = &item.submit_time,
-            x_user_id = X_PADDING_USER_ID,
-            user_id = &item.user_id,
-            remark_lines = remark_lines.iter().enumerate().map(|(i, line)| {
-                let line_y = y_pos + (BASE_ROW_HEIGHT / 2.0) + (i as f32 * LINE_HEIGHT);
-                format!(r#"<text x="0" y="{line_y}" class="table-text row-text">{line}</text>"#)
-            }).collect::<Vec<_>>().join("\n          ")
+            x = X_PADDING_CALLSIGN,
+            text_y = section_y + SECTION_HEIGHT / 2.0,
+            count = items.len(),
         )?;
+        total_height += SECTION_HEIGHT;
+
+        for (index, item) in items.iter().enumerate() {
+            // 处理备注文本换行
+            let (remark_lines, line_count) = if let Some(remark) = &item.roaming_data.remark {
+                wrap_text(remark, MAX_REMARK_WIDTH, MAX_LINES)
+            } else {
+                (vec!["".to_string()], 1)
+            };
+        
+            // 计算当前行高度（基础高度 + 额外行高）
+            let row_height = BASE_ROW_HEIGHT + (line_count.saturating_sub(1) as f32 * LINE_HEIGHT);
+            let y_pos = total_height - HEADER_HEIGHT; // 相对于rows-container的y位置
+        
+            // 计算文本垂直位置（相对于每行的位置）
+            let text_y = y_pos + (BASE_ROW_HEIGHT / 2.0);
+
+            // 计算行的背景颜色
+            let row_color = if index % 2 == 0 { EVEN_ROW_COLOR } else { ODD_ROW_COLOR };
+
+            // 计算更新时间的矩形位置和大小
+            let rect_y = y_pos + (BASE_ROW_HEIGHT - COLOR_BLOCK_HEIGHT) / 2.0;
+            let rect_w = COLOR_BLOCK_WIDTH;
+            let rect_h = COLOR_BLOCK_HEIGHT;
+            // submit_time format: YYYY-MM-DD HH:MM:SS BJT
+            // should convert to ISO 8601 UTC first
+            let submit_time_utc = match convert_bjt_to_utc_iso8601(&item.submit_time) {
+                Ok(utc_string) => utc_string,
+                Err(e) => {
+                    tracing::error!("Failed to parse submit_time: {}", e);
+                    continue;
+                }
+            };
+            let color = match map_time_to_color(&submit_time_utc, &now_time, 0.0, 168.0) {
+                Ok(color) => color,
+                Err(e) => {
+                    tracing::error!("Failed to map time to color: {}", e);
+                    continue;
+                }
+            };
+
+            // 呼号下方标注所属地区
+            let callsign_svg = match Callsign::parse(&item.roaming_data.callsign).and_then(|c| c.lookup()) {
+                Some(lookup) => format!(
+                    r##"<text x="{x}" y="{callsign_y}" class="table-text row-text monospace">{callsign}</text>
+            <text x="{x}" y="{entity_y}" class="table-text" font-size="11" fill="#6e7781">{entity}</text>"##,
+                    x = X_PADDING_CALLSIGN,
+                    callsign_y = text_y - 6.0,
+                    entity_y = text_y + 10.0,
+                    callsign = &item.roaming_data.callsign,
+                    entity = xml_escape(&lookup.entity.name),
+                ),
+                None => format!(
+                    r#"<text x="{x}" y="{text_y}" class="table-text row-text monospace">{callsign}</text>"#,
+                    x = X_PADDING_CALLSIGN,
+                    callsign = &item.roaming_data.callsign,
+                ),
+            };
+
+            // 有起止时间时在提交时间下方标注
+            let x_update_time = X_PADDING_UPDATE_TIME + COLOR_BLOCK_WIDTH + COLOR_BLOCK_TEXT_SPACING;
+            let update_time_svg = match item.window_text() {
+                Some(window_text) => format!(
+                    r##"<text x="{x}" y="{submit_y}" class="table-text row-text monospace">{update_time}</text>
+        <text x="{x}" y="{window_y}" class="table-text" font-size="11" fill="#6e7781">{window_text}</text>"##,
+                    x = x_update_time,
+                    submit_y = text_y - 6.0,
+                    window_y = text_y + 10.0,
+                    update_time = &item.submit_time,
+                ),
+                None => format!(
+                    r#"<text x="{x}" y="{text_y}" class="table-text row-text monospace">{update_time}</text>"#,
+                    x = x_update_time,
+                    update_time = &item.submit_time,
+                ),
+            };
+
+            // 生成行SVG
+            writeln!(
+                rows_svg,
+                r##"      <g class="data-row">
+            <rect x="0" y="{y_pos}" width="100%" height="{row_height}" fill="{row_color}" />
+            {callsign_svg}
+            <text x="{x_grids}" y="{text_y}" class="table-text row-text monospace">{grids}</text>
+            <rect x="{x_update_time_rect}" y="{rect_y}" width="{rect_w}" height="{rect_h}" fill="{color}" rx="1" />
+            {update_time_svg}
+            <text x="{x_user_id}" y="{text_y}" class="table-text row-text monospace">{user_id}</text>
+            <g transform="translate({x_remark}, 0)">
+              {remark_lines}
+            </g>
+          </g>"##,
+                y_pos = y_pos,
+                row_height = row_height,
+                text_y = text_y,
+                callsign_svg = callsign_svg,
+                x_grids = X_PADDING_GRIDS,
+                grids = &item.roaming_data.grid,
+                x_remark = X_PADDING_REMARK,
+                x_update_time_rect = X_PADDING_UPDATE_TIME,
+                update_time_svg = update_time_svg,
+                x_user_id = X_PADDING_USER_ID,
+                user_id = &item.user_id,
+                remark_lines = remark_lines.iter().enumerate().map(|(i, line)| {
+                    let line_y = y_pos + (BASE_ROW_HEIGHT / 2.0) + (i as f32 * LINE_HEIGHT);
+                    let line = xml_escape(line);
+                    format!(r#"<text x="0" y="{line_y}" class="table-text row-text">{line}</text>"#)
+                }).collect::<Vec<_>>().join("\n          ")
+            )?;
         
-        total_height += row_height;
+            total_height += row_height;
+        }
     }
 
     let footer_y = total_height;
     total_height += FOOTER_HEIGHT;
 
-    let render_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let footer_svg = format!(
-        r##"    <g id="footer">
-        <rect x="0" y="{footer_y}" width="100%" height="{FOOTER_HEIGHT}" fill="{FOOTER_COLOR}" />
-        <text x="50%" y="{footer_text_y}" class="table-text footer-text" text-anchor="middle">
-            Rinko Bot v0.1.1, rendered at {time_str} BJT, 测试中
-        </text>
-        </g>"##,
-        footer_y = footer_y,
-        FOOTER_HEIGHT = FOOTER_HEIGHT,
-        FOOTER_COLOR = FOOTER_COLOR,
-        footer_text_y = footer_y + (FOOTER_HEIGHT / 2.0),
-        time_str = render_time
-    );
+    let footer_svg = footer_svg(footer_y, &default_footer_text());
 
     let final_svg = template_content
         .replace("{{SVG_HEIGHT}}", &total_height.to_string())
@@ -152,7 +212,7 @@ pub async fn render_roaming_data(
     // let output_path = "runtime_data/output.svg";
     // tokio::fs::write(output_path, final_svg).await?;
 
-    let png_output_path: &Path = Path::new("runtime_data/pic/roaming_list.png");
+    let png_output_path: &Path = Path::new(ROAMING_LIST_PIC_PATH);
 
     match render_svg_to_png(&final_svg, png_output_path).await {
         Ok(_) => {
@@ -166,7 +226,258 @@ pub async fn render_roaming_data(
     }
 }
 
-fn convert_bjt_to_utc_iso8601(bjt_string: &str) -> anyhow::Result<String> {
+/// Grid squares of active and upcoming roaming stations over the bundled basemap
+pub async fn render_roaming_map(
+    roaming_data: &[UserRoamingData],
+) -> anyhow::Result<()> {
+    const MAP_WIDTH: f64 = 1000.0;
+    const TITLE_HEIGHT: f64 = 44.0;
+    const LABEL_LINE_HEIGHT: f64 = 11.0;
+    const MAX_LABELS: usize = 3;
+
+    let template_content = match read_svg_template_file(SVG_ROAMING_MAP_TEMPLATE_PATH).await {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::error!("Failed to read SVG template file: {}", e);
+            return Err(anyhow::anyhow!("Failed to read SVG template file: {}", e));
+        }
+    };
+
+    // 6-char grids are too small at this scale, shade the 4-char square instead
+    let now = Utc::now();
+    let mut squares: Vec<(String, GridBounds, RoamingState, Vec<String>)> = Vec::new();
+    for item in roaming_data {
+        let state = item.state(now);
+        if state == RoamingState::Expired {
+            continue;
+        }
+        for grid in item.roaming_data.grid.split_whitespace() {
+            let square: String = grid.chars().take(4).collect::<String>().to_uppercase();
+            let Some(bounds) = maidenhead_bounds(&square) else {
+                continue;
+            };
+            match squares.iter_mut().find(|(s, _, _, _)| *s == square) {
+                Some((_, _, existing_state, callsigns)) => {
+                    if state == RoamingState::Active {
+                        *existing_state = RoamingState::Active;
+                    }
+                    if !callsigns.contains(&item.roaming_data.callsign) {
+                        callsigns.push(item.roaming_data.callsign.clone());
+                    }
+                }
+                None => squares.push((square, bounds, state, vec![item.roaming_data.callsign.clone()])),
+            }
+        }
+    }
+
+    let grid_bounds: Vec<GridBounds> = squares.iter().map(|(_, bounds, _, _)| *bounds).collect();
+    let projection = MapProjection::new(map_bounds_for(&grid_bounds), MAP_WIDTH);
+
+    let mut content_svg = String::new();
+    writeln!(
+        content_svg,
+        r##"<text x="20" y="{title_y}" class="table-text header-text">漫游地图 · 正在漫游 {active} 个网格，即将开始 {upcoming} 个网格</text>
+<g transform="translate(0, {TITLE_HEIGHT})">
+<rect x="0" y="0" width="{width}" height="{height}" class="sea" />"##,
+        title_y = TITLE_HEIGHT / 2.0,
+        active = squares.iter().filter(|(_, _, state, _)| *state == RoamingState::Active).count(),
+        upcoming = squares.iter().filter(|(_, _, state, _)| *state == RoamingState::Upcoming).count(),
+        width = projection.width,
+        height = projection.height,
+    )?;
+
+    write_map_base(&mut content_svg, &projection)?;
+
+    // claimed squares, labels drawn last so they stay on top
+    let mut labels_svg = String::new();
+    for (square, grid, state, callsigns) in &squares {
+        let (x1, y1) = projection.project(grid.lon_min, grid.lat_max);
+        let (x2, y2) = projection.project(grid.lon_max, grid.lat_min);
+        let class = if *state == RoamingState::Active { "claimed" } else { "upcoming" };
+        writeln!(
+            content_svg,
+            r#"<rect x="{x1:.1}" y="{y1:.1}" width="{w:.1}" height="{h:.1}" class="{class}"><title>{square}</title></rect>"#,
+            w = x2 - x1,
+            h = y2 - y1,
+        )?;
+
+        let mut lines: Vec<String> = callsigns.iter().take(MAX_LABELS).cloned().collect();
+        if callsigns.len() > MAX_LABELS {
+            lines.push(format!("+{}", callsigns.len() - MAX_LABELS));
+        }
+        let (center_lat, center_lon) = grid.center();
+        let (cx, cy) = projection.project(center_lon, center_lat);
+        let first_y = cy - (lines.len() as f64 - 1.0) * LABEL_LINE_HEIGHT / 2.0;
+        for (index, line) in lines.iter().enumerate() {
+            writeln!(
+                labels_svg,
+                r#"<text x="{cx:.1}" y="{y:.1}" class="callsign-label">{line}</text>"#,
+                y = first_y + index as f64 * LABEL_LINE_HEIGHT,
+            )?;
+        }
+    }
+    content_svg.push_str(&labels_svg);
+    content_svg.push_str("</g>\n");
+
+    let footer_y = (TITLE_HEIGHT + projection.height) as f32;
+    let footer_svg = footer_svg(footer_y, &default_footer_text());
+
+    let final_svg = template_content
+        .replace("{{SVG_WIDTH}}", &projection.width.to_string())
+        .replace("{{SVG_HEIGHT}}", &(footer_y + FOOTER_HEIGHT).to_string())
+        .replace("{{CONTENT}}", &content_svg)
+        .replace("{{FOOTER}}", &footer_svg);
+
+    let png_output_path: &Path = Path::new(ROAMING_MAP_PIC_PATH);
+
+    match render_svg_to_png(&final_svg, png_output_path).await {
+        Ok(_) => {
+            tracing::info!("Successfully rendered PNG to {:?}", png_output_path);
+            Ok(())
+        },
+        Err(e) => {
+            tracing::error!("Failed to render SVG to PNG: {}", e);
+            Err(anyhow::anyhow!("Failed to render SVG to PNG: {}", e))
+        }
+    }
+}
+
+/// Worked squares of one operator, confirmed ones darker, returns the PNG path
+/// - `squares`: (4-char square, confirmed)
+pub async fn render_worked_grids_map(
+    callsign: &str,
+    squares: &[(String, bool)],
+    payload: &MessageEvent,
+) -> anyhow::Result<String> {
+    const MAP_WIDTH: f64 = 1000.0;
+    const TITLE_HEIGHT: f64 = 44.0;
+
+    let template_content = match read_svg_template_file(SVG_ROAMING_MAP_TEMPLATE_PATH).await {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::error!("Failed to read SVG template file: {}", e);
+            return Err(anyhow::anyhow!("Failed to read SVG template file: {}", e));
+        }
+    };
+
+    let squares: Vec<(&String, GridBounds, bool)> = squares
+        .iter()
+        .filter_map(|(square, confirmed)| Some((square, maidenhead_bounds(square)?, *confirmed)))
+        .collect();
+    let grid_bounds: Vec<GridBounds> = squares.iter().map(|(_, bounds, _)| *bounds).collect();
+    let projection = MapProjection::new(map_bounds_for(&grid_bounds), MAP_WIDTH);
+
+    let mut content_svg = String::new();
+    writeln!(
+        content_svg,
+        r##"<text x="20" y="{title_y}" class="table-text header-text">{callsign} 卫星网格 · 已通联 {worked} 个，已确认 {confirmed} 个</text>
+<g transform="translate(0, {TITLE_HEIGHT})">
+<rect x="0" y="0" width="{width}" height="{height}" class="sea" />"##,
+        title_y = TITLE_HEIGHT / 2.0,
+        worked = squares.len(),
+        confirmed = squares.iter().filter(|(_, _, confirmed)| *confirmed).count(),
+        width = projection.width,
+        height = projection.height,
+    )?;
+    write_map_base(&mut content_svg, &projection)?;
+
+    for (square, grid, confirmed) in &squares {
+        let (x1, y1) = projection.project(grid.lon_min, grid.lat_max);
+        let (x2, y2) = projection.project(grid.lon_max, grid.lat_min);
+        let class = if *confirmed { "confirmed" } else { "worked" };
+        writeln!(
+            content_svg,
+            r#"<rect x="{x1:.1}" y="{y1:.1}" width="{w:.1}" height="{h:.1}" class="{class}"><title>{square}</title></rect>"#,
+            w = x2 - x1,
+            h = y2 - y1,
+        )?;
+    }
+    content_svg.push_str("</g>\n");
+
+    let footer_y = (TITLE_HEIGHT + projection.height) as f32;
+    let footer_svg = footer_svg(footer_y, &default_footer_text());
+
+    let final_svg = template_content
+        .replace("{{SVG_WIDTH}}", &projection.width.to_string())
+        .replace("{{SVG_HEIGHT}}", &(footer_y + FOOTER_HEIGHT).to_string())
+        .replace("{{CONTENT}}", &content_svg)
+        .replace("{{FOOTER}}", &footer_svg);
+
+    // file name starts with the render time so the cleanup task can expire it
+    let output_path_string = format!(
+        "{}{}-{}-{}.png",
+        STATS_PIC_PATH_PREFIX,
+        Utc::now().to_rfc3339(),
+        payload.group_id,
+        payload.message_id
+    );
+    let png_output_path = Path::new(&output_path_string);
+
+    match render_svg_to_png(&final_svg, png_output_path).await {
+        Ok(_) => {
+            tracing::info!("Successfully rendered PNG to {:?}", png_output_path);
+            Ok(output_path_string)
+        },
+        Err(e) => {
+            tracing::error!("Failed to render SVG to PNG: {}", e);
+            Err(anyhow::anyhow!("Failed to render SVG to PNG: {}", e))
+        }
+    }
+}
+
+/// Basemap, grid lines and field labels, in map coordinates
+fn write_map_base(
+    svg: &mut String,
+    projection: &MapProjection,
+) -> std::fmt::Result {
+    let bounds = projection.bounds;
+
+    // basemap
+    for path in &BASEMAP.paths {
+        if let Some(data) = projection.path_data(path) {
+            let class = if path.closed { "land" } else { "border" };
+            writeln!(svg, r#"<path d="{data}" class="{class}" />"#)?;
+        }
+    }
+
+    // grid squares every 2x1 deg, fields every 20x10 deg
+    let mut lon = bounds.lon_min;
+    while lon <= bounds.lon_max {
+        let class = if (lon + 180.0) % 20.0 == 0.0 { "grid-field" } else { "grid-square" };
+        let (x, _) = projection.project(lon, 0.0);
+        writeln!(svg, r#"<line x1="{x:.1}" y1="0" x2="{x:.1}" y2="{h:.1}" class="{class}" />"#, h = projection.height)?;
+        lon += 2.0;
+    }
+    let mut lat = bounds.lat_min;
+    while lat <= bounds.lat_max {
+        let class = if (lat + 90.0) % 10.0 == 0.0 { "grid-field" } else { "grid-square" };
+        let (_, y) = projection.project(0.0, lat);
+        writeln!(svg, r#"<line x1="0" y1="{y:.1}" x2="{w:.1}" y2="{y:.1}" class="{class}" />"#, w = projection.width)?;
+        lat += 1.0;
+    }
+
+    // field labels in the top left corner of each field
+    let mut field_lon = ((bounds.lon_min + 180.0) / 20.0).floor() * 20.0 - 180.0;
+    while field_lon < bounds.lon_max {
+        let mut field_lat = ((bounds.lat_min + 90.0) / 10.0).floor() * 10.0 - 90.0;
+        while field_lat < bounds.lat_max {
+            let label_lon = field_lon.max(bounds.lon_min);
+            let label_lat = (field_lat + 10.0).min(bounds.lat_max);
+            let (x, y) = projection.project(label_lon, label_lat);
+            let field = format!(
+                "{}{}",
+                (b'A' + ((field_lon + 180.0) / 20.0) as u8) as char,
+                (b'A' + ((field_lat + 90.0) / 10.0) as u8) as char,
+            );
+            writeln!(svg, r#"<text x="{:.1}" y="{:.1}" class="field-label">{}</text>"#, x + 4.0, y + 16.0, field)?;
+            field_lat += 10.0;
+        }
+        field_lon += 20.0;
+    }
+    Ok(())
+}
+
+pub fn convert_bjt_to_utc_iso8601(bjt_string: &str) -> anyhow::Result<String> {
     // 检查并替换时区缩写 "BJT" 为 UTC 偏移量
     if !bjt_string.ends_with(" BJT") {
         return Err(anyhow::anyhow!("输入字符串必须以 ' BJT' 结尾"));
@@ -235,8 +546,8 @@ fn lerp_color(c1: (u8, u8, u8), c2: (u8, u8, u8), t: f64) -> (u8, u8, u8) {
 pub async fn render_satstatus_data(
     report_data: &Vec<SatelliteFileFormat>,
     payload: &MessageEvent,
-) -> ApiResponse<Vec<String>> {
-    let mut response = ApiResponse::<Vec<String>>::empty();
+) -> Reply {
+    let mut response = Reply::empty();
     tracing::debug!("Rendering satellite status data for {} blocks", report_data.len());
     const BLOCK_TITLE_HEIGHT: f32 = 45.0;
     const HEADER_HEIGHT: f32 = 40.0;
@@ -250,8 +561,6 @@ pub async fn render_satstatus_data(
     const COLOR_BLOCK_WIDTH: f32 = 12.0;
     const COLOR_BLOCK_HEIGHT: f32 = 18.0;
     const COLOR_BLOCK_TEXT_SPACING: f32 = 8.0;
-    const FOOTER_HEIGHT: f32 = 32.0;
-    const FOOTER_COLOR: &str = "#f0f2f5";
 
     let mut all_blocks_svg = String::new();
     let mut current_y_offset = 20.0;
@@ -385,7 +694,7 @@ pub async fn render_satstatus_data(
                         all_blocks_svg,
                         r##"<g class="data-row">
    <text x="{x_callsign}" y="{y_pos}" class="table-text">{callsign}</text>
-   <text x="{x_grid}" y="{y_pos}" class="table-text">{grid}</text>
+   <text x="{x_grid}" y="{y_pos}" class="table-text">{grid}<tspan font-size="12" fill="#6e7781">{entity}</tspan></text>
    <rect x="{x_report}" y="{rect_y}" width="{rect_w}" height="{rect_h}" fill="{color}" rx="1" />
    <text x="{report_text_x}" y="{y_pos}" class="table-text">{report}</text>
    <rect x="{x_time}" y="{rect_y}" width="{rect_w}" height="{rect_h}" fill="{color_time}" rx="1" />
@@ -403,6 +712,10 @@ pub async fn render_satstatus_data(
                         rect_h = COLOR_BLOCK_HEIGHT,
                         callsign = &report.callsign,
                         grid = &report.grid_square,
+                        entity = Callsign::parse(&report.callsign)
+                            .and_then(|c| c.lookup())
+                            .map(|lookup| format!(" · {}", xml_escape(&lookup.entity.primary_prefix)))
+                            .unwrap_or_default(),
                         report = ReportStatus::from_string(&report.report).to_string(),
                         color = ReportStatus::string_to_color_hex(&report.report),
                         time = &report.reported_time,
@@ -429,20 +742,7 @@ pub async fn render_satstatus_data(
     let footer_y = current_y_offset;
     current_y_offset += FOOTER_HEIGHT;
 
-    let render_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let footer_svg = format!(
-        r##"    <g id="footer">
-        <rect x="0" y="{footer_y}" width="100%" height="{FOOTER_HEIGHT}" fill="{FOOTER_COLOR}" />
-        <text x="50%" y="{footer_text_y}" class="table-text footer-text" text-anchor="middle">
-            Rinko Bot v0.1.1, rendered at {time_str} BJT, 测试中
-        </text>
-        </g>"##,
-        footer_y = footer_y,
-        FOOTER_HEIGHT = FOOTER_HEIGHT,
-        FOOTER_COLOR = FOOTER_COLOR,
-        footer_text_y = footer_y + (FOOTER_HEIGHT / 2.0),
-        time_str = render_time
-    );
+    let footer_svg = footer_svg(footer_y, &default_footer_text());
 
     let total_height = current_y_offset;
     let template_content = tokio::fs::read_to_string(SVG_SATSTATUS_TEMPLATE_PATH).await;
@@ -476,10 +776,7 @@ pub async fn render_satstatus_data(
     match render_svg_to_png(&final_svg, png_output_path).await {
         Ok(_) => {
             tracing::info!("Successfully rendered PNG to {:?}", png_output_path);
-            response.message = Some("image".to_string());
-            response.data = Some(vec![format!("file:///server_{}", output_path_string)]);
-            response.success = true;
-            response
+            Reply::image(output_path_string)
         },
         Err(e) => {
             tracing::error!("Failed to render SVG to PNG: {}", e);
@@ -489,6 +786,556 @@ pub async fn render_satstatus_data(
     }
 }
 
+pub async fn render_report_stats(
+    stats: &CallsignStats,
+    payload: &MessageEvent,
+) -> Reply {
+    const LEFT_PADDING: f32 = 20.0;
+    const TITLE_HEIGHT: f32 = 70.0;
+    const CARD_WIDTH: f32 = 185.0;
+    const CARD_HEIGHT: f32 = 80.0;
+    const CARD_SPACING: f32 = 10.0;
+    const SECTION_TITLE_HEIGHT: f32 = 40.0;
+    const ROW_HEIGHT: f32 = 32.0;
+    const X_BAR: f32 = 200.0;
+    const MAX_BAR_WIDTH: f32 = 520.0;
+    const BAR_HEIGHT: f32 = 18.0;
+    const MAX_SATELLITE_ROWS: usize = 8;
+    const SECTION_SPACING: f32 = 20.0;
+    const CARD_COLOR: &str = "#f6f8fa";
+    const BAR_COLOR: &str = "#0969da";
+
+    let mut content_svg = String::new();
+    let mut current_y_offset = 20.0;
+
+    // title, callsign and region
+    let entity = Callsign::parse(&stats.callsign)
+        .and_then(|c| c.lookup())
+        .map(|lookup| xml_escape(&lookup.entity.name))
+        .unwrap_or_default();
+    let last_report = stats.last_report.as_deref()
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc).format("%Y-%m-%d %H:%MZ").to_string())
+        .unwrap_or_else(|| "-".to_string());
+    if let Err(e) = writeln!(
+        content_svg,
+        r##"<text x="{x}" y="{title_y}" class="stats-title">{callsign}</text>
+<text x="{x}" y="{sub_y}" class="table-text sub-text">{entity}  最近报告: {last_report}</text>"##,
+        x = LEFT_PADDING,
+        title_y = current_y_offset + 18.0,
+        sub_y = current_y_offset + 46.0,
+        callsign = &stats.callsign,
+        entity = entity,
+        last_report = last_report,
+    ) {
+        return ApiResponse::error(format!("Failed to write SVG: {}", e));
+    }
+    current_y_offset += TITLE_HEIGHT;
+
+    // summary cards
+    let cards = [
+        ("总报告", stats.total_reports.to_string()),
+        ("卫星", stats.satellites.len().to_string()),
+        ("网格", stats.unique_grids.to_string()),
+        ("连续天数 (最长)", format!("{} ({})", stats.current_streak, stats.longest_streak)),
+    ];
+    for (index, (label, value)) in cards.iter().enumerate() {
+        let x = LEFT_PADDING + index as f32 * (CARD_WIDTH + CARD_SPACING);
+        if let Err(e) = writeln!(
+            content_svg,
+            r##"<g class="stats-card">
+   <rect x="{x}" y="{y}" width="{w}" height="{h}" fill="{color}" rx="6" />
+   <text x="{text_x}" y="{label_y}" class="table-text sub-text">{label}</text>
+   <text x="{text_x}" y="{value_y}" class="table-text stats-number">{value}</text>
+</g>"##,
+            x = x,
+            y = current_y_offset,
+            w = CARD_WIDTH,
+            h = CARD_HEIGHT,
+            color = CARD_COLOR,
+            text_x = x + 14.0,
+            label_y = current_y_offset + 22.0,
+            value_y = current_y_offset + 54.0,
+            label = label,
+            value = value,
+        ) {
+            return ApiResponse::error(format!("Failed to write SVG: {}", e));
+        }
+    }
+    current_y_offset += CARD_HEIGHT + SECTION_SPACING;
+
+    // bar sections, monthly reports then satellites
+    let satellites: Vec<(String, usize)> = stats.satellites.iter().take(MAX_SATELLITE_ROWS).cloned().collect();
+    let sections = [("每月报告", &stats.monthly_reports), ("卫星", &satellites)];
+    for (title, rows) in sections {
+        if let Err(e) = writeln!(
+            content_svg,
+            r##"<text x="{x}" y="{y}" class="table-text header-text">{title}</text>"##,
+            x = LEFT_PADDING,
+            y = current_y_offset + SECTION_TITLE_HEIGHT / 2.0,
+            title = title,
+        ) {
+            return ApiResponse::error(format!("Failed to write SVG: {}", e));
+        }
+        current_y_offset += SECTION_TITLE_HEIGHT;
+
+        let max_count = rows.iter().map(|(_, count)| *count).max().unwrap_or(1).max(1);
+        for (label, count) in rows.iter() {
+            let y_pos = current_y_offset + ROW_HEIGHT / 2.0;
+            let bar_width = (*count as f32 / max_count as f32 * MAX_BAR_WIDTH).max(2.0);
+            if let Err(e) = writeln!(
+                content_svg,
+                r##"<g class="data-row">
+   <text x="{x}" y="{y_pos}" class="table-text row-text monospace">{label}</text>
+   <rect x="{x_bar}" y="{bar_y}" width="{bar_w}" height="{bar_h}" fill="{color}" rx="2" />
+   <text x="{count_x}" y="{y_pos}" class="table-text row-text">{count}</text>
+</g>"##,
+                x = LEFT_PADDING,
+                y_pos = y_pos,
+                label = label,
+                x_bar = X_BAR,
+                bar_y = y_pos - BAR_HEIGHT / 2.0,
+                bar_w = bar_width,
+                bar_h = BAR_HEIGHT,
+                color = BAR_COLOR,
+                count_x = X_BAR + bar_width + 8.0,
+                count = count,
+            ) {
+                return ApiResponse::error(format!("Failed to write SVG: {}", e));
+            }
+            current_y_offset += ROW_HEIGHT;
+        }
+        current_y_offset += SECTION_SPACING;
+    }
+
+    render_stats_svg(&content_svg, current_y_offset, payload).await
+}
+
+pub async fn render_leaderboard(
+    month: &str,
+    leaderboard: &[LeaderboardEntry],
+    payload: &MessageEvent,
+) -> Reply {
+    const LEFT_PADDING: f32 = 20.0;
+    const TITLE_HEIGHT: f32 = 50.0;
+    const HEADER_HEIGHT: f32 = 40.0;
+    const ROW_HEIGHT: f32 = 38.0;
+    const X_RANK: f32 = 20.0;
+    const X_CALLSIGN: f32 = 70.0;
+    const X_REPORTS: f32 = 260.0;
+    const X_SATELLITES: f32 = 380.0;
+    const X_GRIDS: f32 = 500.0;
+    const X_STREAK: f32 = 620.0;
+    const EVEN_ROW_COLOR: &str = "#f6f8fa";
+    const ODD_ROW_COLOR: &str = "#ffffff";
+    // gold, silver, bronze
+    const MEDAL_COLORS: [&str; 3] = ["#d4a017", "#8c959f", "#b0703c"];
+
+    let mut content_svg = String::new();
+    let mut current_y_offset = 20.0;
+
+    if let Err(e) = writeln!(
+        content_svg,
+        r##"<text x="{x}" y="{y}" class="stats-title">{month} 报告排行</text>
+<g class="header-row">
+   <text x="{x_rank}" y="{header_y}" class="table-text header-text">#</text>
+   <text x="{x_callsign}" y="{header_y}" class="table-text header-text">呼号</text>
+   <text x="{x_reports}" y="{header_y}" class="table-text header-text">报告</text>
+   <text x="{x_satellites}" y="{header_y}" class="table-text header-text">卫星</text>
+   <text x="{x_grids}" y="{header_y}" class="table-text header-text">网格</text>
+   <text x="{x_streak}" y="{header_y}" class="table-text header-text">最长连续天数</text>
+</g>"##,
+        x = LEFT_PADDING,
+        y = current_y_offset + 18.0,
+        month = month,
+        header_y = current_y_offset + TITLE_HEIGHT + HEADER_HEIGHT / 2.0,
+        x_rank = X_RANK,
+        x_callsign = X_CALLSIGN,
+        x_reports = X_REPORTS,
+        x_satellites = X_SATELLITES,
+        x_grids = X_GRIDS,
+        x_streak = X_STREAK,
+    ) {
+        return ApiResponse::error(format!("Failed to write SVG: {}", e));
+    }
+    current_y_offset += TITLE_HEIGHT + HEADER_HEIGHT;
+
+    for (index, entry) in leaderboard.iter().enumerate() {
+        let row_color = if index % 2 == 0 { EVEN_ROW_COLOR } else { ODD_ROW_COLOR };
+        let rank_color = MEDAL_COLORS.get(index).copied().unwrap_or("#24292f");
+        let y_pos = current_y_offset + ROW_HEIGHT / 2.0;
+        if let Err(e) = writeln!(
+            content_svg,
+            r##"<g class="data-row">
+   <rect x="0" y="{row_y}" width="100%" height="{row_h}" fill="{row_color}" />
+   <text x="{x_rank}" y="{y_pos}" class="table-text" font-weight="600" fill="{rank_color}">{rank}</text>
+   <text x="{x_callsign}" y="{y_pos}" class="table-text row-text monospace">{callsign}</text>
+   <text x="{x_reports}" y="{y_pos}" class="table-text row-text">{reports}</text>
+   <text x="{x_satellites}" y="{y_pos}" class="table-text row-text">{satellites}</text>
+   <text x="{x_grids}" y="{y_pos}" class="table-text row-text">{grids}</text>
+   <text x="{x_streak}" y="{y_pos}" class="table-text row-text">{streak}</text>
+</g>"##,
+            row_y = current_y_offset,
+            row_h = ROW_HEIGHT,
+            row_color = row_color,
+            y_pos = y_pos,
+            x_rank = X_RANK,
+            rank_color = rank_color,
+            rank = index + 1,
+            x_callsign = X_CALLSIGN,
+            callsign = xml_escape(&entry.callsign),
+            x_reports = X_REPORTS,
+            reports = entry.reports,
+            x_satellites = X_SATELLITES,
+            satellites = entry.satellites,
+            x_grids = X_GRIDS,
+            grids = entry.grids,
+            x_streak = X_STREAK,
+            streak = entry.longest_streak,
+        ) {
+            return ApiResponse::error(format!("Failed to write SVG: {}", e));
+        }
+        current_y_offset += ROW_HEIGHT;
+    }
+    current_y_offset += 20.0;
+
+    render_stats_svg(&content_svg, current_y_offset, payload).await
+}
+
+/// Current values, 7-day sparklines and the HF/VHF tables, written to `SPACE_WEATHER_PIC_PATH`
+pub async fn render_space_weather(
+    weather: &SpaceWeather,
+    history: &[SpaceWeatherSample],
+) -> anyhow::Result<()> {
+    const LEFT_PADDING: f32 = 20.0;
+    const TITLE_HEIGHT: f32 = 70.0;
+    const CARD_WIDTH: f32 = 185.0;
+    const CARD_HEIGHT: f32 = 80.0;
+    const CARD_SPACING: f32 = 10.0;
+    const SECTION_TITLE_HEIGHT: f32 = 40.0;
+    const SECTION_SPACING: f32 = 20.0;
+    const SPARK_ROW_HEIGHT: f32 = 44.0;
+    const X_SPARK: f32 = 120.0;
+    const SPARK_WIDTH: f32 = 520.0;
+    const SPARK_HEIGHT: f32 = 30.0;
+    const X_SPARK_RANGE: f32 = 660.0;
+    const HISTORY_DAYS: i64 = 7;
+    const ROW_HEIGHT: f32 = 30.0;
+    const X_COLUMN_2: f32 = 300.0;
+    const X_COLUMN_3: f32 = 560.0;
+    const CARD_COLOR: &str = "#f6f8fa";
+    const LINE_COLOR: &str = "#0969da";
+    const EVEN_ROW_COLOR: &str = "#f6f8fa";
+
+    let template_content = match read_svg_template_file(SVG_STATS_TEMPLATE_PATH).await {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::error!("Failed to read SVG template file: {}", e);
+            return Err(anyhow::anyhow!("Failed to read SVG template file: {}", e));
+        }
+    };
+
+    // everything from the feed is escaped, one stray `&` would stop the dashboard from rendering
+    let value_text = |value: Option<String>| value.map(|v| xml_escape(&v)).unwrap_or_else(|| "-".to_string());
+    let updated = weather.source_updated.as_ref().unwrap_or(&weather.fetched_at);
+    let updated = chrono::DateTime::parse_from_rfc3339(updated)
+        .map(|t| t.with_timezone(&chrono::FixedOffset::east_opt(8 * 3600).unwrap()).format("%Y-%m-%d %H:%M BJT").to_string())
+        .unwrap_or_else(|_| updated.clone());
+
+    let mut content_svg = String::new();
+    let mut current_y_offset = 20.0;
+
+    writeln!(
+        content_svg,
+        r##"<text x="{x}" y="{title_y}" class="stats-title">空间天气</text>
+<text x="{x}" y="{sub_y}" class="table-text sub-text">更新于 {updated}  地磁 {geomag}  噪声 {noise}  X 射线 {xray}  太阳风 {wind} km/s</text>"##,
+        x = LEFT_PADDING,
+        title_y = current_y_offset + 18.0,
+        sub_y = current_y_offset + 46.0,
+        geomag = value_text(weather.geomag_field.clone()),
+        noise = value_text(weather.signal_noise.clone()),
+        xray = value_text(weather.xray.clone()),
+        wind = value_text(weather.solar_wind.map(|v| v.to_string())),
+    )?;
+    current_y_offset += TITLE_HEIGHT;
+
+    // the K card turns orange at minor and red at strong storm levels
+    let k_color = match weather.k_index {
+        Some(k) if k >= 6.0 => "#cf222e",
+        Some(k) if k >= 4.0 => "#bc4c00",
+        _ => "#24292f",
+    };
+    let cards = [
+        ("太阳通量 SFI", value_text(weather.solar_flux.map(|v| v.to_string())), "#24292f"),
+        ("黑子数 SSN", value_text(weather.sunspots.map(|v| v.to_string())), "#24292f"),
+        ("K 指数", value_text(weather.k_index.map(|v| v.to_string())), k_color),
+        ("A 指数", value_text(weather.a_index.map(|v| v.to_string())), "#24292f"),
+    ];
+    for (index, (label, value, color)) in cards.iter().enumerate() {
+        let x = LEFT_PADDING + index as f32 * (CARD_WIDTH + CARD_SPACING);
+        writeln!(
+            content_svg,
+            r##"<g class="stats-card">
+   <rect x="{x}" y="{y}" width="{w}" height="{h}" fill="{card_color}" rx="6" />
+   <text x="{text_x}" y="{label_y}" class="table-text sub-text">{label}</text>
+   <text x="{text_x}" y="{value_y}" class="table-text stats-number" fill="{color}">{value}</text>
+</g>"##,
+            y = current_y_offset,
+            w = CARD_WIDTH,
+            h = CARD_HEIGHT,
+            card_color = CARD_COLOR,
+            text_x = x + 14.0,
+            label_y = current_y_offset + 22.0,
+            value_y = current_y_offset + 54.0,
+        )?;
+    }
+    current_y_offset += CARD_HEIGHT + SECTION_SPACING;
+
+    // sparklines, x is time over the last 7 days so gaps in the history stay visible
+    let now = Utc::now();
+    let since = now - chrono::Duration::days(HISTORY_DAYS);
+    let samples: Vec<(chrono::DateTime<Utc>, &SpaceWeatherSample)> = history
+        .iter()
+        .filter_map(|s| {
+            let time = chrono::DateTime::parse_from_rfc3339(&s.time).ok()?.with_timezone(&Utc);
+            (time >= since).then_some((time, s))
+        })
+        .collect();
+    let series_of = |value_of: fn(&SpaceWeatherSample) -> Option<f64>| {
+        samples
+            .iter()
+            .filter_map(|(time, s)| Some((*time, value_of(s)?)))
+            .collect::<Vec<_>>()
+    };
+    let series = [
+        ("SFI", series_of(|s| s.solar_flux)),
+        ("SSN", series_of(|s| s.sunspots.map(|v| v as f64))),
+        ("K", series_of(|s| s.k_index)),
+        ("A", series_of(|s| s.a_index)),
+    ];
+
+    writeln!(
+        content_svg,
+        r##"<text x="{x}" y="{y}" class="table-text header-text">近 {HISTORY_DAYS} 天</text>"##,
+        x = LEFT_PADDING,
+        y = current_y_offset + SECTION_TITLE_HEIGHT / 2.0,
+    )?;
+    current_y_offset += SECTION_TITLE_HEIGHT;
+
+    let window_secs = (now - since).num_seconds() as f32;
+    for (label, values) in series {
+        let points: Vec<(f32, f64)> = values
+            .iter()
+            .map(|(time, value)| ((*time - since).num_seconds() as f32 / window_secs * SPARK_WIDTH, *value))
+            .collect();
+        let min = points.iter().map(|(_, v)| *v).fold(f64::INFINITY, f64::min);
+        let max = points.iter().map(|(_, v)| *v).fold(f64::NEG_INFINITY, f64::max);
+        // a flat series sits in the middle
+        let ratio = |v: f64| if max > min { ((v - min) / (max - min)) as f32 } else { 0.5 };
+        let y_pos = current_y_offset + SPARK_ROW_HEIGHT / 2.0;
+        let spark_top = y_pos - SPARK_HEIGHT / 2.0;
+        let polyline: Vec<String> = points
+            .iter()
+            .map(|(x, v)| format!(
+                "{:.1},{:.1}",
+                X_SPARK + x,
+                spark_top + SPARK_HEIGHT - ratio(*v) * SPARK_HEIGHT
+            ))
+            .collect();
+        let range = if points.is_empty() { "无数据".to_string() } else { format!("{} ~ {}", min, max) };
+
+        writeln!(
+            content_svg,
+            r##"<g class="data-row">
+   <text x="{x}" y="{y_pos}" class="table-text row-text monospace">{label}</text>
+   <rect x="{X_SPARK}" y="{spark_top}" width="{SPARK_WIDTH}" height="{SPARK_HEIGHT}" fill="{CARD_COLOR}" rx="2" />
+   <polyline points="{points}" fill="none" stroke="{LINE_COLOR}" stroke-width="2" />
+   <text x="{X_SPARK_RANGE}" y="{y_pos}" class="table-text sub-text">{range}</text>
+</g>"##,
+            x = LEFT_PADDING,
+            points = polyline.join(" "),
+        )?;
+        current_y_offset += SPARK_ROW_HEIGHT;
+    }
+    current_y_offset += SECTION_SPACING;
+
+    // HF by band, VHF only lists what the feed reports
+    let condition_color = |condition: &str| match condition.to_lowercase().as_str() {
+        "good" => "#1a7f37",
+        "fair" => "#bc4c00",
+        "poor" | "band closed" => "#cf222e",
+        _ => "#24292f",
+    };
+    let bands = weather.hf_bands();
+    let hf_rows: Vec<[String; 3]> = bands
+        .iter()
+        .map(|band| {
+            let condition = |time: &str| {
+                weather.band_conditions
+                    .iter()
+                    .find(|b| b.band == *band && b.time == time)
+                    .map(|b| xml_escape(&b.condition))
+                    .unwrap_or_else(|| "-".to_string())
+            };
+            [xml_escape(band), condition("day"), condition("night")]
+        })
+        .collect();
+    let vhf_rows: Vec<[String; 3]> = weather.vhf_conditions
+        .iter()
+        .map(|v| [xml_escape(&v.phenomenon), xml_escape(&v.location.replace('_', " ")), xml_escape(&v.condition)])
+        .collect();
+    let tables = [
+        ("短波", ["波段", "白天", "夜间"], hf_rows),
+        ("VHF", ["现象", "区域", "状态"], vhf_rows),
+    ];
+
+    for (title, headers, rows) in tables {
+        if rows.is_empty() {
+            continue;
+        }
+        writeln!(
+            content_svg,
+            r##"<text x="{x}" y="{y}" class="table-text header-text">{title}</text>
+<g class="header-row">
+   <text x="{x}" y="{header_y}" class="table-text sub-text">{h1}</text>
+   <text x="{X_COLUMN_2}" y="{header_y}" class="table-text sub-text">{h2}</text>
+   <text x="{X_COLUMN_3}" y="{header_y}" class="table-text sub-text">{h3}</text>
+</g>"##,
+            x = LEFT_PADDING,
+            y = current_y_offset + SECTION_TITLE_HEIGHT / 2.0,
+            header_y = current_y_offset + SECTION_TITLE_HEIGHT + ROW_HEIGHT / 2.0,
+            h1 = headers[0],
+            h2 = headers[1],
+            h3 = headers[2],
+        )?;
+        current_y_offset += SECTION_TITLE_HEIGHT + ROW_HEIGHT;
+
+        for (index, [first, second, third]) in rows.iter().enumerate() {
+            let row_color = if index % 2 == 0 { EVEN_ROW_COLOR } else { "#ffffff" };
+            // HF colours both day and night, VHF only the state column
+            let second_color = if title == "短波" { condition_color(second) } else { "#24292f" };
+            writeln!(
+                content_svg,
+                r##"<g class="data-row">
+   <rect x="0" y="{row_y}" width="100%" height="{ROW_HEIGHT}" fill="{row_color}" />
+   <text x="{x}" y="{y_pos}" class="table-text row-text monospace">{first}</text>
+   <text x="{X_COLUMN_2}" y="{y_pos}" class="table-text" fill="{second_color}">{second}</text>
+   <text x="{X_COLUMN_3}" y="{y_pos}" class="table-text" fill="{third_color}">{third}</text>
+</g>"##,
+                row_y = current_y_offset,
+                x = LEFT_PADDING,
+                y_pos = current_y_offset + ROW_HEIGHT / 2.0,
+                third_color = condition_color(third),
+            )?;
+            current_y_offset += ROW_HEIGHT;
+        }
+        current_y_offset += SECTION_SPACING;
+    }
+
+    let footer_y = current_y_offset;
+    let footer_svg = footer_svg(footer_y, &format!("Rinko Bot v0.1.1, data from hamqsl.com, rendered at {} BJT", render_time()));
+
+    let final_svg = template_content
+        .replace("{{SVG_HEIGHT}}", &(footer_y + FOOTER_HEIGHT).to_string())
+        .replace("{{CONTENT}}", &content_svg)
+        .replace("{{FOOTER}}", &footer_svg);
+
+    let png_output_path: &Path = Path::new(SPACE_WEATHER_PIC_PATH);
+
+    match render_svg_to_png(&final_svg, png_output_path).await {
+        Ok(_) => {
+            tracing::info!("Successfully rendered PNG to {:?}", png_output_path);
+            Ok(())
+        },
+        Err(e) => {
+            tracing::error!("Failed to render SVG to PNG: {}", e);
+            Err(anyhow::anyhow!("Failed to render SVG to PNG: {}", e))
+        }
+    }
+}
+
+/// Fill the stats template and write it to `STATS_PIC_PATH_PREFIX`
+async fn render_stats_svg(
+    content_svg: &str,
+    content_height: f32,
+    payload: &MessageEvent,
+) -> Reply {
+
+    let template_content = match read_svg_template_file(SVG_STATS_TEMPLATE_PATH).await {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::error!("Failed to read SVG template file: {}", e);
+            return ApiResponse::error(e);
+        }
+    };
+
+    let footer_y = content_height;
+    let footer_svg = footer_svg(footer_y, &default_footer_text());
+
+    let final_svg = template_content
+        .replace("{{SVG_HEIGHT}}", &(content_height + FOOTER_HEIGHT).to_string())
+        .replace("{{CONTENT}}", content_svg)
+        .replace("{{FOOTER}}", &footer_svg);
+
+    // file name starts with the render time so the cleanup task can expire it
+    let output_path_string = format!(
+        "{}{}-{}-{}.png",
+        STATS_PIC_PATH_PREFIX,
+        Utc::now().to_rfc3339(),
+        payload.group_id,
+        payload.message_id
+    );
+    let png_output_path = Path::new(&output_path_string);
+
+    match render_svg_to_png(&final_svg, png_output_path).await {
+        Ok(_) => {
+            tracing::info!("Successfully rendered PNG to {:?}", png_output_path);
+            Reply::image(output_path_string)
+        },
+        Err(e) => {
+            tracing::error!("Failed to render SVG to PNG: {}", e);
+            ApiResponse::error(format!("Failed to render SVG to PNG: {}", e))
+        }
+    }
+}
+
+/// Footer bar at `y`, every picture ends with one
+fn footer_svg(y: f32, text: &str) -> String {
+    format!(
+        r##"    <g id="footer">
+        <rect x="0" y="{y}" width="100%" height="{FOOTER_HEIGHT}" fill="{FOOTER_COLOR}" />
+        <text x="50%" y="{text_y}" class="table-text footer-text" text-anchor="middle">
+            {text}
+        </text>
+        </g>"##,
+        text_y = y + FOOTER_HEIGHT / 2.0,
+    )
+}
+
+fn default_footer_text() -> String {
+    format!("Rinko Bot v0.1.1, rendered at {} BJT, 测试中", render_time())
+}
+
+fn render_time() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Text from cty.dat or users goes into SVG markup, e.g. `Trinidad & Tobago`
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 fn wrap_text(text: &str, max_width: f32, max_lines: usize) -> (Vec<String>, usize) {
     // 简单换行算法：按字符分割
     let avg_char_width = 8.0; // 平均字符宽度估计值
@@ -569,4 +1416,4 @@ async fn render_svg_to_png(svg_data: &str, output_path: &Path) -> Result<(), Box
     tokio::fs::write(output_path, png_data).await?;
     
     Ok(())
-}
\ No newline at end of file
+}
\ No newline at end of file
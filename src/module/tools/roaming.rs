@@ -1,25 +1,52 @@
 use crate::{
     app_status::AppStatus,
-    fs::handler::{FileData, FileFormat, FileRequest},
-    module::prelude::*,
-    msg:: prelude::MessageEvent,
-    response::ApiResponse,
-    module::tools::render,
+    fs::handler::{check_file_exists, load_file, write_file, FileData, FileFormat, FileRequest},
+    module::{amsat::report_audit::read_audit_log, callsign::parser::callsign_region_text, prelude::*},
+    msg::{prelude::MessageEvent, segment::Segment},
+    response::{ApiResponse, Reply},
+    module::tools::{footprint::fetch_sat_footprint, render, roaming_feed::publish_roaming_change},
 };
 use std::{sync::Arc};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, TimeZone, Utc};
 use regex::Regex;
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
 
 const USER_ROAMING_DATA: &str = "runtime_data/user_roaming_data.json";
+/// Expired entries are moved here by the scheduled task
+const USER_ROAMING_ARCHIVE: &str = "runtime_data/user_roaming_archive.json";
+/// Entries without a window in the remark expire this long after submission
+pub const ROAMING_DEFAULT_VALIDITY_DAYS: i64 = 7;
+
+lazy_static::lazy_static! {
+    /// `8.1-8.5`, `2025-08-01 ~ 2025-08-05`, `8月1日 12:00 至 8月5日`
+    static ref DATE_RANGE_RE: Regex = Regex::new(
+        r"(?P<start>(?:\d{4}[-./年])?\d{1,2}[-./月]\d{1,2}日?(?:\s*\d{1,2}:\d{2})?)\s*(?:~|～|-|—|至|到)\s*(?P<end>(?:\d{4}[-./年])?\d{1,2}[-./月]\d{1,2}日?(?:\s*\d{1,2}:\d{2})?)"
+    ).unwrap();
+    static ref DATE_RE: Regex = Regex::new(
+        r"^(?:(?P<year>\d{4})[-./年])?(?P<month>\d{1,2})[-./月](?P<day>\d{1,2})日?(?:\s*(?P<hour>\d{1,2}):(?P<minute>\d{2}))?$"
+    ).unwrap();
+    /// Held from reading the roaming list to writing it back (and rendering it),
+    /// so an add, remove, import and the archive task never overwrite each other
+    pub static ref ROAMING_DATA_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoamingData {
     pub callsign: String,
     pub grid: String,
     pub remark: Option<String>,
+    /// RFC3339, parsed from the remark at submission
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// RFC3339, parsed from the remark at submission
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
 }
 
+/// Owner of entries created by `/roaming import`, their real owners are unknown
+pub const IMPORTED_ROAMING_OWNER: &str = "import";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserRoamingData {
     pub user_id: String,
@@ -27,179 +54,113 @@ pub struct UserRoamingData {
     pub roaming_data: RoamingData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RoamingSubmitHistory {
-    pub user_id: u64,
-    pub earliest_submit: String,    // ISO 8601 format
-    pub submit_count: usize,
-    pub block: bool,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoamingState {
+    Active,
+    Upcoming,
+    Expired,
 }
 
-const MAX_SUBMIT_LIMIT: usize = 3;
-const USER_ROAMING_SUBMIT_HISTORY: &str = "runtime_data/user_roaming_submit_history.json";
-
-async fn read_user_roaming_submit_history(
-    tx_filerequest: &Arc<RwLock<tokio::sync::mpsc::Sender<FileRequest>>>,
-) -> anyhow::Result<Vec<RoamingSubmitHistory>> {
-    let tx_filerequest = tx_filerequest.write().await;
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    let file_request = FileRequest::Read {
-        path: USER_ROAMING_SUBMIT_HISTORY.into(),
-        format: FileFormat::Json,
-        responder: tx,
-    };
+fn parse_rfc3339_utc(t: &Option<String>) -> Option<DateTime<Utc>> {
+    t.as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
 
-    if let Err(e) = tx_filerequest.send(file_request).await {
-        return Err(anyhow::anyhow!("Failed to send file read request: {}", e));
-    };
+impl UserRoamingData {
+    fn submitted_at(&self) -> Option<DateTime<Utc>> {
+        render::convert_bjt_to_utc_iso8601(&self.submit_time)
+            .ok()
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+            .map(|t| t.with_timezone(&Utc))
+    }
 
-    let read_result = match rx.await {
-        Ok(data) => data,
-        Err(e) => {
-            return Err(anyhow::anyhow!("Failed to receive file read response: {}", e));
+    pub fn state(&self, now: DateTime<Utc>) -> RoamingState {
+        let end = parse_rfc3339_utc(&self.roaming_data.end).or_else(|| {
+            self.submitted_at().map(|t| t + chrono::Duration::days(ROAMING_DEFAULT_VALIDITY_DAYS))
+        });
+        if end.is_some_and(|end| end < now) {
+            return RoamingState::Expired;
         }
-    };
-
-    let submit_history = match read_result {
-        Ok(FileData::Json(data)) => match serde_json::from_value::<Vec<RoamingSubmitHistory>>(data) {
-            Ok(data) => data,
-            Err(e) => {
-                return Err(anyhow::anyhow!("Failed to parse submit history: {}", e));
-            }
-        },
-        _ => {
-            return Err(anyhow::anyhow!("Unexpected file format received"));
+        if parse_rfc3339_utc(&self.roaming_data.start).is_some_and(|start| start > now) {
+            return RoamingState::Upcoming;
         }
-    };
-
-    Ok(submit_history)
-}
-
-async fn write_user_roaming_submit_history(
-    tx_filerequest: &Arc<RwLock<tokio::sync::mpsc::Sender<FileRequest>>>,
-    submit_history: Vec<RoamingSubmitHistory>
-) -> anyhow::Result<()> {
-    let tx_filerequest = tx_filerequest.write().await;
-    let (tx, rx) = tokio::sync::oneshot::channel();
-    let file_request = FileRequest::Write {
-        path: USER_ROAMING_SUBMIT_HISTORY.into(),
-        format: FileFormat::Json,
-        data: FileData::Json(serde_json::to_value(submit_history).unwrap()),
-        responder: tx,
-    };
-
-    if let Err(e) = tx_filerequest.send(file_request).await {
-        return Err(anyhow::anyhow!("Failed to send file write request: {}", e));
-    };
+        RoamingState::Active
+    }
 
-    let write_response = match rx.await {
-        Ok(data) => data,
-        Err(e) => {
-            return Err(anyhow::anyhow!("Failed to receive file write response: {}", e));
-        }
-    };
+    /// Window from the remark, otherwise submission time plus the default validity
+    pub fn effective_window(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = parse_rfc3339_utc(&self.roaming_data.start).or_else(|| self.submitted_at())?;
+        let end = parse_rfc3339_utc(&self.roaming_data.end)
+            .unwrap_or(start + chrono::Duration::days(ROAMING_DEFAULT_VALIDITY_DAYS));
+        Some((start, end))
+    }
 
-    match write_response {
-        Ok(_) => Ok(()),
-        Err(e) => Err(anyhow::anyhow!("Failed to write submit history: {}", e)),
+    /// e.g. `08-01 00:00 ~ 08-05 23:59 BJT`
+    pub fn window_text(&self) -> Option<String> {
+        let bjt = FixedOffset::east_opt(8 * 3600)?;
+        let format = |t: &Option<String>| {
+            t.as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&bjt).format("%m-%d %H:%M").to_string())
+        };
+        Some(format!(
+            "{} ~ {} BJT",
+            format(&self.roaming_data.start)?,
+            format(&self.roaming_data.end)?
+        ))
     }
 }
 
-async fn user_roaming_submit_history_handler(
-    payload: &MessageEvent,
-    tx_filerequest: &Arc<RwLock<tokio::sync::mpsc::Sender<FileRequest>>>,
-    admin_id: &Vec<u64>,
-) -> ApiResponse<Vec<String>> {
-    let user_id = payload.user_id;
-    let submit_history = read_user_roaming_submit_history(&tx_filerequest).await;
-
-    let submit_history = match submit_history {
-        Ok(data) => data,
-        Err(e) => {
-            tracing::error!("Failed to read roaming submit history: {}", e);
-            let response = ApiResponse::error(format!("Failed to read roaming submit history: {}", e));
-            return response;
-        }
+/// Find a date range in the remark, dates are BJT
+/// - a missing year is the current one, rolled forward if the range would already be over
+/// - a missing time means the whole day
+pub fn parse_roaming_window(
+    remark: &str,
+    now: DateTime<Utc>,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let bjt = FixedOffset::east_opt(8 * 3600)?;
+    let caps = DATE_RANGE_RE.captures(remark)?;
+    let current_year = now.with_timezone(&bjt).year();
+
+    let parse_date = |text: &str, year: i32, end_of_day: bool| -> Option<(DateTime<Utc>, bool)> {
+        let caps = DATE_RE.captures(text.trim())?;
+        let explicit_year = caps.name("year").and_then(|y| y.as_str().parse::<i32>().ok());
+        let month = caps["month"].parse::<u32>().ok()?;
+        let day = caps["day"].parse::<u32>().ok()?;
+        let date = NaiveDate::from_ymd_opt(explicit_year.unwrap_or(year), month, day)?;
+        let time = match (caps.name("hour"), caps.name("minute")) {
+            (Some(h), Some(m)) => date.and_hms_opt(h.as_str().parse().ok()?, m.as_str().parse().ok()?, 0)?,
+            _ if end_of_day => date.and_hms_opt(23, 59, 59)?,
+            _ => date.and_hms_opt(0, 0, 0)?,
+        };
+        let time = bjt.from_local_datetime(&time).single()?.with_timezone(&Utc);
+        Some((time, explicit_year.is_some()))
     };
 
-    let mut submit_history_to_write = Vec::new();
-    let mut find = false;
-    let now = chrono::Utc::now();
-
-    // find if the user has submit history
-    for entry in submit_history {
-        // Process each entry
-        if entry.user_id == user_id {
-            find = true;
-            let earliest_submit = entry.earliest_submit;
-            let submit_count = entry.submit_count;
-            let block = entry.block;
-
-            if block {
-                let response = ApiResponse::error(format!("你已被封禁，无法提交漫游信息喵。请联系管理员解封。"));
-                return response;
-            }
-
-            // Check if the user has submitted more than the limit
-            if submit_count >= MAX_SUBMIT_LIMIT && !admin_id.contains(&user_id) {
-                let earliest_submit = earliest_submit.parse::<chrono::DateTime<chrono::Utc>>().unwrap_or_else(|_| chrono::Utc::now());
-                let next_allowed_submit_time = earliest_submit + chrono::Duration::hours(1);
-                let dt = (next_allowed_submit_time - now).num_minutes() + 1;
-                let response_msg = format!(
-                    "你已超过提交漫游信息的限制喵，请在 {} 分钟后再试^ ^)/",
-                    dt
-                );
-                let response = ApiResponse::error(response_msg);
-                return response;
-            }
-
-            // Update submit history
-            let updated_entry = RoamingSubmitHistory {
-                user_id,
-                earliest_submit: earliest_submit.clone(),
-                submit_count: submit_count + 1,
-                block: false,
-            };
-            submit_history_to_write.push(updated_entry);
-        } else {
-            // clean up history that is longer than 1 hour
-            let earliest_submit = entry.earliest_submit.parse::<chrono::DateTime<chrono::Utc>>().unwrap_or_else(|_| chrono::Utc::now());
-            if earliest_submit + chrono::Duration::hours(1) < now {
-                // If the earliest submit time is more than 1 hour ago, discard this entry
-                continue;
-            }
-            submit_history_to_write.push(entry);
+    let mut year = current_year;
+    for _ in 0..2 {
+        let (start, start_has_year) = parse_date(&caps["start"], year, false)?;
+        let (mut end, end_has_year) = parse_date(&caps["end"], year, true)?;
+        // 12.30-1.3
+        if end < start && !end_has_year {
+            end = parse_date(&caps["end"], year + 1, true)?.0;
         }
-    }
-
-    if !find {
-        // If no entry found for the user, create a new one
-        let now = chrono::Utc::now();
-        let new_entry = RoamingSubmitHistory {
-            user_id,
-            earliest_submit: now.to_rfc3339(),
-            submit_count: 1,
-            block: false,
-        };
-        submit_history_to_write.push(new_entry);
-    }
-
-    let write_result = write_user_roaming_submit_history(&tx_filerequest, submit_history_to_write).await;
-    match write_result {
-        Ok(_) => {
-            tracing::info!("User roaming submit history updated successfully for user_id: {}", user_id);
+        if end < start {
+            return None;
         }
-        Err(e) => {
-            tracing::error!("Failed to write roaming submit history: {}", e);
-            let response = ApiResponse::error(format!("Failed to write roaming submit history: {}", e));
-            return response;
+        // written in December for a January trip
+        if end < now - chrono::Duration::days(30) && !start_has_year && !end_has_year {
+            year += 1;
+            continue;
         }
+        return Some((start, end));
     }
 
-    ApiResponse::ok(vec!["".to_string()])
+    None
 }
 
-async fn write_roaming_data_to_file(
+pub async fn write_roaming_data_to_file(
     tx_filerequest: &Arc<RwLock<tokio::sync::mpsc::Sender<FileRequest>>>,
     roaming_data: &Vec<UserRoamingData>,
 ) -> anyhow::Result<()> {
@@ -231,7 +192,7 @@ async fn write_roaming_data_to_file(
     }
 }
 
-async fn read_roaming_data(
+pub async fn read_roaming_data(
     tx_filerequest: &Arc<RwLock<tokio::sync::mpsc::Sender<FileRequest>>>
 ) -> anyhow::Result<Vec<UserRoamingData>> {
     let tx_filerequest = tx_filerequest.write().await;
@@ -268,23 +229,37 @@ async fn read_roaming_data(
     Ok(roaming_data)
 }
 
+/// Whether `user_id` already has an entry under another callsign, one entry per user
+pub fn owns_other_roaming(roaming_data: &[UserRoamingData], user_id: &str, callsign: &str) -> bool {
+    roaming_data.iter().any(|r| r.user_id == user_id && r.roaming_data.callsign != callsign)
+}
+
 pub async fn add_roaming(
     app_status: &Arc<AppStatus>,
     args: &String,
     payload: &MessageEvent
-) -> ApiResponse<Vec<String>> {
-    let mut response = ApiResponse::<Vec<String>>::empty();
+) -> Reply {
+    let mut response = Reply::empty();
 
     let args = match parse_input_flexible(args) {
         Some(parsed) => parsed,
         None => {
-            return ApiResponse::<Vec<String>>::error("无法解析输入喵，请确保格式为：<呼号> <网格1> [网格2 ...] [备注]，呼号和网格间用空格分隔，多个网格间也用空格分隔，呼号可以使用'/'喵\n备注信息可选，建议输入备注信息并添加漫游起止时间，暂时不支持换行>_\n但是由于目前验证机制不成熟，需要确保你输入的呼号包含在你的群昵称内".to_string());
+            return Reply::error("无法解析输入喵，请确保格式为：<呼号> <网格1> [网格2 ...] [备注]，呼号和网格间用空格分隔，多个网格间也用空格分隔，呼号可以使用'/'喵\n备注信息可选，建议输入备注信息并添加漫游起止时间(如 8.1-8.5 或 2025-08-01 12:00~2025-08-05)，过期后会自动归档，暂时不支持换行>_\n但是由于目前验证机制不成熟，需要确保你输入的呼号包含在你的群昵称内".to_string());
         }
     };
 
     let callsign = args.callsign;
     let grid = args.grids;
     let info = args.remark;
+    let window = info.as_deref().and_then(|remark| parse_roaming_window(remark, Utc::now()));
+    if window.is_some_and(|(_, end)| end < Utc::now()) {
+        return ApiResponse::error("漫游结束时间已经过去了喵，请检查备注中的起止时间".to_string());
+    }
+    // the default end is stored as well, an entry without one predates windows
+    let (start, end) = match window {
+        Some((start, end)) => (Some(start.to_rfc3339()), Some(end.to_rfc3339())),
+        None => (None, Some((Utc::now() + chrono::Duration::days(ROAMING_DEFAULT_VALIDITY_DAYS)).to_rfc3339())),
+    };
 
     tracing::info!("Adding roaming data: {} {} {:?}", callsign, grid, info);
 
@@ -293,24 +268,26 @@ pub async fn add_roaming(
         config_guard.bot_config.admin_id.clone()
     };
 
-    let submit_history_response = user_roaming_submit_history_handler(payload, &app_status.file_tx, &admin_id).await;
-    if !submit_history_response.success {
-        return submit_history_response;
+    if !is_valid_callsign(&callsign) {
+        return Reply::error(format!("{}不是有效的呼号喵", callsign));
     }
 
     if !callsign_auth(&callsign, payload, &admin_id) {
         return ApiResponse::error("Rinko无法验证你的身份喵");
     }
 
-    let grids = grid.split_whitespace().collect::<Vec<&str>>();
-    if grids.is_empty() {
-        return ApiResponse::<Vec<String>>::error("请提供漫游网格喵".to_string());
+    if grid.split_whitespace().next().is_none() {
+        return Reply::error("请提供漫游网格喵".to_string());
     }
-    for g in &grids {
-        if !is_valid_maidenhead_grid(g) {
-            return ApiResponse::<Vec<String>>::error(format!("{}不是有效的梅登黑格网格喵", g));
+    // stored as `OM89ab` like an import, whatever case was typed
+    let mut grids = Vec::new();
+    for g in grid.split_whitespace() {
+        match normalize_grid(g) {
+            Some(g) => grids.push(g),
+            None => return Reply::error(format!("{}不是有效的梅登黑格网格喵", g)),
         }
     }
+    let grid = grids.join(" ");
 
     let tx_filerequest = app_status.file_tx.clone();
     let update_time = format!("{} BJT", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
@@ -321,6 +298,7 @@ pub async fn add_roaming(
         user_id.to_string()
     };
 
+    let _roaming_guard = ROAMING_DATA_LOCK.lock().await;
     let mut roaming_data = match read_roaming_data(&tx_filerequest).await {
         Ok(data) => data,
         Err(e) => {
@@ -329,24 +307,30 @@ pub async fn add_roaming(
         }
     };
 
-    if let Some(existing) = roaming_data.iter_mut().find(|r| r.roaming_data.callsign.contains(&callsign)) {
+    let mut previous = None;
+    if let Some(existing) = roaming_data.iter_mut().find(|r| r.roaming_data.callsign == callsign) {
         // check if the update comes from another user
         if existing.user_id != user_id_string && user_id_string != "Admin" {
             return ApiResponse::error("你是谁？请规范使用Rinko".to_string());
         }
+        previous = Some(existing.clone());
         existing.roaming_data.grid = grid.clone().into();
         existing.roaming_data.remark = info.map(|s| s.into());
+        existing.roaming_data.start = start;
+        existing.roaming_data.end = end;
         existing.submit_time = update_time;
-        response.data = Some(vec![format!("{}的漫游信息已更新为: {}", callsign, grid)]);
+        response.data = Some(vec![Segment::text(format!("{}的漫游信息已更新为: {}", callsign, grid))]);
     } else {
         // check if the user owns another data as another callsign
-        if roaming_data.iter().any(|r| r.user_id == user_id.to_string() && r.roaming_data.callsign != callsign) {
+        if owns_other_roaming(&roaming_data, &user_id.to_string(), &callsign) {
             return ApiResponse::error("你不能同时拥有多个漫游信息喵".to_string());
         }
         let new_roaming_data = RoamingData {
             callsign: callsign.clone(),
             grid: grid.clone().into(),
             remark: info.map(|s| s.into()),
+            start,
+            end,
         };
         let new_data = UserRoamingData {
             user_id: user_id.to_string(),   // use admin's id instead of "Admin"
@@ -354,7 +338,18 @@ pub async fn add_roaming(
             roaming_data: new_roaming_data,
         };
         roaming_data.push(new_data);
-        response.data = Some(vec![format!("{}的漫游信息已添加: {}", callsign, grid)]);
+        response.data = Some(vec![Segment::text(format!("{}的漫游信息已添加: {}", callsign, grid))]);
+    }
+
+    if let Some(data) = response.data.as_mut() {
+        let window_text = roaming_data
+            .iter()
+            .find(|r| r.roaming_data.callsign == callsign)
+            .and_then(|r| r.window_text());
+        match window_text {
+            Some(window_text) => data.push(Segment::text(format!("漫游时间: {}", window_text))),
+            None => data.push(Segment::text(format!("没有从备注中识别到起止时间，将在{}天后自动归档喵", ROAMING_DEFAULT_VALIDITY_DAYS))),
+        }
     }
 
     // sort roaming data by submit time, descending
@@ -368,6 +363,15 @@ pub async fn add_roaming(
         }
     }
 
+    // announcements and subscriber mentions should not hold up the reply
+    let (entry, others): (Vec<UserRoamingData>, Vec<UserRoamingData>) = roaming_data
+        .iter()
+        .cloned()
+        .partition(|r| previous.as_ref().map_or(r.roaming_data.callsign == callsign, |p| p.roaming_data.callsign == r.roaming_data.callsign));
+    if let Some(entry) = entry.into_iter().next() {
+        tokio::spawn(publish_roaming_change(Arc::clone(app_status), entry, previous, others));
+    }
+
     match render::render_roaming_data(&roaming_data).await {
         Ok(_) => {}
         Err(e) => {
@@ -385,8 +389,8 @@ pub async fn remove_roaming(
     app_status: &Arc<AppStatus>,
     args: &String,
     payload: &MessageEvent
-) -> ApiResponse<Vec<String>> {
-    let mut response = ApiResponse::<Vec<String>>::empty();
+) -> Reply {
+    let mut response = Reply::empty();
 
     // Args: remove <callsign>
     let callsign = match args.split_whitespace().nth(1).map(|s| s.to_uppercase()) {
@@ -407,6 +411,7 @@ pub async fn remove_roaming(
     }
 
     let tx_filerequest = app_status.file_tx.clone();
+    let _roaming_guard = ROAMING_DATA_LOCK.lock().await;
     let roaming_data = match read_roaming_data(&tx_filerequest).await {
         Ok(data) => data,
         Err(e) => {
@@ -442,7 +447,7 @@ pub async fn remove_roaming(
     match write_roaming_data_to_file(&tx_filerequest, &filtered_data).await {
         Ok(_) => {
             response.success = true;
-            response.data = Some(vec![format!("{}的漫游信息已删除喵", callsign)]);
+            response.data = Some(vec![Segment::text(format!("{}的漫游信息已删除喵", callsign))]);
         }
         Err(e) => {
             tracing::error!("Failed to write roaming data: {}", e);
@@ -462,20 +467,98 @@ pub async fn remove_roaming(
     response
 }
 
+#[derive(Debug, Clone, PartialEq)]
+enum RoamingQuery {
+    /// callsign substring
+    Callsign(String),
+    /// `OM89` or `OM89ab`, matches entries in the same square/subsquare
+    Grid(String),
+    /// `OM`
+    Field(String),
+    /// `within 500km [of OM89]`, defaults to the user's last reported grid
+    Within { km: f64, grid: Option<String> },
+    /// `in <sat>`, inside the current footprint of the satellite
+    InFootprint(String),
+}
+
+fn parse_roaming_query(args: &str) -> Option<RoamingQuery> {
+    // Args: list <query>
+    let tokens: Vec<&str> = args.split_whitespace().skip(1).collect();
+    let first = tokens.first()?;
+
+    match first.to_lowercase().as_str() {
+        "within" | "near" | "附近" => {
+            let km = tokens.get(1)?.to_lowercase().trim_end_matches("km").parse::<f64>().ok()?;
+            // `km` may be its own token, the grid follows an optional `of`/`from`
+            let grid = tokens[2..]
+                .iter()
+                .find(|t| !matches!(t.to_lowercase().as_str(), "km" | "of" | "from"))
+                .map(|g| g.to_string());
+            Some(RoamingQuery::Within { km, grid })
+        }
+        "in" => {
+            let satellite = tokens[1..].join(" ");
+            if satellite.is_empty() {
+                return None;
+            }
+            Some(RoamingQuery::InFootprint(satellite))
+        }
+        _ if first.len() == 2 && first.chars().all(|c| ('A'..='R').contains(&c.to_ascii_uppercase())) => {
+            Some(RoamingQuery::Field(first.to_uppercase()))
+        }
+        _ if is_valid_grid_any_case(first) => Some(RoamingQuery::Grid(first.to_string())),
+        _ => Some(RoamingQuery::Callsign(first.to_uppercase())),
+    }
+}
+
+/// Grid the user reported from most recently, then their own roaming entry
+async fn user_reference_grid(
+    app_status: &Arc<AppStatus>,
+    roaming_data: &[UserRoamingData],
+    user_id: u64,
+) -> Option<String> {
+    let audit_grid = match read_audit_log(app_status).await {
+        Ok(entries) => entries
+            .into_iter()
+            .rev()
+            .filter(|e| e.actor.as_ref().is_some_and(|a| a.user_id == user_id))
+            .find_map(|e| e.grid.filter(|g| is_valid_grid_any_case(g))),
+        Err(e) => {
+            tracing::warn!("Failed to read audit log for reference grid: {}", e);
+            None
+        }
+    };
+
+    audit_grid.or_else(|| {
+        roaming_data
+            .iter()
+            .find(|r| r.user_id == user_id.to_string())
+            .and_then(|r| r.roaming_data.grid.split_whitespace().next().map(|g| g.to_string()))
+    })
+}
+
+/// Shortest distance from the point to any of the entry's grids
+pub fn roaming_distance_km(item: &UserRoamingData, from: (f64, f64)) -> Option<f64> {
+    item.roaming_data
+        .grid
+        .split_whitespace()
+        .filter_map(maidenhead_to_lat_lon)
+        .map(|to| great_circle_distance_km(from, to))
+        .min_by(|a, b| a.total_cmp(b))
+}
+
 pub async fn list_roaming(
     app_status: &Arc<AppStatus>,
     args: &String,
-) -> ApiResponse<Vec<String>> {
-    let mut response = ApiResponse::<Vec<String>>::empty();
-
-    // Args: list [Callsign]/[Grid]
-    let keywords_filter = args.split_whitespace().nth(1).map(|s| s.to_uppercase());
-    if keywords_filter.is_none() {
-        response.data = Some(vec!["file:///server_runtime_data/pic/roaming_list.png".to_string()]);
-        response.success = true;
-        response.message = Some("image".to_string());
-        return response;
-    }
+    payload: &MessageEvent,
+) -> Reply {
+    // Args: list [callsign | grid | field | within <N>km [of <grid>] | in <sat>]
+    let Some(query) = parse_roaming_query(args) else {
+        if args.split_whitespace().nth(1).is_some() {
+            return ApiResponse::error("无法解析查询喵，可以使用: <呼号> | <网格> | <大网格如OM> | within <N>km [of <网格>] | in <卫星>".to_string());
+        }
+        return Reply::image(render::ROAMING_LIST_PIC_PATH);
+    };
 
     let tx_filerequest = app_status.file_tx.clone();
     let roaming_data = match read_roaming_data(&tx_filerequest).await {
@@ -486,37 +569,235 @@ pub async fn list_roaming(
         }
     };
 
-    // filter roaming data by callsign
-    let filtered_data = if let Some(callsign) = keywords_filter {
-        roaming_data.into_iter().filter(|r| r.roaming_data.callsign.contains(&callsign)).collect()
-    } else if let Some(grid) = keywords_filter {
-        roaming_data.into_iter().filter(|r| r.roaming_data.grid.contains(&grid)).collect()
-    } else {
-        roaming_data
+    // reference point for sorting, (lat, lon)
+    let mut header = None;
+    let (filtered_data, reference): (Vec<UserRoamingData>, Option<(f64, f64)>) = match query {
+        RoamingQuery::Callsign(callsign) => (
+            roaming_data.into_iter().filter(|r| r.roaming_data.callsign.contains(&callsign)).collect(),
+            None,
+        ),
+        RoamingQuery::Grid(grid) => {
+            let grid_upper = grid.to_uppercase();
+            let filtered = roaming_data
+                .into_iter()
+                .filter(|r| r.roaming_data.grid.split_whitespace().any(|g| {
+                    let g = g.to_uppercase();
+                    // OM89 matches OM89ab, OM89ab matches OM89ab and OM89
+                    g.starts_with(&grid_upper) || grid_upper.starts_with(&g)
+                }))
+                .collect();
+            (filtered, maidenhead_to_lat_lon(&grid))
+        }
+        RoamingQuery::Field(field) => {
+            let (in_field, others): (Vec<UserRoamingData>, Vec<UserRoamingData>) = roaming_data
+                .into_iter()
+                .partition(|r| r.roaming_data.grid.split_whitespace().any(|g| g.to_uppercase().starts_with(&field)));
+            // `BA`, `BG` and `BH` are callsign prefixes as well as fields
+            if in_field.is_empty() {
+                (others.into_iter().filter(|r| r.roaming_data.callsign.contains(&field)).collect(), None)
+            } else {
+                (in_field, None)
+            }
+        }
+        RoamingQuery::Within { km, grid } => {
+            let grid = match grid {
+                Some(grid) => grid,
+                None => match user_reference_grid(app_status, &roaming_data, payload.user_id).await {
+                    Some(grid) => grid,
+                    None => return ApiResponse::error("不知道你在哪个网格喵，请使用 within <N>km of <网格>".to_string()),
+                },
+            };
+            let Some(center) = maidenhead_to_lat_lon(&grid) else {
+                return ApiResponse::error(format!("{}不是有效的梅登黑格网格喵", grid));
+            };
+            header = Some(format!("距离 {} {}km 以内:", grid, km));
+            let filtered = roaming_data
+                .into_iter()
+                .filter(|r| roaming_distance_km(r, center).is_some_and(|d| d <= km))
+                .collect();
+            (filtered, Some(center))
+        }
+        RoamingQuery::InFootprint(satellite) => {
+            let footprint = match fetch_sat_footprint(app_status, &satellite).await {
+                Ok(footprint) => footprint,
+                Err(e) => return ApiResponse::error(format!("获取卫星位置失败喵: {}", e)),
+            };
+            let center = (footprint.lat, footprint.lon);
+            header = Some(format!(
+                "{} 当前星下点 {:.1}, {:.1}，高度 {:.0}km，覆盖半径 {:.0}km:",
+                footprint.name, footprint.lat, footprint.lon, footprint.altitude, footprint.radius
+            ));
+            let filtered = roaming_data
+                .into_iter()
+                .filter(|r| roaming_distance_km(r, center).is_some_and(|d| d <= footprint.radius))
+                .collect();
+            (filtered, Some(center))
+        }
     };
 
-    // response.data = Some(filtered_data.into_iter().map(|r| format!("{}:\n网格: {}\n备注: {:?}", r.callsign, r.grid, r.info)).collect());
+    let now = Utc::now();
     let mut data = Vec::new();
-    for r in filtered_data {
-        let formated_string = format!(
-            "{}:\n网格: {}\n提交时间: {}\n",
-            r.roaming_data.callsign,
-            r.roaming_data.grid,
-            r.submit_time,
-        );
-        if let Some(info) = r.roaming_data.remark {
-            data.push(format!("{}备注: {}\n", formated_string, info));
-        } else {
-            data.push(formated_string);
+    let mut found = false;
+    data.extend(header);
+    for (state, title) in [(RoamingState::Active, "正在漫游:"), (RoamingState::Upcoming, "即将开始:")] {
+        let mut section: Vec<(&UserRoamingData, Option<f64>)> = filtered_data
+            .iter()
+            .filter(|r| r.state(now) == state)
+            .map(|r| (r, reference.and_then(|from| roaming_distance_km(r, from))))
+            .collect();
+        if section.is_empty() {
+            continue;
+        }
+        // nearest first, entries without a usable grid last
+        section.sort_by(|a, b| match (a.1, b.1) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+
+        found = true;
+        data.push(title.to_string());
+        for (r, distance) in section {
+            let mut formated_string = format!(
+                "{}:\n网格: {}\n提交时间: {}\n",
+                r.roaming_data.callsign,
+                r.roaming_data.grid,
+                r.submit_time,
+            );
+            if let Some(distance) = distance {
+                formated_string.push_str(&format!("距离: {:.0}km\n", distance));
+            }
+            if let Some(window_text) = r.window_text() {
+                formated_string.push_str(&format!("漫游时间: {}\n", window_text));
+            }
+            if let Some(region) = callsign_region_text(&r.roaming_data.callsign) {
+                formated_string.push_str(&format!("地区: {}\n", region));
+            }
+            if let Some(info) = &r.roaming_data.remark {
+                data.push(format!("{}备注: {}\n", formated_string, info));
+            } else {
+                data.push(formated_string);
+            }
         }
     }
 
-    if data.is_empty() {
+    if !found {
         data.push("没有找到任何相关漫游信息喵".to_string());
     }
 
-    response.data = Some(data);
-    response
+    Reply::lines(data)
+}
+
+/// Roaming list, empty before the first submission
+pub async fn load_roaming_list(
+    app_status: &AppStatus,
+) -> anyhow::Result<Vec<UserRoamingData>> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), USER_ROAMING_DATA.to_string()).await {
+        return Ok(Vec::new());
+    }
+    read_roaming_data(&tx_filerequest).await
+}
+
+/// `/roaming map`, rendered on request so the active/upcoming split is current
+pub async fn roaming_map(
+    app_status: &Arc<AppStatus>,
+) -> Reply {
+    let roaming_data = match load_roaming_list(app_status).await {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to read roaming data: {}", e);
+            return ApiResponse::error(format!("文件读取失败: {}", e));
+        }
+    };
+
+    match render::render_roaming_map(&roaming_data).await {
+        Ok(_) => Reply::image(render::ROAMING_MAP_PIC_PATH),
+        Err(e) => ApiResponse::error(format!("渲染漫游地图失败: {}", e)),
+    }
+}
+
+pub async fn read_roaming_archive(
+    app_status: &AppStatus,
+) -> anyhow::Result<Vec<UserRoamingData>> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), USER_ROAMING_ARCHIVE.to_string()).await {
+        return Ok(Vec::new());
+    }
+
+    match load_file(tx_filerequest, USER_ROAMING_ARCHIVE.to_string(), FileFormat::Json).await? {
+        FileData::Json(data) => serde_json::from_value(data)
+            .map_err(|e| anyhow::anyhow!("Failed to parse roaming archive: {}", e)),
+        _ => Err(anyhow::anyhow!("Unexpected file format received")),
+    }
+}
+
+/// Entries saved before windows were recorded have no end time, they get the window in their remark
+/// (read as of the submission) or `ROAMING_DEFAULT_VALIDITY_DAYS` from `now`, so the first archive run
+/// after upgrading doesn't move every entry older than a week at once. Returns how many were updated
+pub fn migrate_legacy_windows(
+    roaming_data: &mut [UserRoamingData],
+    now: DateTime<Utc>,
+) -> usize {
+    let mut migrated = 0;
+    for entry in roaming_data.iter_mut().filter(|r| r.roaming_data.end.is_none()) {
+        let submitted = entry.submitted_at().unwrap_or(now);
+        let window = entry.roaming_data.remark.as_deref().and_then(|r| parse_roaming_window(r, submitted));
+        let (start, end) = match window {
+            Some((start, end)) => (Some(start), end),
+            None => (None, now + chrono::Duration::days(ROAMING_DEFAULT_VALIDITY_DAYS)),
+        };
+        entry.roaming_data.start = entry.roaming_data.start.take().or(start.map(|t| t.to_rfc3339()));
+        entry.roaming_data.end = Some(end.to_rfc3339());
+        migrated += 1;
+    }
+    migrated
+}
+
+/// Move expired entries to the archive file, returns how many were moved
+pub async fn archive_expired_roaming(
+    app_status: &Arc<AppStatus>,
+) -> anyhow::Result<usize> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), USER_ROAMING_DATA.to_string()).await {
+        return Ok(0);
+    }
+
+    let now = Utc::now();
+    let _roaming_guard = ROAMING_DATA_LOCK.lock().await;
+    let mut roaming_data = read_roaming_data(&tx_filerequest).await?;
+    let migrated = migrate_legacy_windows(&mut roaming_data, now);
+    if migrated > 0 {
+        tracing::info!("Gave {} roaming entries without an end time a window", migrated);
+    }
+    let (expired, remaining): (Vec<UserRoamingData>, Vec<UserRoamingData>) = roaming_data
+        .into_iter()
+        .partition(|r| r.state(now) == RoamingState::Expired);
+    if expired.is_empty() {
+        if migrated > 0 {
+            write_roaming_data_to_file(&tx_filerequest, &remaining).await?;
+        }
+        return Ok(0);
+    }
+
+    let mut archive = read_roaming_archive(app_status).await?;
+    let expired_count = expired.len();
+    archive.extend(expired);
+
+    // archive first and wait for it, a failed archive write keeps the entries in the live list
+    write_file(
+        tx_filerequest.clone(),
+        USER_ROAMING_ARCHIVE.to_string(),
+        &FileData::Json(serde_json::to_value(&archive)?),
+    ).await?;
+    write_roaming_data_to_file(&tx_filerequest, &remaining).await?;
+
+    if let Err(e) = render::render_roaming_data(&remaining).await {
+        tracing::error!("Failed to render roaming data: {}", e);
+    }
+
+    Ok(expired_count)
 }
 
 #[derive(Debug)]
@@ -570,3 +851,124 @@ fn parse_input_flexible(input: &str) -> Option<ParsedInput> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BJT wall clock as UTC
+    fn bjt(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(year, month, day, hour, minute, second)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn entry(submit_time: &str, remark: Option<&str>, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> UserRoamingData {
+        UserRoamingData {
+            user_id: "10000".to_string(),
+            submit_time: submit_time.to_string(),
+            roaming_data: RoamingData {
+                callsign: "BG2XXX".to_string(),
+                grid: "OM89".to_string(),
+                remark: remark.map(|r| r.to_string()),
+                start: start.map(|t| t.to_rfc3339()),
+                end: end.map(|t| t.to_rfc3339()),
+            },
+        }
+    }
+
+    #[test]
+    fn parses_short_dates_as_whole_days() {
+        let now = bjt(2025, 7, 20, 12, 0, 0);
+        assert_eq!(
+            parse_roaming_window("自驾 8.1-8.5 欢迎联络", now),
+            Some((bjt(2025, 8, 1, 0, 0, 0), bjt(2025, 8, 5, 23, 59, 59)))
+        );
+        assert_eq!(
+            parse_roaming_window("8月1日 12:00 至 8月5日", now),
+            Some((bjt(2025, 8, 1, 12, 0, 0), bjt(2025, 8, 5, 23, 59, 59)))
+        );
+    }
+
+    #[test]
+    fn parses_full_dates() {
+        let now = bjt(2025, 7, 20, 12, 0, 0);
+        assert_eq!(
+            parse_roaming_window("2025-08-01 12:00 ~ 2025-08-05 18:30", now),
+            Some((bjt(2025, 8, 1, 12, 0, 0), bjt(2025, 8, 5, 18, 30, 0)))
+        );
+    }
+
+    #[test]
+    fn rolls_the_year_forward() {
+        // across new year
+        let now = bjt(2025, 12, 20, 12, 0, 0);
+        assert_eq!(
+            parse_roaming_window("12.30-1.3", now),
+            Some((bjt(2025, 12, 30, 0, 0, 0), bjt(2026, 1, 3, 23, 59, 59)))
+        );
+        // written in December for a January trip
+        assert_eq!(
+            parse_roaming_window("1.10-1.15", now),
+            Some((bjt(2026, 1, 10, 0, 0, 0), bjt(2026, 1, 15, 23, 59, 59)))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_windows() {
+        let now = bjt(2025, 7, 20, 12, 0, 0);
+        assert_eq!(parse_roaming_window("没有日期", now), None);
+        assert_eq!(parse_roaming_window("2025-08-05 ~ 2025-08-01", now), None);
+        assert_eq!(parse_roaming_window("2.30-3.2", now), None);
+        assert_eq!(parse_roaming_window("8.1-8.5 25:00", now), None);
+    }
+
+    #[test]
+    fn effective_window_falls_back_to_the_submission() {
+        let submitted = bjt(2025, 8, 1, 12, 0, 0);
+        let window = entry("2025-08-01 12:00:00 BJT", None, None, None).effective_window();
+        assert_eq!(window, Some((submitted, submitted + chrono::Duration::days(ROAMING_DEFAULT_VALIDITY_DAYS))));
+
+        let end = bjt(2025, 8, 3, 0, 0, 0);
+        let window = entry("2025-08-01 12:00:00 BJT", None, None, Some(end)).effective_window();
+        assert_eq!(window, Some((submitted, end)));
+    }
+
+    #[test]
+    fn recognises_grids_of_any_case() {
+        for grid in ["OM89", "om89", "OM89ab", "OM89AB"] {
+            assert_eq!(parse_roaming_query(&format!("list {}", grid)), Some(RoamingQuery::Grid(grid.to_string())));
+        }
+        assert!(maidenhead_to_lat_lon("OM89AB").is_some());
+        assert_eq!(parse_roaming_query("list om"), Some(RoamingQuery::Field("OM".to_string())));
+        assert_eq!(parse_roaming_query("list bg2xxx"), Some(RoamingQuery::Callsign("BG2XXX".to_string())));
+        assert_eq!(
+            parse_roaming_query("list within 500 km of OM89AB"),
+            Some(RoamingQuery::Within { km: 500.0, grid: Some("OM89AB".to_string()) })
+        );
+    }
+
+    #[test]
+    fn legacy_entries_get_an_end_time() {
+        let now = bjt(2025, 9, 1, 12, 0, 0);
+        let new_end = bjt(2025, 9, 5, 0, 0, 0);
+        let mut roaming_data = vec![
+            entry("2025-07-01 12:00:00 BJT", None, None, None),
+            entry("2025-07-01 12:00:00 BJT", Some("7.2-7.4"), None, None),
+            entry("2025-08-30 12:00:00 BJT", None, None, Some(new_end)),
+        ];
+
+        assert_eq!(migrate_legacy_windows(&mut roaming_data, now), 2);
+        // no window, a week from the upgrade rather than from the submission
+        assert_eq!(roaming_data[0].effective_window().unwrap().1, now + chrono::Duration::days(ROAMING_DEFAULT_VALIDITY_DAYS));
+        assert_eq!(roaming_data[0].state(now), RoamingState::Active);
+        // the remark is read as of the submission, the trip is over
+        assert_eq!(roaming_data[1].effective_window(), Some((bjt(2025, 7, 2, 0, 0, 0), bjt(2025, 7, 4, 23, 59, 59))));
+        assert_eq!(roaming_data[1].state(now), RoamingState::Expired);
+        // entries with an end are left alone
+        assert_eq!(roaming_data[2].effective_window().unwrap().1, new_end);
+        assert_eq!(migrate_legacy_windows(&mut roaming_data, now), 0);
+    }
+}
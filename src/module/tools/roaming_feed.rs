@@ -0,0 +1,382 @@
+use crate::{
+    app_status::AppStatus,
+    fs::handler::{check_file_exists, load_file, write_file, FileData, FileFormat},
+    module::{
+        prelude::{grid_square, is_valid_grid_any_case},
+        tools::roaming::{read_roaming_archive, UserRoamingData},
+    },
+    msg::{
+        group_msg::{send_group_message_to_groups, send_group_message_with_at, send_private_messages},
+        prelude::MessageEvent,
+    },
+    response::{ApiResponse, Reply},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+
+/// Every new or changed activation, digests are built from here
+const ROAMING_FEED: &str = "runtime_data/roaming_feed.json";
+const ROAMING_SUBSCRIPTIONS: &str = "runtime_data/roaming_subscriptions.json";
+const FEED_KEEP_DAYS: i64 = 30;
+const MAX_SUBSCRIPTION_TARGETS: usize = 20;
+
+lazy_static::lazy_static! {
+    /// Held from reading the feed to writing it back, changes are published from spawned tasks
+    /// and the digest marks them while new ones may come in
+    static ref ROAMING_FEED_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+    /// Same for the subscription list
+    static ref ROAMING_SUBSCRIPTIONS_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RoamingChangeKind {
+    New,
+    Updated,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RoamingChange {
+    /// RFC3339
+    pub time: String,
+    pub kind: RoamingChangeKind,
+    pub callsign: String,
+    pub grids: Vec<String>,
+    /// 4-char squares nobody activated before, from live and archived entries
+    pub rare_grids: Vec<String>,
+    pub window: Option<String>,
+    /// included in a daily digest already
+    #[serde(default)]
+    pub digested: bool,
+}
+
+impl RoamingChange {
+    fn to_display_string(&self) -> String {
+        let title = match self.kind {
+            RoamingChangeKind::New => "新漫游",
+            RoamingChangeKind::Updated => "漫游更新",
+        };
+        let mut text = format!("[{}] {}: {}", title, self.callsign, self.grids.join(" "));
+        if let Some(window) = &self.window {
+            text.push_str(&format!("\n时间: {}", window));
+        }
+        if !self.rare_grids.is_empty() {
+            text.push_str(&format!("\n稀有网格: {}", self.rare_grids.join(" ")));
+        }
+        text
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RoamingSubscription {
+    pub user_id: u64,
+    /// notifications are sent to the group the subscription was made in, 0 for private
+    pub group_id: u64,
+    /// uppercase field (`OM`) or square (`OM89`)
+    pub targets: Vec<String>,
+}
+
+async fn read_json_list<T: serde::de::DeserializeOwned>(
+    app_status: &AppStatus,
+    path: &str,
+) -> anyhow::Result<Vec<T>> {
+    let tx_filerequest = app_status.file_tx.clone();
+    if !check_file_exists(tx_filerequest.clone(), path.to_string()).await {
+        return Ok(Vec::new());
+    }
+
+    match load_file(tx_filerequest, path.to_string(), FileFormat::Json).await? {
+        FileData::Json(data) => serde_json::from_value(data)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path, e)),
+        _ => Err(anyhow::anyhow!("Unexpected file format received")),
+    }
+}
+
+async fn write_json_list<T: Serialize>(
+    app_status: &AppStatus,
+    path: &str,
+    data: &[T],
+) -> anyhow::Result<()> {
+    write_file(
+        app_status.file_tx.clone(),
+        path.to_string(),
+        &FileData::Json(serde_json::to_value(data)?),
+    ).await
+}
+
+/// Called after `add_roaming` saved the entry
+/// - records the change in the feed
+/// - announces it right away unless digest mode is on
+/// - mentions users subscribed to one of the new grids
+pub async fn publish_roaming_change(
+    app_status: Arc<AppStatus>,
+    entry: UserRoamingData,
+    previous: Option<UserRoamingData>,
+    others: Vec<UserRoamingData>,
+) {
+    let grids: Vec<String> = entry.roaming_data.grid.split_whitespace().map(|g| g.to_string()).collect();
+    let kind = match &previous {
+        Some(previous) => {
+            // a remark-only edit is not worth an announcement
+            if previous.roaming_data.grid == entry.roaming_data.grid
+                && previous.window_text() == entry.window_text()
+            {
+                return;
+            }
+            RoamingChangeKind::Updated
+        }
+        None => RoamingChangeKind::New,
+    };
+    let previous_squares: HashSet<String> = previous
+        .iter()
+        .flat_map(|p| p.roaming_data.grid.split_whitespace().filter_map(grid_square))
+        .collect();
+    let new_squares: Vec<String> = grids
+        .iter()
+        .filter_map(|g| grid_square(g))
+        .filter(|g| !previous_squares.contains(g))
+        .collect();
+
+    let mut known_squares: HashSet<String> = others
+        .iter()
+        .flat_map(|r| r.roaming_data.grid.split_whitespace().filter_map(grid_square))
+        .collect();
+    match read_roaming_archive(&app_status).await {
+        Ok(archive) => known_squares.extend(
+            archive.iter().flat_map(|r| r.roaming_data.grid.split_whitespace().filter_map(grid_square)),
+        ),
+        Err(e) => tracing::warn!("Failed to read roaming archive: {}", e),
+    }
+
+    let mut seen = HashSet::new();
+    let rare_grids: Vec<String> = new_squares
+        .iter()
+        .filter(|g| !known_squares.contains(*g) && seen.insert(g.to_string()))
+        .cloned()
+        .collect();
+
+    let change = RoamingChange {
+        time: Utc::now().to_rfc3339(),
+        kind,
+        callsign: entry.roaming_data.callsign.clone(),
+        grids,
+        rare_grids,
+        window: entry.window_text(),
+        digested: false,
+    };
+
+    let announce_config = app_status.config.read().await.roaming_announce_config.clone();
+    if let Some(config) = announce_config.as_ref().filter(|c| !c.digest) {
+        let response = Reply::text(change.to_display_string());
+        send_group_message_to_groups(response, &config.group_id, &app_status).await;
+    }
+
+    if let Err(e) = notify_subscribers(&app_status, &change, &new_squares).await {
+        tracing::error!("Failed to notify roaming subscribers: {}", e);
+    }
+
+    let _feed_guard = ROAMING_FEED_LOCK.lock().await;
+    let mut feed: Vec<RoamingChange> = match read_json_list(&app_status, ROAMING_FEED).await {
+        Ok(feed) => feed,
+        Err(e) => {
+            tracing::error!("Failed to read roaming feed: {}", e);
+            return;
+        }
+    };
+    let now = Utc::now();
+    feed.retain(|c| {
+        DateTime::parse_from_rfc3339(&c.time)
+            .map(|t| now - t.with_timezone(&Utc) <= chrono::Duration::days(FEED_KEEP_DAYS))
+            .unwrap_or(false)
+    });
+    feed.push(change);
+    if let Err(e) = write_json_list(&app_status, ROAMING_FEED, &feed).await {
+        tracing::error!("Failed to write roaming feed: {}", e);
+    }
+}
+
+async fn notify_subscribers(
+    app_status: &Arc<AppStatus>,
+    change: &RoamingChange,
+    new_squares: &[String],
+) -> anyhow::Result<()> {
+    let subscriptions: Vec<RoamingSubscription> = read_json_list(app_status, ROAMING_SUBSCRIPTIONS).await?;
+
+    // group_id -> users to mention, subscriptions made in private are answered there
+    let mut mentions: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+    let mut private = Vec::new();
+    for subscription in &subscriptions {
+        let matched = subscription
+            .targets
+            .iter()
+            .any(|target| new_squares.iter().any(|square| square.starts_with(target.as_str())));
+        if !matched {
+            continue;
+        }
+        match subscription.group_id {
+            0 => private.push(subscription.user_id),
+            group_id => mentions.entry(group_id).or_default().push(subscription.user_id),
+        }
+    }
+
+    let text = format!("你订阅的网格有新漫游喵\n{}", change.to_display_string());
+    let groups = futures::future::join_all(
+        mentions.iter().map(|(group_id, users)| send_group_message_with_at(*group_id, users, &text, app_status)),
+    );
+    tokio::join!(groups, send_private_messages(&private, &text, app_status));
+    Ok(())
+}
+
+/// Daily summary of rare grids that showed up since the last digest
+pub async fn send_roaming_digest(
+    app_status: &Arc<AppStatus>,
+) -> anyhow::Result<()> {
+    let Some(config) = app_status.config.read().await.roaming_announce_config.clone() else {
+        return Ok(());
+    };
+
+    let feed_guard = ROAMING_FEED_LOCK.lock().await;
+    let mut feed: Vec<RoamingChange> = read_json_list(app_status, ROAMING_FEED).await?;
+    let pending: Vec<&RoamingChange> = feed.iter().filter(|c| !c.digested).collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    // one line per rare square, activators joined
+    let mut rare: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for change in &pending {
+        for grid in &change.rare_grids {
+            let activators = rare.entry(grid.clone()).or_default();
+            let activator = match &change.window {
+                Some(window) => format!("{} ({})", change.callsign, window),
+                None => change.callsign.clone(),
+            };
+            if !activators.contains(&activator) {
+                activators.push(activator);
+            }
+        }
+    }
+
+    for change in feed.iter_mut() {
+        change.digested = true;
+    }
+    write_json_list(app_status, ROAMING_FEED, &feed).await?;
+    // sending can take a while, new changes may be recorded meanwhile
+    drop(feed_guard);
+
+    if !rare.is_empty() {
+        let mut data = vec![format!("今日新增稀有网格 {} 个喵:", rare.len())];
+        data.extend(rare.iter().map(|(grid, activators)| format!("{}: {}", grid, activators.join(", "))));
+        send_group_message_to_groups(Reply::lines(data), &config.group_id, app_status).await;
+    }
+    Ok(())
+}
+
+/// `/roaming sub [网格|大网格 ...]`, without targets lists the current subscription
+pub async fn subscribe_roaming(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let mut targets = Vec::new();
+    for target in args.split_whitespace().skip(1) {
+        let is_field = target.len() == 2 && target.chars().all(|c| ('A'..='R').contains(&c.to_ascii_uppercase()));
+        if !is_field && !is_valid_grid_any_case(target) {
+            return ApiResponse::error(format!("{}不是有效的网格喵", target));
+        }
+        // subscriptions work on squares, finer grids are cut down
+        let Some(target) = (if is_field { Some(target.to_uppercase()) } else { grid_square(target) }) else {
+            continue;
+        };
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+
+    let _subscriptions_guard = ROAMING_SUBSCRIPTIONS_LOCK.lock().await;
+    let mut subscriptions: Vec<RoamingSubscription> = match read_json_list(app_status, ROAMING_SUBSCRIPTIONS).await {
+        Ok(data) => data,
+        Err(e) => return ApiResponse::error(format!("文件读取失败: {}", e)),
+    };
+    let index = subscriptions
+        .iter()
+        .position(|s| s.user_id == payload.user_id && s.group_id == payload.group_id);
+
+    if targets.is_empty() {
+        return match index.map(|i| &subscriptions[i]).filter(|s| !s.targets.is_empty()) {
+            Some(subscription) => Reply::text(format!("你订阅的网格: {}", subscription.targets.join(" "))),
+            None => Reply::text("你还没有订阅任何网格喵，使用 /roaming sub <网格|大网格> 订阅"),
+        };
+    }
+
+    let subscription = match index {
+        Some(i) => &mut subscriptions[i],
+        None => {
+            subscriptions.push(RoamingSubscription {
+                user_id: payload.user_id,
+                group_id: payload.group_id,
+                targets: Vec::new(),
+            });
+            subscriptions.last_mut().unwrap()
+        }
+    };
+    for target in targets {
+        if !subscription.targets.contains(&target) {
+            subscription.targets.push(target);
+        }
+    }
+    if subscription.targets.len() > MAX_SUBSCRIPTION_TARGETS {
+        return ApiResponse::error(format!("最多只能订阅 {} 个网格喵", MAX_SUBSCRIPTION_TARGETS));
+    }
+    let text = format!("已订阅: {}", subscription.targets.join(" "));
+
+    match write_json_list(app_status, ROAMING_SUBSCRIPTIONS, &subscriptions).await {
+        Ok(_) => Reply::text(text),
+        Err(e) => ApiResponse::error(format!("文件写入失败: {}", e)),
+    }
+}
+
+/// `/roaming unsub <网格|大网格 ...|all>`
+pub async fn unsubscribe_roaming(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let targets: Vec<String> = args.split_whitespace().skip(1).map(|t| t.to_uppercase()).collect();
+    if targets.is_empty() {
+        return ApiResponse::error("请提供要取消订阅的网格喵，或使用 /roaming unsub all".to_string());
+    }
+
+    let _subscriptions_guard = ROAMING_SUBSCRIPTIONS_LOCK.lock().await;
+    let mut subscriptions: Vec<RoamingSubscription> = match read_json_list(app_status, ROAMING_SUBSCRIPTIONS).await {
+        Ok(data) => data,
+        Err(e) => return ApiResponse::error(format!("文件读取失败: {}", e)),
+    };
+    let Some(subscription) = subscriptions
+        .iter_mut()
+        .find(|s| s.user_id == payload.user_id && s.group_id == payload.group_id)
+    else {
+        return ApiResponse::error("你还没有订阅任何网格喵".to_string());
+    };
+
+    if targets.iter().any(|t| t == "ALL") {
+        subscription.targets.clear();
+    } else {
+        // a field is kept as written, a grid is cut down to its square like in sub
+        subscription.targets.retain(|t| {
+            !targets.iter().any(|target| grid_square(target).as_ref().unwrap_or(target) == t)
+        });
+    }
+    let text = if subscription.targets.is_empty() {
+        "已取消全部订阅喵".to_string()
+    } else {
+        format!("剩余订阅: {}", subscription.targets.join(" "))
+    };
+    subscriptions.retain(|s| !s.targets.is_empty());
+
+    match write_json_list(app_status, ROAMING_SUBSCRIPTIONS, &subscriptions).await {
+        Ok(_) => Reply::text(text),
+        Err(e) => ApiResponse::error(format!("文件写入失败: {}", e)),
+    }
+}
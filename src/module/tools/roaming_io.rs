@@ -0,0 +1,529 @@
+use crate::{
+    app_status::AppStatus,
+    fs::handler::{write_file, FileData},
+    module::{
+        prelude::{is_valid_callsign, maidenhead_to_lat_lon, normalize_grid},
+        tools::{
+            render,
+            roaming::{load_roaming_list, parse_roaming_window, ROAMING_DEFAULT_VALIDITY_DAYS, write_roaming_data_to_file, RoamingData, RoamingState, UserRoamingData, IMPORTED_ROAMING_OWNER, ROAMING_DATA_LOCK},
+        },
+    },
+    msg::prelude::MessageEvent,
+    response::{ApiResponse, Reply},
+};
+use serde::Serialize;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+
+const ROAMING_EXPORT_PATH_PREFIX: &str = "runtime_data/export/roaming";
+const CSV_HEADER: &[&str] = &["callsign", "grid", "start", "end", "remark", "submit_time", "state"];
+/// RFC 5545 line length limit in octets
+const ICS_LINE_LIMIT: usize = 75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Ics,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_str(input: &str) -> Option<Self> {
+        match input.to_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "ics" | "ical" | "icalendar" => Some(ExportFormat::Ics),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ics => "ics",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ics => "text/calendar; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+}
+
+/// Public view of an entry, QQ ids are left out
+#[derive(Serialize, Debug)]
+pub struct RoamingExportEntry<'a> {
+    callsign: &'a str,
+    grids: Vec<&'a str>,
+    remark: Option<&'a str>,
+    /// RFC3339, falls back to the submission time
+    start: Option<String>,
+    /// RFC3339, falls back to the default validity
+    end: Option<String>,
+    submit_time: &'a str,
+    state: &'static str,
+}
+
+fn state_text(state: RoamingState) -> &'static str {
+    match state {
+        RoamingState::Active => "active",
+        RoamingState::Upcoming => "upcoming",
+        RoamingState::Expired => "expired",
+    }
+}
+
+fn export_entry(entry: &UserRoamingData, now: DateTime<Utc>) -> RoamingExportEntry<'_> {
+    let window = entry.effective_window();
+    RoamingExportEntry {
+        callsign: &entry.roaming_data.callsign,
+        grids: entry.roaming_data.grid.split_whitespace().collect(),
+        remark: entry.roaming_data.remark.as_deref(),
+        start: window.map(|(start, _)| start.to_rfc3339()),
+        end: window.map(|(_, end)| end.to_rfc3339()),
+        submit_time: &entry.submit_time,
+        state: state_text(entry.state(now)),
+    }
+}
+
+pub fn export_entries(roaming_data: &[UserRoamingData], now: DateTime<Utc>) -> Vec<RoamingExportEntry<'_>> {
+    roaming_data.iter().map(|r| export_entry(r, now)).collect()
+}
+
+pub fn export_roaming(
+    roaming_data: &[UserRoamingData],
+    format: ExportFormat,
+    now: DateTime<Utc>,
+) -> anyhow::Result<String> {
+    match format {
+        ExportFormat::Csv => Ok(roaming_to_csv(roaming_data, now)),
+        ExportFormat::Ics => Ok(roaming_to_ics(roaming_data, now)),
+        ExportFormat::Json => {
+            Ok(serde_json::to_string_pretty(&export_entries(roaming_data, now))?)
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn roaming_to_csv(roaming_data: &[UserRoamingData], now: DateTime<Utc>) -> String {
+    let mut csv = CSV_HEADER.join(",");
+    csv.push_str("\r\n");
+    for entry in roaming_data.iter().map(|r| export_entry(r, now)) {
+        let row = [
+            entry.callsign.to_string(),
+            entry.grids.join(" "),
+            entry.start.unwrap_or_default(),
+            entry.end.unwrap_or_default(),
+            entry.remark.unwrap_or_default().to_string(),
+            entry.submit_time.to_string(),
+            entry.state.to_string(),
+        ];
+        csv.push_str(&row.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// RFC 4180, quoted fields may contain separators, quotes and line breaks
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows.retain(|row| row.iter().any(|f| !f.trim().is_empty()));
+    rows
+}
+
+/// Escape TEXT values, RFC 5545 3.3.11
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold at 75 octets without splitting a UTF-8 character
+fn ics_line(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > ICS_LINE_LIMIT {
+            ics.push_str("\r\n ");
+            // the leading space counts towards the next line
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+fn ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn roaming_to_ics(roaming_data: &[UserRoamingData], now: DateTime<Utc>) -> String {
+    let mut ics = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//CiRCLE_sat_bot//Roaming//ZH",
+        "CALSCALE:GREGORIAN",
+        "METHOD:PUBLISH",
+        "X-WR-CALNAME:Rinko 漫游日历",
+    ] {
+        ics_line(&mut ics, line);
+    }
+
+    for entry in roaming_data {
+        let Some((start, end)) = entry.effective_window() else {
+            continue;
+        };
+        let data = &entry.roaming_data;
+        let grids = data.grid.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        ics_line(&mut ics, "BEGIN:VEVENT");
+        // stable across exports so calendar apps update instead of duplicating
+        ics_line(&mut ics, &format!("UID:{}-{}@circle-sat-bot", data.callsign.replace('/', "-"), ics_time(start)));
+        ics_line(&mut ics, &format!("DTSTAMP:{}", ics_time(now)));
+        ics_line(&mut ics, &format!("DTSTART:{}", ics_time(start)));
+        ics_line(&mut ics, &format!("DTEND:{}", ics_time(end)));
+        ics_line(&mut ics, &format!("SUMMARY:{}", ics_text(&format!("{} 漫游 {}", data.callsign, grids))));
+        ics_line(&mut ics, &format!("LOCATION:{}", ics_text(&grids)));
+        if let Some((lat, lon)) = data.grid.split_whitespace().next().and_then(maidenhead_to_lat_lon) {
+            ics_line(&mut ics, &format!("GEO:{:.4};{:.4}", lat, lon));
+        }
+        if let Some(remark) = &data.remark {
+            ics_line(&mut ics, &format!("DESCRIPTION:{}", ics_text(remark)));
+        }
+        ics_line(&mut ics, "END:VEVENT");
+    }
+
+    ics_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// Rows from an import, with the reason for every skipped line
+#[derive(Debug, Default)]
+pub struct RoamingImport {
+    pub entries: Vec<RoamingData>,
+    pub errors: Vec<String>,
+}
+
+/// Header row required, `callsign` and `grid` columns, `start`, `end` and `remark` optional
+/// - start/end take RFC3339 or the same BJT dates as the remark, e.g. `2025-08-01 12:00`
+/// - without start/end the window is read from the remark, or ends `ROAMING_DEFAULT_VALIDITY_DAYS` from now
+/// - extra columns are ignored, so an export can be imported again
+pub fn parse_roaming_csv(content: &str, now: DateTime<Utc>) -> anyhow::Result<RoamingImport> {
+    let rows = parse_csv(content);
+    let Some((header, rows)) = rows.split_first() else {
+        return Err(anyhow::anyhow!("CSV is empty"));
+    };
+    let column = |names: &[&str]| {
+        header.iter().position(|h| names.contains(&h.trim().to_lowercase().as_str()))
+    };
+    let (Some(callsign_col), Some(grid_col)) = (column(&["callsign", "呼号"]), column(&["grid", "grids", "网格"])) else {
+        return Err(anyhow::anyhow!("CSV header must contain callsign and grid columns"));
+    };
+    let start_col = column(&["start", "开始"]);
+    let end_col = column(&["end", "结束"]);
+    let remark_col = column(&["remark", "备注"]);
+
+    let mut import = RoamingImport::default();
+    for (index, row) in rows.iter().enumerate() {
+        // +2: 1-based and the header row
+        let line = index + 2;
+        let get = |col: Option<usize>| {
+            col.and_then(|c| row.get(c)).map(|f| f.trim()).filter(|f| !f.is_empty())
+        };
+
+        let Some(callsign) = get(Some(callsign_col)).map(|c| c.to_uppercase()) else {
+            import.errors.push(format!("第{}行: 缺少呼号", line));
+            continue;
+        };
+        if !is_valid_callsign(&callsign) {
+            import.errors.push(format!("第{}行: {}不是有效的呼号", line, callsign));
+            continue;
+        }
+        let raw_grids: Vec<&str> = get(Some(grid_col))
+            .unwrap_or_default()
+            .split(|c: char| c.is_whitespace() || c == ';' || c == '/')
+            .filter(|g| !g.is_empty())
+            .collect();
+        if raw_grids.is_empty() {
+            import.errors.push(format!("第{}行: 缺少网格", line));
+            continue;
+        }
+        // other loggers write `OM89AB`, stored as `OM89ab`
+        let grids: Vec<String> = match raw_grids.iter().map(|g| normalize_grid(g).ok_or(*g)).collect() {
+            Ok(grids) => grids,
+            Err(grid) => {
+                import.errors.push(format!("第{}行: {}不是有效的梅登黑格网格", line, grid));
+                continue;
+            }
+        };
+
+        let remark = get(remark_col).map(|r| r.to_string());
+        let window = match (get(start_col), get(end_col)) {
+            (Some(start), Some(end)) => {
+                let rfc3339 = |t: &str| DateTime::parse_from_rfc3339(t).ok().map(|t| t.with_timezone(&Utc));
+                match (rfc3339(start), rfc3339(end)) {
+                    (Some(start), Some(end)) => Some((start, end)),
+                    _ => parse_roaming_window(&format!("{} ~ {}", start, end), now),
+                }
+                .filter(|(start, end)| start <= end)
+                .map(Some)
+                .ok_or(format!("第{}行: 无法解析起止时间 {} ~ {}", line, start, end))
+            }
+            (None, None) => Ok(remark.as_deref().and_then(|r| parse_roaming_window(r, now))),
+            _ => Err(format!("第{}行: 开始和结束时间需要同时填写", line)),
+        };
+        let window = match window {
+            Ok(window) => window,
+            Err(e) => {
+                import.errors.push(e);
+                continue;
+            }
+        };
+        if window.is_some_and(|(_, end)| end < now) {
+            import.errors.push(format!("第{}行: {}的漫游已经结束", line, callsign));
+            continue;
+        }
+
+        import.entries.push(RoamingData {
+            callsign,
+            grid: grids.join(" "),
+            remark,
+            start: window.map(|(start, _)| start.to_rfc3339()),
+            end: Some(window.map(|(_, end)| end).unwrap_or(now + chrono::Duration::days(ROAMING_DEFAULT_VALIDITY_DAYS)).to_rfc3339()),
+        });
+    }
+    Ok(import)
+}
+
+/// Replace entries with the same callsign and append the rest, returns (added, updated)
+/// - a replaced entry keeps its owner, new ones belong to `IMPORTED_ROAMING_OWNER`, not to the importing admin
+pub fn merge_imported_roaming(
+    roaming_data: &mut Vec<UserRoamingData>,
+    entries: Vec<RoamingData>,
+    submit_time: &str,
+) -> (usize, usize) {
+    let (mut added, mut updated) = (0, 0);
+    for entry in entries {
+        match roaming_data.iter_mut().find(|r| r.roaming_data.callsign == entry.callsign) {
+            Some(existing) => {
+                existing.submit_time = submit_time.to_string();
+                existing.roaming_data = entry;
+                updated += 1;
+            }
+            None => {
+                roaming_data.push(UserRoamingData {
+                    user_id: IMPORTED_ROAMING_OWNER.to_string(),
+                    submit_time: submit_time.to_string(),
+                    roaming_data: entry,
+                });
+                added += 1;
+            }
+        }
+    }
+    roaming_data.sort_by(|a, b| b.submit_time.cmp(&a.submit_time));
+    (added, updated)
+}
+
+/// `/roaming export [csv|ics|json]`
+pub async fn export_roaming_command(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+) -> Reply {
+    // Args: export [format]
+    let format = match args.split_whitespace().nth(1) {
+        Some(format) => match ExportFormat::from_str(format) {
+            Some(format) => format,
+            None => return ApiResponse::error("支持的导出格式: csv | ics | json".to_string()),
+        },
+        None => ExportFormat::Csv,
+    };
+
+    // with the HTTP service configured the feed is always current, no file needed
+    let public_url = {
+        let config_guard = app_status.config.read().await;
+        config_guard.http_config.as_ref().and_then(|c| c.public_url.clone())
+    };
+    if let Some(public_url) = public_url {
+        return Reply::lines(vec![format!(
+            "漫游数据 ({}): {}/roaming.{}",
+            format.extension(),
+            public_url.trim_end_matches('/'),
+            format.extension()
+        )]);
+    }
+
+    let roaming_data = match load_roaming_list(app_status).await {
+        Ok(data) => data,
+        Err(e) => return ApiResponse::error(format!("文件读取失败: {}", e)),
+    };
+    let content = match export_roaming(&roaming_data, format, Utc::now()) {
+        Ok(content) => content,
+        Err(e) => return ApiResponse::error(format!("导出失败: {}", e)),
+    };
+
+    let path = format!("{}.{}", ROAMING_EXPORT_PATH_PREFIX, format.extension());
+    if let Err(e) = write_file(app_status.file_tx.clone(), path.clone(), &FileData::Text(content)).await {
+        return ApiResponse::error(format!("文件写入失败: {}", e));
+    }
+    let name = format!("roaming.{}", format.extension());
+    Reply::file(path, name)
+}
+
+/// `/roaming import` followed by CSV lines, admin only
+/// - existing entries with the same callsign are replaced and keep their owner
+/// - imported entries are not announced, bulk imports would flood the groups
+pub async fn import_roaming_command(
+    app_status: &Arc<AppStatus>,
+    args: &str,
+    payload: &MessageEvent,
+) -> Reply {
+    let admin_id = {
+        let config_guard = app_status.config.read().await;
+        config_guard.bot_config.admin_id.clone()
+    };
+    if !admin_id.contains(&payload.user_id) {
+        return ApiResponse::error("只有管理员可以批量导入漫游信息喵".to_string());
+    }
+
+    // Args: import\n<csv>
+    let content = args.trim_start().strip_prefix("import").unwrap_or_default();
+    if content.trim().is_empty() {
+        return ApiResponse::error("请在 /roaming import 后换行粘贴CSV，表头需要包含 callsign,grid，可选 start,end,remark".to_string());
+    }
+
+    let now = Utc::now();
+    let import = match parse_roaming_csv(content, now) {
+        Ok(import) => import,
+        Err(e) => return ApiResponse::error(format!("CSV解析失败喵: {}", e)),
+    };
+    if import.entries.is_empty() {
+        let mut data = vec!["没有可以导入的漫游信息喵".to_string()];
+        data.extend(import.errors);
+        return ApiResponse::error(data.join("\n"));
+    }
+
+    let _roaming_guard = ROAMING_DATA_LOCK.lock().await;
+    let mut roaming_data = match load_roaming_list(app_status).await {
+        Ok(data) => data,
+        Err(e) => return ApiResponse::error(format!("文件读取失败: {}", e)),
+    };
+
+    let update_time = format!("{} BJT", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+    let (added, updated) = merge_imported_roaming(&mut roaming_data, import.entries, &update_time);
+
+    if let Err(e) = write_roaming_data_to_file(&app_status.file_tx, &roaming_data).await {
+        tracing::error!("Failed to write roaming data: {}", e);
+        return ApiResponse::error(format!("文件写入失败: {}", e));
+    }
+    if let Err(e) = render::render_roaming_data(&roaming_data).await {
+        tracing::error!("Failed to render roaming data: {}", e);
+    }
+
+    tracing::info!("Imported roaming data: {} added, {} updated, {} skipped", added, updated, import.errors.len());
+    let mut data = vec![format!("导入完成: 新增{}条，更新{}条，跳过{}条", added, updated, import.errors.len())];
+    data.extend(import.errors);
+    Reply::lines(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::tools::roaming::owns_other_roaming;
+
+    fn entry(callsign: &str, grid: &str) -> RoamingData {
+        RoamingData {
+            callsign: callsign.to_string(),
+            grid: grid.to_string(),
+            remark: None,
+            start: None,
+            end: None,
+        }
+    }
+
+    #[test]
+    fn import_keeps_owners_and_leaves_the_admin_free() {
+        let admin = "10000";
+        let mut roaming_data = vec![UserRoamingData {
+            user_id: "20000".to_string(),
+            submit_time: "2025-08-01 12:00:00 BJT".to_string(),
+            roaming_data: entry("BG1AAA", "OM89"),
+        }];
+
+        let (added, updated) = merge_imported_roaming(
+            &mut roaming_data,
+            vec![entry("BG1AAA", "ON80"), entry("BG2BBB", "PN11")],
+            "2025-08-02 12:00:00 BJT",
+        );
+        assert_eq!((added, updated), (1, 1));
+
+        let replaced = roaming_data.iter().find(|r| r.roaming_data.callsign == "BG1AAA").unwrap();
+        assert_eq!(replaced.user_id, "20000");
+        assert_eq!(replaced.roaming_data.grid, "ON80");
+        let imported = roaming_data.iter().find(|r| r.roaming_data.callsign == "BG2BBB").unwrap();
+        assert_eq!(imported.user_id, IMPORTED_ROAMING_OWNER);
+
+        // the admin's own `/roaming add` after an import
+        assert!(!owns_other_roaming(&roaming_data, admin, "BG3CCC"));
+        assert!(owns_other_roaming(&roaming_data, "20000", "BG3CCC"));
+    }
+
+    #[test]
+    fn parses_import_rows() {
+        let now = Utc::now();
+        let import = parse_roaming_csv("callsign,grid,remark\nbg1aaa,OM89,test\nBG2BBB,XX\n", now).unwrap();
+        assert_eq!(import.entries.len(), 1);
+        assert_eq!(import.entries[0].callsign, "BG1AAA");
+        assert_eq!(import.errors.len(), 1);
+    }
+
+    #[test]
+    fn imports_grids_of_any_case() {
+        let import = parse_roaming_csv("callsign,grid
+BG1AAA,OM89AB;om90
+BG2BBB,om89zz
+", Utc::now()).unwrap();
+        assert_eq!(import.entries.len(), 1);
+        assert_eq!(import.entries[0].grid, "OM89ab OM90");
+        assert_eq!(import.errors.len(), 1);
+    }
+}
@@ -2,37 +2,44 @@ use reqwest;
 use serde_json;
 use std::sync::Arc;
 use crate::{
-    response::ApiResponse,
+    response::{ApiResponse, Reply},
     i18n,
     app_status::AppStatus,
 };
-use crate::msg::prelude::*;
+use crate::msg::{
+    image::inline_images,
+    prelude::*,
+    push::{deliver_push, deliver_pushes, PushMessage, PushTarget},
+    segment::{text_with_at, to_onebot_message, Segment},
+};
+
+fn reply_segments(response: Reply) -> Vec<Segment> {
+    response
+        .into_segments()
+        .unwrap_or_else(|| vec![Segment::text(i18n::text("no_response_data"))])
+}
 
+/// Answers in the chat the message came from, group or private
 #[allow(unused)]
 pub async fn send_group_msg(
-    response: ApiResponse<Vec<String>>,
+    response: Reply,
     payload: &MessageEvent,
     url: &String,
 ) {
-    let message_text: String = response
-        .data
-        .map(|data| data.join("\n"))
-        .unwrap_or_else(|| response.message.unwrap_or_else(|| i18n::text("no_response_data")));
+    let segments = reply_segments(response);
 
-    let group_id = payload.group_id;
-    let msg_body = serde_json::json!({
-        "group_id": group_id,
-        "message": [
-            {
-                "type": "text",
-                "data": {
-                    "text": message_text
-                }
-            }
-        ]
-    });
+    let (endpoint, msg_body) = match PushTarget::reply_to(payload) {
+        PushTarget::Group(group_id) => ("send_group_msg", serde_json::json!({
+            "group_id": group_id,
+            "message": to_onebot_message(&segments)
+        })),
+        PushTarget::Private(user_id) => ("send_private_msg", serde_json::json!({
+            "user_id": user_id,
+            "message": to_onebot_message(&segments)
+        })),
+    };
 
-    let endpoint_url = format!("{}/send_group_msg", url);
+    let endpoint_url = format!("{}/{}", url, endpoint);
     let client = reqwest::Client::new();
     let response = client
         .post(endpoint_url)
@@ -53,83 +60,157 @@ pub async fn send_group_msg(
 }
 
 pub async fn send_group_message_to_multiple_groups(
-    response: ApiResponse<Vec<String>>,
+    response: Reply,
+    app_status: &Arc<AppStatus>,
+) {
+    let groups = app_status.config.read().await.bot_config.group_id.clone();
+    send_group_message_to_groups(response, &groups, app_status).await;
+}
+
+/// Same as `send_group_message_to_multiple_groups` with an explicit group list, delivered concurrently
+pub async fn send_group_message_to_groups(
+    response: Reply,
+    groups: &[u64],
     app_status: &Arc<AppStatus>,
 ) {
     if response == ApiResponse::empty() {
         return ;
     };
-    let config = app_status.config.read().await;
-    let url = config.bot_config.sse_url.clone();
-    let groups = &config.bot_config.group_id;
-    let msg_text = response
-        .data
-        .map(|data| data.join("\n"))
-        .unwrap_or_else(|| response.message.unwrap_or_else(|| i18n::text("no_response_data")));
-    for group_id in groups {
-        let msg_body = serde_json::json!({
+    let segments = reply_segments(response);
+    let messages = groups
+        .iter()
+        .map(|group_id| PushMessage {
+            target: PushTarget::Group(*group_id),
+            segments: segments.clone(),
+        })
+        .collect();
+    deliver_pushes(app_status, messages).await;
+}
+
+/// The same text to each user in private, delivered concurrently
+pub async fn send_private_messages(
+    user_ids: &[u64],
+    text: &str,
+    app_status: &Arc<AppStatus>,
+) {
+    let messages = user_ids
+        .iter()
+        .map(|user_id| PushMessage {
+            target: PushTarget::Private(*user_id),
+            segments: vec![Segment::text(text)],
+        })
+        .collect();
+    deliver_pushes(app_status, messages).await;
+}
+
+/// Text message with `at` segments for the mentioned users in front
+pub async fn send_group_message_with_at(
+    group_id: u64,
+    at: &[u64],
+    text: &str,
+    app_status: &Arc<AppStatus>,
+) {
+    post_group_message(group_id, at, text, app_status).await;
+}
+
+async fn post_group_message(
+    group_id: u64,
+    at: &[u64],
+    text: &str,
+    app_status: &Arc<AppStatus>,
+) {
+    let message = PushMessage {
+        target: PushTarget::Group(group_id),
+        segments: text_with_at(at, text),
+    };
+    deliver_push(app_status, message).await;
+}
+
+/// OneBot API, used when no core takes the push and for replies of the OneBot adapter
+/// - over the reverse WebSocket when a OneBot implementation is connected, otherwise HTTP (`sse_url`)
+/// - a forward segment goes out through the `*_forward_msg` endpoints, other segments are dropped
+/// - with `image_transfer = inline` images are sent as `base64://`, the OneBot side may not share the disk
+pub async fn post_onebot_message(
+    message: &PushMessage,
+    app_status: &Arc<AppStatus>,
+) {
+    let url = app_status.config.read().await.bot_config.sse_url.clone();
+    let segments = inline_images(app_status, None, message.segments.clone()).await;
+    let forward = segments.iter().find_map(|s| match s {
+        Segment::Forward { nodes } => Some(nodes.iter().map(|n| n.to_onebot()).collect::<Vec<_>>()),
+        _ => None,
+    });
+    let (endpoint, msg_body) = match (message.target, forward) {
+        (PushTarget::Group(group_id), Some(nodes)) => ("send_group_forward_msg", serde_json::json!({
             "group_id": group_id,
-            "message": [
-                {
-                    "type": "text",
-                    "data": {
-                        "text": msg_text
-                    }
-                }
-            ]
-        });
-
-        let endpoint_url = format!("{}/send_group_msg", url);
-        let client = reqwest::Client::new();
-        let response = client
-            .post(endpoint_url)
-            .json(&msg_body)
-            .send()
-            .await;
-
-        match response {
-            Ok(res) => {
-                let status = res.status();
-                let body = res.text().await.unwrap_or_else(|_| "<Failed to read body>".to_string());
-                if !status.is_success() {
-                    tracing::error!("{}: {}", i18n::text("send_group_msg_err"), body);
-                }
-                if body.contains("error") {
-                    tracing::error!("{}: {}", i18n::text("send_group_msg_err"), body);
-                }
+            "messages": nodes
+        })),
+        (PushTarget::Private(user_id), Some(nodes)) => ("send_private_forward_msg", serde_json::json!({
+            "user_id": user_id,
+            "messages": nodes
+        })),
+        (PushTarget::Group(group_id), None) => ("send_group_msg", serde_json::json!({
+            "group_id": group_id,
+            "message": to_onebot_message(&segments)
+        })),
+        (PushTarget::Private(user_id), None) => ("send_private_msg", serde_json::json!({
+            "user_id": user_id,
+            "message": to_onebot_message(&segments)
+        })),
+    };
+
+    // a OneBot implementation on reverse WebSocket takes the call there
+    let ws = app_status.onebot_ws.read().await.clone();
+    if let Some(ws) = ws {
+        let action = serde_json::json!({ "action": endpoint, "params": msg_body });
+        if ws.send(action).is_ok() {
+            return;
+        }
+    }
+
+    let endpoint_url = format!("{}/{}", url, endpoint);
+    let client = reqwest::Client::new();
+    let response = client
+        .post(endpoint_url)
+        .json(&msg_body)
+        .send()
+        .await;
+
+    match response {
+        Ok(res) => {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_else(|_| "<Failed to read body>".to_string());
+            if !status.is_success() {
+                tracing::error!("{}: {}", i18n::text("send_group_msg_err"), body);
             }
-            Err(err) => {
-                tracing::error!("{}: {}", i18n::text("send_group_msg_err"), err);
+            if body.contains("error") {
+                tracing::error!("{}: {}", i18n::text("send_group_msg_err"), body);
             }
         }
+        Err(err) => {
+            tracing::error!("{}: {}", i18n::text("send_group_msg_err"), err);
+        }
     }
 }
 
 pub async fn _send_picture_to_group(
-    response: ApiResponse<Vec<String>>,
+    response: Reply,
     payload: &MessageEvent,
     app_status: &Arc<AppStatus>,
 ) {
     let config = app_status.config.read().await;
     let url = config.bot_config.sse_url.clone();
-    let image_path = match response.data {
-        Some(data) if !data.is_empty() => data[0].clone(),
-        _ => {
-            tracing::error!("No image data provided in response");
-            return;
-        }
+    let image = response.data.and_then(|data| {
+        data.into_iter().find(|s| matches!(s, Segment::Image { .. } | Segment::ImageBytes { .. }))
+    });
+    let Some(image) = image else {
+        tracing::error!("No image data provided in response");
+        return;
     };
 
     let msg_body = serde_json::json!({
         "group_id": payload.group_id,
-        "message": [
-            {
-                "type": "image",
-                "data": {
-                    "file": image_path
-                }
-            }
-        ]
+        "message": [image.to_onebot()]
     });
 
     let endpoint_url = format!("{}/send_group_msg", url);
@@ -0,0 +1,144 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use crate::{
+    app_status::AppStatus,
+    config::ImageTransfer,
+    fs::handler::{load_file, FileData, FileFormat},
+    msg::segment::{content_hash, ForwardNode, Segment},
+};
+
+/// Hashes remembered per link, a core announcing `image_cache` keeps at least as many
+pub const IMAGE_CACHE_SIZE: usize = 64;
+
+/// Inlined images each connected core already holds, by peer address
+#[derive(Debug, Default)]
+pub struct ImageCache {
+    sent: Mutex<HashMap<String, VecDeque<String>>>,
+}
+
+impl ImageCache {
+    pub fn new() -> Arc<Self> {
+        Arc::new(ImageCache::default())
+    }
+
+    /// True if the peer already has the image
+    async fn contains(&self, peer: &str, hash: &str) -> bool {
+        self.sent
+            .lock()
+            .await
+            .get(peer)
+            .is_some_and(|hashes| hashes.iter().any(|h| h == hash))
+    }
+
+    /// Records the images of a frame once it reached the peer, see `sent_image_hashes`
+    /// - a frame that failed to send or was not delivered leaves the cache as it was,
+    ///   so the next one carries the bytes again
+    pub async fn remember(&self, peer: &str, segments: &[Segment]) {
+        let mut sent = self.sent.lock().await;
+        let hashes = sent.entry(peer.to_string()).or_default();
+        for hash in sent_image_hashes(segments) {
+            if hashes.contains(&hash) {
+                continue;
+            }
+            if hashes.len() >= IMAGE_CACHE_SIZE {
+                hashes.pop_front();
+            }
+            hashes.push_back(hash);
+        }
+    }
+
+    /// A reconnected core starts with an empty cache
+    pub async fn forget_peer(&self, peer: &str) {
+        self.sent.lock().await.remove(peer);
+    }
+}
+
+/// Hashes of the image bytes carried by the segments, forwarded nodes included
+fn sent_image_hashes(segments: &[Segment]) -> Vec<String> {
+    segments
+        .iter()
+        .flat_map(|segment| match segment {
+            Segment::ImageBytes { hash, .. } => vec![hash.clone()],
+            Segment::Forward { nodes } => nodes.iter().flat_map(|node| sent_image_hashes(&node.segments)).collect(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// With `image_transfer = inline`, replaces image paths with their bytes for a core or OneBot
+/// - `peer` is given when the frame can carry `CachedImage`, i.e. not the legacy `Chat` reply or OneBot
+/// - images over `inline_image_limit` or failing to load keep their path
+/// - the caller records the sent images with `ImageCache::remember` after the frame got through
+pub async fn inline_images(
+    app_status: &Arc<AppStatus>,
+    peer: Option<&str>,
+    segments: Vec<Segment>,
+) -> Vec<Segment> {
+    let (transfer, limit) = {
+        let config_guard = app_status.config.read().await;
+        (config_guard.bot_config.image_transfer, config_guard.bot_config.inline_image_limit)
+    };
+    if transfer != ImageTransfer::Inline {
+        return segments;
+    }
+
+    // the core announces whether it keeps images by hash
+    let cache_peer = match peer {
+        Some(peer) => app_status.peer_handshakes
+            .read()
+            .await
+            .get(peer)
+            .filter(|handshake| handshake.supports("image_cache"))
+            .map(|_| peer.to_string()),
+        None => None,
+    };
+
+    let mut inlined = Vec::with_capacity(segments.len());
+    for segment in segments {
+        inlined.push(inline_segment(app_status, cache_peer.as_deref(), limit, segment).await);
+    }
+    inlined
+}
+
+async fn inline_segment(
+    app_status: &Arc<AppStatus>,
+    cache_peer: Option<&str>,
+    limit: usize,
+    segment: Segment,
+) -> Segment {
+    match segment {
+        Segment::Image { path } => {
+            let data = match load_file(app_status.file_tx.clone(), path.clone(), FileFormat::Png).await {
+                Ok(FileData::Png(data)) => data,
+                Ok(_) => return Segment::Image { path },
+                Err(e) => {
+                    tracing::error!("Failed to load image {} for inlining: {}", path, e);
+                    return Segment::Image { path };
+                }
+            };
+            if data.len() > limit {
+                tracing::warn!("Image {} is {} bytes, over the inline limit of {}, sending the path", path, data.len(), limit);
+                return Segment::Image { path };
+            }
+
+            let hash = content_hash(&data);
+            match cache_peer {
+                Some(peer) if app_status.images.contains(peer, &hash).await => Segment::CachedImage { hash },
+                _ => Segment::ImageBytes { hash, data },
+            }
+        }
+        Segment::Forward { nodes } => {
+            let mut inlined_nodes = Vec::with_capacity(nodes.len());
+            for node in nodes {
+                let mut segments = Vec::with_capacity(node.segments.len());
+                for segment in node.segments {
+                    segments.push(Box::pin(inline_segment(app_status, cache_peer, limit, segment)).await);
+                }
+                inlined_nodes.push(ForwardNode { segments, ..node });
+            }
+            Segment::Forward { nodes: inlined_nodes }
+        }
+        other => other,
+    }
+}
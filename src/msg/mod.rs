@@ -1,2 +1,5 @@
 pub mod group_msg;
-pub mod prelude;
\ No newline at end of file
+pub mod image;
+pub mod prelude;
+pub mod push;
+pub mod segment;
\ No newline at end of file
@@ -2,6 +2,8 @@
 use serde::{Serialize, Deserialize};
 use serde_json;
 
+/// OneBot v11 message event, array message format
+/// - fields some implementations leave out are defaulted
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct MessageEvent {
@@ -9,18 +11,31 @@ pub struct MessageEvent {
     pub user_id: u64,
     pub time: i64,
     pub message_id: u64,
+    #[serde(default)]
     pub message_seq: u64,
     pub message_type: String,
     pub sender: Sender,
+    #[serde(default)]
     pub raw_message: String,
+    #[serde(default)]
     pub font: u32,
+    #[serde(default)]
     pub sub_type: String,
     pub message: Vec<MessageElement>,
+    #[serde(default)]
     pub message_format: String,
     pub post_type: String,
+    #[serde(default)]
     pub group_id: u64,
 }
 
+impl MessageEvent {
+    /// `group_id` is 0 in private chats
+    pub fn is_private(&self) -> bool {
+        self.message_type == "private"
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct BinMessageEvent {
@@ -44,9 +59,13 @@ pub struct BinMessageEvent {
 #[serde(rename_all = "snake_case")]
 pub struct Sender {
     pub user_id: u64,
+    #[serde(default)]
     pub nickname: String,
+    #[serde(default)]
     pub card: String,
+    #[serde(default)]
     pub role: String,
+    #[serde(default)]
     pub title: String,
 }
 
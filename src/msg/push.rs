@@ -0,0 +1,168 @@
+use crate::{
+    app_status::AppStatus,
+    msg::{group_msg::post_onebot_message, image::inline_images, prelude::MessageEvent, segment::Segment},
+    socket::BotMessage,
+};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex};
+
+/// How long the core gets to report a push as delivered
+/// - an unconfirmed push is not resent over HTTP, the core may still deliver it
+const PUSH_DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushTarget {
+    Group(u64),
+    Private(u64),
+}
+
+impl PushTarget {
+    /// The chat a message came from, where its reply goes
+    pub fn reply_to(payload: &MessageEvent) -> Self {
+        if payload.is_private() {
+            PushTarget::Private(payload.user_id)
+        } else {
+            PushTarget::Group(payload.group_id)
+        }
+    }
+}
+
+/// Message the server sends on its own, alerts, reminders and announcements
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PushMessage {
+    pub target: PushTarget,
+    pub segments: Vec<Segment>,
+}
+
+/// Pushes waiting for `Delivered` from the core
+#[derive(Debug, Default)]
+pub struct PushTracker {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, oneshot::Sender<Result<(), String>>>>,
+}
+
+impl PushTracker {
+    pub fn new() -> Arc<Self> {
+        Arc::new(PushTracker::default())
+    }
+
+    async fn register(&self) -> (u64, oneshot::Receiver<Result<(), String>>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+        (id, rx)
+    }
+
+    async fn forget(&self, id: u64) {
+        self.pending.lock().await.remove(&id);
+    }
+
+    /// Called for `Delivered` frames, late ones after a timeout are ignored
+    pub async fn complete(&self, id: u64, result: Result<(), String>) {
+        match self.pending.lock().await.remove(&id) {
+            Some(tx) => {
+                let _ = tx.send(result);
+            }
+            None => tracing::warn!("Delivery report for unknown push {}", id),
+        }
+    }
+}
+
+/// Why a push did not go through the core
+#[derive(Debug)]
+enum PushFailure {
+    /// no core to send it to, sending failed or the core reported a failure, safe to resend
+    NotDelivered(String),
+    /// sent but not confirmed in time, resending could deliver it twice
+    Unconfirmed(String),
+}
+
+/// Over the bot channel when a connected core supports it, otherwise straight to OneBot
+pub async fn deliver_push(
+    app_status: &Arc<AppStatus>,
+    message: PushMessage,
+) {
+    match push_over_channel(app_status, &message).await {
+        Ok(peer) => tracing::info!("Push to {:?} delivered by {}", message.target, peer),
+        Err(PushFailure::NotDelivered(e)) => {
+            tracing::warn!("Push to {:?} not delivered by core, using OneBot HTTP: {}", message.target, e);
+            post_onebot_message(&message, app_status).await;
+        }
+        Err(PushFailure::Unconfirmed(e)) => {
+            tracing::warn!("Push to {:?} may not have been delivered, not resending: {}", message.target, e);
+        }
+    }
+}
+
+/// Several pushes at once, e.g. one per group, so a slow core does not hold up the rest
+pub async fn deliver_pushes(
+    app_status: &Arc<AppStatus>,
+    messages: Vec<PushMessage>,
+) {
+    futures::future::join_all(messages.into_iter().map(|message| deliver_push(app_status, message))).await;
+}
+
+/// The core that delivers pushes, `bot_config.push_core` by handshake name or address
+/// - without it only a single connected core supporting push is used, with several
+///   (e.g. a test core next to production) the push goes to OneBot rather than to a guess
+async fn push_peer(
+    app_status: &Arc<AppStatus>,
+) -> Result<String, String> {
+    let push_core = app_status.config.read().await.bot_config.push_core.clone();
+    let handshakes = app_status.peer_handshakes.read().await;
+    let mut peers = handshakes.iter().filter(|(_, handshake)| handshake.supports("push"));
+
+    if let Some(push_core) = push_core {
+        return peers
+            .find(|(peer, handshake)| **peer == push_core || handshake.name == push_core)
+            .map(|(peer, _)| peer.clone())
+            .ok_or_else(|| format!("push core {} is not connected or does not support push", push_core));
+    }
+    let peers: Vec<&String> = peers.map(|(peer, _)| peer).collect();
+    match peers.as_slice() {
+        [] => Err("no connected core supports push".to_string()),
+        [peer] => Ok(peer.to_string()),
+        _ => Err(format!("{} connected cores support push, set bot_config.push_core to choose one", peers.len())),
+    }
+}
+
+async fn push_over_channel(
+    app_status: &Arc<AppStatus>,
+    message: &PushMessage,
+) -> Result<String, PushFailure> {
+    let peer = push_peer(app_status).await.map_err(PushFailure::NotDelivered)?;
+    let (id, rx) = app_status.push.register().await;
+    let message = PushMessage {
+        target: message.target,
+        segments: inline_images(app_status, Some(&peer), message.segments.clone()).await,
+    };
+    let segments = message.segments.clone();
+    let sent = app_status
+        .send_bot_message_to(&peer, BotMessage::Push { id, message })
+        .await
+        .map_err(|e| e.to_string());
+    if let Err(e) = sent {
+        app_status.push.forget(id).await;
+        return Err(PushFailure::NotDelivered(format!("{}: {}", peer, e)));
+    }
+
+    match tokio::time::timeout(PUSH_DELIVERY_TIMEOUT, rx).await {
+        Ok(Ok(Ok(()))) => {
+            app_status.images.remember(&peer, &segments).await;
+            Ok(peer)
+        }
+        Ok(Ok(Err(e))) => Err(PushFailure::NotDelivered(format!("{} rejected push {}: {}", peer, id, e))),
+        Ok(Err(_)) => Err(PushFailure::Unconfirmed(format!("{} dropped push {}", peer, id))),
+        Err(_) => {
+            app_status.push.forget(id).await;
+            Err(PushFailure::Unconfirmed(format!("{} did not confirm push {} in {}s", peer, id, PUSH_DELIVERY_TIMEOUT.as_secs())))
+        }
+    }
+}
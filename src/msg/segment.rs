@@ -0,0 +1,157 @@
+use base64::Engine;
+use serde::{Serialize, Deserialize};
+
+/// Scheme the core maps onto the shared runtime directory
+pub const SERVER_FILE_URI_PREFIX: &str = "file:///server_";
+
+/// Outgoing message segment, what handlers reply with and pushes carry
+/// - externally tagged like `BinMessageElement` so bincode can carry it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Segment {
+    Text { text: String },
+    /// Path relative to the working directory, e.g. `runtime_data/pic/roaming_map.png`
+    Image { path: String },
+    /// PNG bytes, `hash` is `content_hash(data)`
+    ImageBytes { hash: String, data: Vec<u8> },
+    /// `ImageBytes` with this hash was already sent on the same link
+    /// - only sent to cores announcing `image_cache`, never to OneBot
+    CachedImage { hash: String },
+    At { qq: u64 },
+    /// Quote the message being answered
+    Reply { message_id: u64 },
+    /// Merged forward message, sent on its own
+    Forward { nodes: Vec<ForwardNode> },
+    /// Path relative to the working directory, `name` is what the receiver sees
+    File { path: String, name: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ForwardNode {
+    pub name: String,
+    pub uin: u64,
+    pub segments: Vec<Segment>,
+}
+
+impl From<String> for Segment {
+    fn from(text: String) -> Self {
+        Segment::Text { text }
+    }
+}
+
+impl Segment {
+    pub fn text(text: impl Into<String>) -> Self {
+        Segment::Text { text: text.into() }
+    }
+
+    pub fn image(path: impl Into<String>) -> Self {
+        Segment::Image { path: path.into() }
+    }
+
+    /// `file:///server_` URI the core understands
+    pub fn server_uri(path: &str) -> String {
+        format!("{}{}", SERVER_FILE_URI_PREFIX, path)
+    }
+
+    /// `base64://` URI OneBot implementations accept in place of a file
+    pub fn base64_uri(data: &[u8]) -> String {
+        format!("base64://{}", base64::engine::general_purpose::STANDARD.encode(data))
+    }
+
+    /// Absolute `file://` URI for a OneBot implementation on the same machine
+    fn local_file_uri(path: &str) -> String {
+        match std::path::absolute(path) {
+            Ok(path) => format!("file://{}", path.display()),
+            Err(_) => format!("file://{}", path),
+        }
+    }
+
+    /// OneBot v11 array message segment
+    pub fn to_onebot(&self) -> serde_json::Value {
+        match self {
+            Segment::Text { text } => serde_json::json!({
+                "type": "text",
+                "data": { "text": text }
+            }),
+            Segment::Image { path } => serde_json::json!({
+                "type": "image",
+                "data": { "file": Segment::local_file_uri(path) }
+            }),
+            Segment::ImageBytes { data, .. } => serde_json::json!({
+                "type": "image",
+                "data": { "file": Segment::base64_uri(data) }
+            }),
+            Segment::CachedImage { hash } => {
+                tracing::warn!("Cached image {} has no bytes to send to OneBot", hash);
+                serde_json::json!({
+                    "type": "text",
+                    "data": { "text": "[图片]" }
+                })
+            }
+            Segment::At { qq } => serde_json::json!({
+                "type": "at",
+                "data": { "qq": qq.to_string() }
+            }),
+            Segment::Reply { message_id } => serde_json::json!({
+                "type": "reply",
+                "data": { "id": message_id.to_string() }
+            }),
+            Segment::Forward { nodes } => serde_json::json!({
+                "type": "forward",
+                "data": { "content": nodes.iter().map(ForwardNode::to_onebot).collect::<Vec<_>>() }
+            }),
+            Segment::File { path, name } => serde_json::json!({
+                "type": "file",
+                "data": { "file": Segment::local_file_uri(path), "name": name }
+            }),
+        }
+    }
+}
+
+impl ForwardNode {
+    /// `node` segment for `send_group_forward_msg`
+    pub fn to_onebot(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "node",
+            "data": {
+                "name": self.name,
+                "uin": self.uin.to_string(),
+                "content": to_onebot_message(&self.segments)
+            }
+        })
+    }
+}
+
+/// FNV-1a of the bytes with the length, the cache key for inlined images
+pub fn content_hash(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}-{}", hash, data.len())
+}
+
+/// Consecutive text segments are separate lines, the same as the old `Vec<String>` replies
+pub fn to_onebot_message(segments: &[Segment]) -> Vec<serde_json::Value> {
+    let mut message = Vec::new();
+    let mut previous_text = false;
+    for segment in segments {
+        let is_text = matches!(segment, Segment::Text { .. });
+        if let (true, Segment::Text { text }) = (previous_text, segment) {
+            message.push(Segment::text(format!("\n{}", text)).to_onebot());
+        } else {
+            message.push(segment.to_onebot());
+        }
+        previous_text = is_text;
+    }
+    message
+}
+
+/// `at` segments in front of the text, with the space OneBot clients expect after a mention
+pub fn text_with_at(at: &[u64], text: &str) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = at.iter().map(|qq| Segment::At { qq: *qq }).collect();
+    let text = if at.is_empty() { text.to_string() } else { format!(" {}", text) };
+    segments.push(Segment::text(text));
+    segments
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::msg::segment::Segment;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ApiResponse<T> {
@@ -44,7 +45,92 @@ impl<T> ApiResponse<T> {
     }
 }
 
-#[allow(dead_code)]
+/// What command handlers answer with
+pub type Reply = ApiResponse<Vec<Segment>>;
+
+impl ApiResponse<Vec<Segment>> {
+    /// One text segment per line
+    pub fn lines(lines: Vec<String>) -> Self {
+        Self::ok(lines.into_iter().map(Segment::from).collect())
+    }
+
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::ok(vec![Segment::text(text)])
+    }
+
+    pub fn image(path: impl Into<String>) -> Self {
+        Self::ok(vec![Segment::image(path)])
+    }
+
+    pub fn file(path: impl Into<String>, name: impl Into<String>) -> Self {
+        Self::ok(vec![Segment::File { path: path.into(), name: name.into() }])
+    }
+
+    /// Segments to send, a reply without data becomes its message text
+    pub fn into_segments(self) -> Option<Vec<Segment>> {
+        match (self.data, self.message) {
+            (Some(data), _) if !data.is_empty() => Some(data),
+            (_, Some(message)) => Some(vec![Segment::text(message)]),
+            _ => None,
+        }
+    }
+
+    /// The `Vec<String>` form cores before segments understand
+    /// - the first image or file wins, inlined images as `base64://` and is sent alone as `message = "image"`/`"file"`,
+    ///   unless the reply already names its kind (e.g. `"solar image"`)
+    /// - otherwise one string per text segment, mentions as `@QQ`
+    pub fn to_legacy(&self) -> ApiResponse<Vec<String>> {
+        let Some(segments) = &self.data else {
+            return ApiResponse {
+                success: self.success,
+                data: None,
+                message: self.message.clone(),
+            };
+        };
+
+        let attachment = segments.iter().find_map(|s| match s {
+            Segment::Image { path } => Some((Segment::server_uri(path), "image")),
+            Segment::ImageBytes { data, .. } => Some((Segment::base64_uri(data), "image")),
+            Segment::File { path, .. } => Some((Segment::server_uri(path), "file")),
+            _ => None,
+        });
+        if let Some((uri, kind)) = attachment {
+            return ApiResponse {
+                success: self.success,
+                data: Some(vec![uri]),
+                message: Some(self.message.clone().unwrap_or_else(|| kind.to_string())),
+            };
+        }
+
+        ApiResponse {
+            success: self.success,
+            data: Some(legacy_lines(segments)),
+            message: self.message.clone(),
+        }
+    }
+}
+
+fn legacy_lines(segments: &[Segment]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for segment in segments {
+        match segment {
+            Segment::Text { text } => lines.push(text.clone()),
+            Segment::At { qq } => lines.push(format!("@{}", qq)),
+            Segment::Forward { nodes } => {
+                for node in nodes {
+                    lines.extend(legacy_lines(&node.segments));
+                }
+            }
+            Segment::Image { .. }
+            | Segment::ImageBytes { .. }
+            | Segment::CachedImage { .. }
+            | Segment::File { .. }
+            | Segment::Reply { .. } => {}
+        }
+    }
+    lines
+}
+
 pub fn json_response<T: Serialize>(
     success: bool,
     message: Option<String>,
@@ -10,19 +10,20 @@ use tokio::{
     },
 };
 use std::{
+    collections::HashMap,
     sync::Arc,
     net::SocketAddr,
     time::{Duration, Instant},
     path::PathBuf,
 };
 use crate::{
-    app_status::AppStatus,
-    config::Config,
+    app_status::{AppStatus, BotMessageSender},
+    config::{Config, LinkCodec, LinkMode},
     fs,
     i18n,
-    router,
-    msg::prelude::BinMessageEvent,
-    response::ApiResponse,
+    module::handler::{quota::QuotaState, registry::COMMAND_REGISTRY, router},
+    msg::{image::{inline_images, ImageCache}, prelude::BinMessageEvent, push::{PushMessage, PushTracker}},
+    response::{ApiResponse, Reply},
     CONFIG_FILE_PATH
 };
 use tokio::sync::OnceCell;
@@ -30,19 +31,106 @@ use tokio::sync::OnceCell;
 pub static GLOBAL_APP_STATUS: OnceCell<AppStatus> = OnceCell::const_new();
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
 
-#[derive(Serialize, Deserialize, Debug)]
+/// 1 was the bare enum without a handshake, peers that never send one are treated as that
+pub const PROTOCOL_VERSION: u32 = 2;
+/// Oldest version a handshaking peer may announce, v1 peers do not handshake at all
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+pub const SERVER_NAME: &str = "CiRCLE_sat_bot_server";
+/// Optional protocol features this side understands, announced in the handshake
+pub const CAPABILITIES: &[&str] = &["error_frame", "request", "push", "image_cache"];
+
+/// - bincode encodes the variant index, so new variants only ever go at the end
+/// - new fields must be `#[serde(default)]`, which only the JSON codec can honour
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum BotMessage {
     Heartbeat,
     Pong,
     Chat { from: String, to: String, content: MsgContent },
+    /// Sent by the server only after the core's own, unless `bot_config.handshake_first` is set
+    Handshake(Handshake),
+    /// The peer could not handle a frame
+    Error(ErrorFrame),
+    /// `Chat` with a correlation id, answered by `Ack` and then a `Response` with the same id
+    /// - `timeout_ms` is relative to when the server receives the frame, past it the server
+    ///   gives up and answers with an error; the old `deadline_ms` name is read the same way
+    Request {
+        id: u64,
+        #[serde(alias = "deadline_ms")]
+        timeout_ms: Option<u64>,
+        from: String,
+        to: String,
+        content: MsgContent,
+    },
+    /// Typed reply segments, `Chat` answers keep the `Vec<String>` form for older cores
+    Response {
+        id: u64,
+        from: String,
+        to: String,
+        payload: Option<BinMessageEvent>,
+        reply: Reply,
+    },
+    /// The request was received and is being handled
+    Ack { id: u64 },
+    /// Server-initiated message for the core to deliver, only sent to cores announcing `push`
+    Push { id: u64, message: PushMessage },
+    /// Delivery report for a `Push`, `error` is None on success
+    Delivered { id: u64, error: Option<String> },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Handshake {
+    pub protocol_version: u32,
+    pub name: String,
+    /// Commands the router answers, without the leading `/`
+    #[serde(default)]
+    pub commands: Vec<String>,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+impl Handshake {
+    pub fn local() -> Self {
+        Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            name: SERVER_NAME.to_string(),
+            commands: COMMAND_REGISTRY.names(),
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErrorFrame {
+    /// `decode_failed`, `unsupported_version`, ... kept a string so new codes do not break old peers
+    pub code: String,
+    pub message: String,
+}
+
+impl ErrorFrame {
+    pub fn new(code: &str, message: impl Into<String>) -> Self {
+        ErrorFrame {
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MsgContent {
+    #[serde(default)]
     pub command: Option<String>,
+    #[serde(default)]
     pub payload: Option<BinMessageEvent>,
+    #[serde(default)]
     pub message: Option<String>,
+    #[serde(default)]
     pub api_response: Option<ApiResponse<Vec<String>>>,
 }
 impl MsgContent {
@@ -56,77 +144,369 @@ impl MsgContent {
     }
 }
 
-pub async fn handle_connection(
-    stream: TcpStream,
-    addr: SocketAddr,
-    app_status: Arc<AppStatus>,
-) -> anyhow::Result<()> {
-    tracing::info!("New connection from {}", addr);
+#[derive(Debug)]
+pub struct CodecError(String);
 
-    let (r, w) = stream.into_split();
-    let (tx, mut rx) = channel::<BotMessage, _, _>(r, w);
+impl std::fmt::Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
 
-    let tx = Arc::new(Mutex::new(tx));
-    let last_seen = Arc::new(Mutex::new(Instant::now()));
+/// Frame encoding of a core link, from `bot_config.codec`
+#[derive(Clone, Debug)]
+pub enum WireCodec {
+    Bincode(channels::serdes::Bincode),
+    Json(channels::serdes::Json),
+}
+
+impl WireCodec {
+    pub fn from_config(codec: LinkCodec) -> Self {
+        match codec {
+            LinkCodec::Bincode => WireCodec::Bincode(channels::serdes::Bincode::new()),
+            LinkCodec::Json => WireCodec::Json(channels::serdes::Json::new()),
+        }
+    }
+}
+
+impl<T: Serialize> channels::serdes::Serializer<T> for WireCodec {
+    type Error = CodecError;
+
+    fn serialize(&mut self, t: &T) -> Result<Vec<u8>, Self::Error> {
+        match self {
+            WireCodec::Bincode(codec) => codec.serialize(t).map_err(|e| CodecError(e.to_string())),
+            WireCodec::Json(codec) => codec.serialize(t).map_err(|e| CodecError(e.to_string())),
+        }
+    }
+}
 
-    app_status.update_bot_connection(tx.clone()).await;
+impl<T> channels::serdes::Deserializer<T> for WireCodec
+where
+    for<'de> T: Deserialize<'de>,
+{
+    type Error = CodecError;
+
+    fn deserialize(&mut self, buf: &mut [u8]) -> Result<T, Self::Error> {
+        match self {
+            WireCodec::Bincode(codec) => codec.deserialize(buf).map_err(|e| CodecError(e.to_string())),
+            WireCodec::Json(codec) => codec.deserialize(buf).map_err(|e| CodecError(e.to_string())),
+        }
+    }
+}
+
+/// Starts every configured core link and runs until they all end, which they do not
+pub async fn serve_core_links(
+    app_status: Arc<AppStatus>,
+) {
+    let (link_mode, addrs) = {
+        let config_guard = app_status.config.read().await;
+        let bot_config = &config_guard.bot_config;
+        let addrs: Vec<String> = std::iter::once(bot_config.listen_addr.clone())
+            .chain(bot_config.extra_addr.iter().cloned())
+            .collect();
+        (bot_config.link_mode, addrs)
+    };
+    if link_mode == LinkMode::None {
+        tracing::info!("Core link disabled, running standalone");
+        std::future::pending::<()>().await;
+    }
+
+    let tasks: Vec<_> = addrs
+        .into_iter()
+        .map(|addr| match link_mode {
+            LinkMode::Client => tokio::spawn(core_connection_manager(Arc::clone(&app_status), addr)),
+            LinkMode::Server => tokio::spawn(core_listener(Arc::clone(&app_status), addr)),
+            LinkMode::None => unreachable!(),
+        })
+        .collect();
+    futures::future::join_all(tasks).await;
+}
+
+/// Server mode, every accepted core gets its own link
+pub async fn core_listener(
+    app_status: Arc<AppStatus>,
+    addr: String,
+) {
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Failed to listen for core on {}: {}", addr, e);
+            return;
+        }
+    };
+    tracing::info!("Waiting for core connections on {}", addr);
 
     loop {
-        match rx.recv().await {
-            Ok(msg) => {
-                // pass app_status to the task
-                let app_status_clone = Arc::clone(&app_status);
-                let tx_clone = Arc::clone(&tx);
-                let last_seen_clone = Arc::clone(&last_seen);
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                tracing::info!("New connection from {}", peer);
+                let app_status = Arc::clone(&app_status);
                 tokio::spawn(async move {
-                    *last_seen_clone.lock().await = Instant::now();
-
-                    match msg {
-                        BotMessage::Heartbeat => {
-                            let mut last_seen_guard = last_seen_clone.lock().await;
-                            *last_seen_guard = Instant::now();
-                            let mut tx_guard = tx_clone.lock().await;
-                            if let Err(e) = tx_guard.send(BotMessage::Pong).await {
-                                tracing::error!("Failed to send Pong: {}", e);
-                            }
-                        }
-                        BotMessage::Pong => {
-                            // No action needed for Pong
-                        }
-                        BotMessage::Chat { from, to, content } => {
-                            let response = router::bot_message_handler(
-                                content.clone(),
-                                app_status_clone.clone()
-                            ).await;
-                            let response_content = MsgContent {
-                                command: None,
-                                payload: content.payload.clone(),
-                                message: content.message.clone(),
-                                api_response: Some(response),
-                            };
-                            let mut tx_guard = tx_clone.lock().await;
-                            if let Err(e) = tx_guard.send(BotMessage::Chat {
-                                from,
-                                to,
-                                content: response_content,
-                            }).await {
-                                tracing::error!("Failed to send Chat response: {}", e);
-                            }
-                        }
+                    if let Err(e) = run_core_link(stream, peer.to_string(), app_status).await {
+                        tracing::error!("Core link from {} lost: {}", peer, e);
                     }
+                    tracing::info!("Connection closed: {}", peer);
                 });
             }
             Err(e) => {
-                tracing::error!("Error receiving message from {}: {}", addr, e);
-                break;
+                tracing::error!("Failed to accept core connection on {}: {}", addr, e);
             }
         }
     }
+}
 
-    app_status.clear_bot_connection().await;
-    tracing::info!("Connection closed: {}", addr);
+/// Keeps the link to the core up, reconnecting with exponential backoff
+/// - the backoff only resets once a link has stayed up past `CLIENT_TIMEOUT`
+pub async fn core_connection_manager(
+    app_status: Arc<AppStatus>,
+    addr: String,
+) {
+    let mut backoff = RECONNECT_BACKOFF_MIN;
+    loop {
+        match TcpStream::connect(&addr).await {
+            Ok(stream) => {
+                tracing::info!("Connected to core at {}", addr);
+                let connected_at = Instant::now();
+                if let Err(e) = run_core_link(stream, addr.clone(), Arc::clone(&app_status)).await {
+                    tracing::error!("Core link to {} lost: {}", addr, e);
+                }
+                if connected_at.elapsed() > CLIENT_TIMEOUT {
+                    backoff = RECONNECT_BACKOFF_MIN;
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to connect to core at {}: {}", addr, e);
+            }
+        }
+
+        tracing::info!("Reconnecting to core in {}s", backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+    }
+}
+
+/// One connected session in either mode, returns when the peer closes, errors or goes silent
+async fn run_core_link(
+    stream: TcpStream,
+    peer: String,
+    app_status: Arc<AppStatus>,
+) -> anyhow::Result<()> {
+    let (codec, handshake_first) = {
+        let config_guard = app_status.config.read().await;
+        (WireCodec::from_config(config_guard.bot_config.codec), config_guard.bot_config.handshake_first)
+    };
+    let (r, w) = stream.into_split();
+    let tx = channels::Sender::<BotMessage, _, _>::builder()
+        .writer(w)
+        .serializer(codec.clone())
+        .build();
+    let mut rx = channels::Receiver::<BotMessage, _, _>::builder()
+        .reader(r)
+        .deserializer(codec)
+        .build();
+
+    let tx: BotMessageSender = Arc::new(Mutex::new(tx));
+    let last_seen = Arc::new(Mutex::new(Instant::now()));
+    let peer_dead = Arc::new(tokio::sync::Notify::new());
+
+    app_status.update_bot_connection(&peer, tx.clone()).await;
+    // a v1 core cannot decode the Handshake variant and would drop the link
+    if handshake_first
+        && let Err(e) = tx.lock().await.send(BotMessage::Handshake(Handshake::local())).await
+    {
+        app_status.clear_bot_connection(&peer).await;
+        return Err(anyhow::anyhow!("Failed to send Handshake to {}: {}", peer, e));
+    }
+
+    // our own heartbeats, anything received counts as a sign of life
+    let heartbeat_task = tokio::spawn({
+        let tx = Arc::clone(&tx);
+        let last_seen = Arc::clone(&last_seen);
+        let peer_dead = Arc::clone(&peer_dead);
+        let peer = peer.clone();
+        async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+                let silent_for = last_seen.lock().await.elapsed();
+                if silent_for > CLIENT_TIMEOUT {
+                    tracing::warn!("No message from {} for {}s", peer, silent_for.as_secs());
+                    break;
+                }
+                if let Err(e) = tx.lock().await.send(BotMessage::Heartbeat).await {
+                    tracing::error!("Failed to send Heartbeat: {}", e);
+                    break;
+                }
+            }
+            peer_dead.notify_one();
+        }
+    });
+
+    let result = loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Ok(msg) => {
+                    *last_seen.lock().await = Instant::now();
+                    tokio::spawn(handle_core_message(msg, peer.clone(), Arc::clone(&tx), Arc::clone(&app_status)));
+                }
+                // the frame itself arrived intact, most likely a newer peer, keep the link
+                Err(channels::error::RecvError::Serde(e)) => {
+                    *last_seen.lock().await = Instant::now();
+                    tracing::warn!("Undecodable frame from {}: {}", peer, e);
+                    let error = ErrorFrame::new("decode_failed", e.to_string());
+                    if let Err(e) = tx.lock().await.send(BotMessage::Error(error)).await {
+                        tracing::error!("Failed to send Error frame: {}", e);
+                    }
+                }
+                Err(e) => break Err(anyhow::anyhow!("Error receiving message from {}: {}", peer, e)),
+            },
+            _ = peer_dead.notified() => {
+                break Err(anyhow::anyhow!("Core stopped responding"));
+            }
+        }
+    };
 
-    Ok(())
+    heartbeat_task.abort();
+    app_status.clear_bot_connection(&peer).await;
+    result
+}
+
+/// Shared by client and server mode
+async fn handle_core_message(
+    msg: BotMessage,
+    peer: String,
+    tx: BotMessageSender,
+    app_status: Arc<AppStatus>,
+) {
+    match msg {
+        BotMessage::Heartbeat => {
+            if let Err(e) = tx.lock().await.send(BotMessage::Pong).await {
+                tracing::error!("Failed to send Pong: {}", e);
+            }
+        }
+        BotMessage::Pong => {
+            // last_seen is already updated
+        }
+        BotMessage::Handshake(handshake) => {
+            // the peer stays a v1 peer, the link itself is kept
+            if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&handshake.protocol_version) {
+                tracing::error!(
+                    "{} from {} speaks protocol v{}, supported are v{}-v{}",
+                    handshake.name,
+                    peer,
+                    handshake.protocol_version,
+                    MIN_PROTOCOL_VERSION,
+                    PROTOCOL_VERSION
+                );
+                let error = ErrorFrame::new(
+                    "unsupported_version",
+                    format!("supported protocol versions are {}-{}", MIN_PROTOCOL_VERSION, PROTOCOL_VERSION),
+                );
+                if let Err(e) = tx.lock().await.send(BotMessage::Error(error)).await {
+                    tracing::error!("Failed to send Error frame: {}", e);
+                }
+                return;
+            }
+            tracing::info!(
+                "Handshake from {}: {} protocol v{}, {} commands, capabilities {:?}",
+                peer,
+                handshake.name,
+                handshake.protocol_version,
+                handshake.commands.len(),
+                handshake.capabilities
+            );
+            app_status.update_peer_handshake(&peer, handshake).await;
+
+            let handshake_first = {
+                let config_guard = app_status.config.read().await;
+                config_guard.bot_config.handshake_first
+            };
+            if !handshake_first
+                && let Err(e) = tx.lock().await.send(BotMessage::Handshake(Handshake::local())).await
+            {
+                tracing::error!("Failed to send Handshake to {}: {}", peer, e);
+            }
+        }
+        BotMessage::Error(error) => {
+            tracing::error!("Error frame from {}: [{}] {}", peer, error.code, error.message);
+        }
+        BotMessage::Request { id, timeout_ms, content, .. } => {
+            if let Err(e) = tx.lock().await.send(BotMessage::Ack { id }).await {
+                tracing::error!("Failed to send Ack: {}", e);
+            }
+
+            // without a timeout from the core, fall back to the configured one, 0 is none
+            let timeout = match timeout_ms {
+                Some(ms) => Some(Duration::from_millis(ms)),
+                None => {
+                    let config_guard = app_status.config.read().await;
+                    let secs = config_guard.backend_config.request_timeout_secs;
+                    (secs > 0).then(|| Duration::from_secs(secs))
+                }
+            };
+            let handler = router::bot_message_handler(content.clone(), app_status.clone());
+            let mut response = match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, handler).await {
+                    Ok(response) => response,
+                    Err(_) => {
+                        tracing::warn!("Request {} from {} cancelled after {}ms", id, peer, timeout.as_millis());
+                        ApiResponse::error("处理超时了喵，请稍后再试".to_string())
+                    }
+                },
+                None => handler.await,
+            };
+            if let Some(data) = response.data.take() {
+                response.data = Some(inline_images(&app_status, Some(&peer), data).await);
+            }
+            let segments = response.data.clone().unwrap_or_default();
+            match tx.lock().await.send(BotMessage::Response {
+                id,
+                from: SERVER_NAME.to_string(),
+                to: "CiRCLE_sat_bot_core".to_string(),
+                payload: content.payload.clone(),
+                reply: response,
+            }).await {
+                Ok(()) => app_status.images.remember(&peer, &segments).await,
+                Err(e) => tracing::error!("Failed to send Response {}: {}", id, e),
+            }
+        }
+        BotMessage::Delivered { id, error } => {
+            app_status.push.complete(id, error.map_or(Ok(()), Err)).await;
+        }
+        BotMessage::Push { id, .. } => {
+            tracing::warn!("Unexpected push {} from {}", id, peer);
+            let error = ErrorFrame::new("unsupported", "the server does not deliver pushes");
+            if let Err(e) = tx.lock().await.send(BotMessage::Error(error)).await {
+                tracing::error!("Failed to send Error frame: {}", e);
+            }
+        }
+        BotMessage::Response { id, .. } | BotMessage::Ack { id } => {
+            // the server does not send requests of its own
+            tracing::warn!("Unexpected reply {} from {}", id, peer);
+        }
+        BotMessage::Chat { content, .. } => {
+            let mut response = router::bot_message_handler(
+                content.clone(),
+                app_status.clone()
+            ).await;
+            if let Some(data) = response.data.take() {
+                response.data = Some(inline_images(&app_status, None, data).await);
+            }
+            let response_content = MsgContent {
+                command: None,
+                payload: content.payload.clone(),
+                message: None,
+                api_response: Some(response.to_legacy()),
+            };
+            if let Err(e) = tx.lock().await.send(BotMessage::Chat {
+                from: SERVER_NAME.to_string(),
+                to: "CiRCLE_sat_bot_core".to_string(),
+                content: response_content,
+            }).await {
+                tracing::error!("Failed to send Chat response: {}", e);
+            }
+        }
+    }
 }
 
 pub async fn initialize_app_status() -> AppStatus {
@@ -172,7 +552,12 @@ pub async fn initialize_app_status() -> AppStatus {
     let app_status = AppStatus {
         config: app_config,
         file_tx: tx_filerequest,
-        botmsg_tx: Arc::new(RwLock::new(None)),
+        botmsg_tx: Arc::new(RwLock::new(HashMap::new())),
+        peer_handshakes: Arc::new(RwLock::new(HashMap::new())),
+        push: PushTracker::new(),
+        images: ImageCache::new(),
+        onebot_ws: Arc::new(RwLock::new(None)),
+        quota: QuotaState::new(),
     };
 
     GLOBAL_APP_STATUS.set(app_status.clone()).expect("Failed to set global app status");